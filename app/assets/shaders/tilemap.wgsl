@@ -1,5 +1,7 @@
-#import bevy_sprite::mesh2d_types
 #import bevy_sprite::mesh2d_view_bindings
+#import bevy_sprite::mesh2d_bindings
+
+#import bevy_sprite::mesh2d_functions
 
 #ifdef TONEMAP_IN_SHADER
 #import bevy_core_pipeline::tonemapping
@@ -13,9 +15,37 @@ var color_sampler: sampler;
 var lighting_texture: texture_2d<f32>;
 @group(1) @binding(3)
 var lighting_sampler: sampler;
+@group(1) @binding(4)
+var<uniform> color: vec4<f32>;
+// UV rect (min.xy, max.xy) of the frame currently shown for every atlas index.
+@group(1) @binding(5)
+var frame_texture: texture_2d<f32>;
+
+struct Vertex {
+    @location(0) position: vec3<f32>,
+    @location(1) uv: vec2<f32>,
+    @location(2) atlas_index: u32,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) @interpolate(flat) atlas_index: u32,
+};
+
+@vertex
+fn vertex(vertex: Vertex) -> VertexOutput {
+    var out: VertexOutput;
+    let world_position = mesh2d_position_local_to_world(mesh.model, vec4<f32>(vertex.position, 1.0));
+    out.clip_position = mesh2d_position_world_to_clip(world_position);
+    out.uv = vertex.uv;
+    out.atlas_index = vertex.atlas_index;
+    return out;
+}
 
 struct FragmentInput {
-    #import bevy_sprite::mesh2d_vertex_output
+    @location(0) uv: vec2<f32>,
+    @location(1) @interpolate(flat) atlas_index: u32,
 };
 
 @fragment
@@ -23,10 +53,9 @@ fn fragment(
     @builtin(position) clip_position: vec4<f32>,
     in: FragmentInput
 ) -> @location(0) vec4<f32> {
-    var output_color: vec4<f32> = textureSample(color_texture, color_sampler, in.uv);
-    #ifdef VERTEX_COLORS
-        output_color = output_color * in.color;
-    #endif
+    let frame_rect = textureLoad(frame_texture, vec2<i32>(i32(in.atlas_index), 0), 0);
+    let uv = mix(frame_rect.xy, frame_rect.zw, in.uv);
+    var output_color: vec4<f32> = textureSample(color_texture, color_sampler, uv) * color;
 
     var clip_uv = (clip_position.xy - view.viewport.xy) / view.viewport.zw;
     var lighting_color = textureSample(lighting_texture, lighting_sampler, clip_uv);
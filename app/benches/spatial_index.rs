@@ -0,0 +1,117 @@
+use bevy::prelude::*;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use defendio_app::spatial::SpatialIndex;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+const ENTITIES: u32 = 10_000;
+/// Side of the square the entities are spread over, in tiles.
+const AREA: f32 = 512.0;
+
+#[derive(Component)]
+struct Marker;
+
+fn entities() -> Vec<(Entity, Vec2)> {
+    let mut rng = StdRng::seed_from_u64(0);
+    (0..ENTITIES)
+        .map(|i| {
+            let position = Vec2::new(rng.gen_range(0.0..AREA), rng.gen_range(0.0..AREA));
+            (Entity::from_raw(i), position)
+        })
+        .collect()
+}
+
+fn centers() -> Vec<Vec2> {
+    let mut rng = StdRng::seed_from_u64(1);
+    (0..100)
+        .map(|_| Vec2::new(rng.gen_range(0.0..AREA), rng.gen_range(0.0..AREA)))
+        .collect()
+}
+
+fn radius(c: &mut Criterion) {
+    let entities = entities();
+    let centers = centers();
+    let mut index = SpatialIndex::<Marker>::default();
+    for (entity, position) in entities.iter() {
+        index.insert(*entity, *position);
+    }
+
+    let mut group = c.benchmark_group("radius 10k");
+    group.bench_function("spatial index", |b| {
+        b.iter(|| {
+            for center in centers.iter() {
+                black_box(index.query_radius(*center, 8.0).count());
+            }
+        })
+    });
+    group.bench_function("brute force", |b| {
+        b.iter(|| {
+            for center in centers.iter() {
+                black_box(
+                    entities
+                        .iter()
+                        .filter(|(_, position)| position.distance_squared(*center) <= 64.0)
+                        .count(),
+                );
+            }
+        })
+    });
+    group.finish();
+}
+
+fn nearest(c: &mut Criterion) {
+    let entities = entities();
+    let centers = centers();
+    let mut index = SpatialIndex::<Marker>::default();
+    for (entity, position) in entities.iter() {
+        index.insert(*entity, *position);
+    }
+
+    let mut group = c.benchmark_group("nearest 10k");
+    group.bench_function("spatial index", |b| {
+        b.iter(|| {
+            for center in centers.iter() {
+                black_box(index.nearest(*center, 5));
+            }
+        })
+    });
+    group.bench_function("brute force", |b| {
+        // Selecting only reorders the entities, the same buffer is used for every center.
+        let mut buffer = entities.clone();
+        b.iter(|| {
+            for center in centers.iter() {
+                let compare = |a: &(Entity, Vec2), b: &(Entity, Vec2)| {
+                    a.1.distance_squared(*center)
+                        .total_cmp(&b.1.distance_squared(*center))
+                };
+                buffer.select_nth_unstable_by(5, compare);
+                let mut nearest = buffer[..5].to_vec();
+                nearest.sort_by(compare);
+                black_box(nearest);
+            }
+        })
+    });
+    group.finish();
+}
+
+fn update(c: &mut Criterion) {
+    let entities = entities();
+    let mut index = SpatialIndex::<Marker>::default();
+    for (entity, position) in entities.iter() {
+        index.insert(*entity, *position);
+    }
+
+    // Moving every entity by a fraction of a tile, as enemies do on each step.
+    c.bench_function("update 10k", |b| {
+        let mut offset = 0.0;
+        b.iter(|| {
+            offset += 0.1;
+            for (entity, position) in entities.iter() {
+                index.insert(*entity, *position + Vec2::splat(offset % 1.0));
+            }
+        })
+    });
+}
+
+criterion_group!(benches, radius, nearest, update);
+criterion_main!(benches);
@@ -8,8 +8,6 @@ use bevy::sprite::MaterialMesh2dBundle;
 use bevy::window::WindowResolution;
 use defendio_app::asset::TilemapAssetGroup;
 use defendio_app::camera::{MainCameraBundle, MainCameraComponent, MainCameraPlugin};
-use defendio_app::interaction::input_action::InputActionPlugin;
-use defendio_app::interaction::{InteractionPlugin, MousePosition};
 use defendio_app::lighting::{LightBundle, LightingPlugin};
 use defendio_app::plugin::AppCorePlugin;
 use defendio_app::state::AppState;
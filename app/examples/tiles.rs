@@ -11,11 +11,16 @@ use bevy::window::WindowResolution;
 use defendio_app::asset::TilemapAssetGroup;
 use defendio_app::camera::{MainCameraBundle, MainCameraComponent, MainCameraPlugin};
 use defendio_app::lighting::{LightBundle, LightingPlugin};
+use defendio_app::pathfinding::NavigationTilemapComponent;
 use defendio_app::plugin::AppCorePlugin;
 use defendio_app::state::AppState;
-use defendio_app::tilemap::bundle::TilemapBundle;
+use defendio_app::tilemap::bundle::{TilemapBundle, TilemapLayer};
+use defendio_app::tilemap::data::TilemapData;
+use defendio_app::tilemap::generator::noise::NoiseTilemapGenerator;
+use defendio_app::tilemap::generator::TilemapGeneratorConfig;
 use defendio_app::tilemap::material::TilemapMaterial;
 use defendio_app::tilemap::plugin::TilemapPlugin;
+use defendio_app::tilemap::streaming::TilemapStreamingComponent;
 use defendio_app::world_material::material::WorldMaterial;
 
 #[derive(Component)]
@@ -47,18 +52,25 @@ fn main() -> Result<(), Box<dyn Error + Send + Sync + 'static>> {
 
 fn on_game_state_enter(
     mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<TilemapMaterial>>,
-    mut images: Res<Assets<Image>>,
     tilemap_asset_group: Res<TilemapAssetGroup>,
     texture_atlases: Res<Assets<TextureAtlas>>,
     asset_server: Res<AssetServer>,
 ) {
-    commands.spawn(TilemapBundle::make(
-        &tilemap_asset_group,
-        materials,
-        meshes.as_mut(),
-        &images,
-        &texture_atlases,
-    ));
+    let texture_atlas = texture_atlases
+        .get(&tilemap_asset_group.texture_atlas)
+        .unwrap();
+    commands
+        .spawn(TilemapBundle::new([TilemapLayer::new(
+            "ground",
+            TilemapData::new(),
+            tilemap_asset_group.texture_atlas.clone(),
+            materials.add(TilemapMaterial::new(texture_atlas.texture.clone())),
+        )]))
+        .insert(TilemapStreamingComponent::new(
+            0,
+            NoiseTilemapGenerator,
+            TilemapGeneratorConfig::default().with_seed(rand::random()),
+        ))
+        .insert(NavigationTilemapComponent);
 }
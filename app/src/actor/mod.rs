@@ -0,0 +1,23 @@
+use bevy::prelude::*;
+
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct HealthComponent {
+    pub current: f32,
+    pub max: f32,
+}
+
+/// Circle used for hits and overlaps, in world units.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct ColliderComponent {
+    pub radius: f32,
+}
+
+/// Movement speed in tiles per second.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct SpeedComponent(pub f32);
+
+impl HealthComponent {
+    pub fn new(max: f32) -> Self {
+        HealthComponent { current: max, max }
+    }
+}
@@ -1,4 +1,5 @@
 use crate::asset::TilemapAssetGroup;
+use crate::prototype::PrototypeSet;
 use crate::state::AppState;
 use bevy::asset::LoadState;
 use bevy::prelude::*;
@@ -38,6 +39,7 @@ fn on_load_enter(
     let tilemap_shader: Handle<Shader> = asset_server.load("shaders/tilemap.wgsl");
     let light_shader: Handle<Shader> = asset_server.load("shaders/light.wgsl");
     let world_shader: Handle<Shader> = asset_server.load("shaders/world.wgsl");
+    let prototypes: Handle<PrototypeSet> = asset_server.load("prototypes/base.prototypes.yaml");
 
     commands.insert_resource(AssetLoadState {
         check_timer: Timer::from_seconds(0.1f32, TimerMode::Repeating),
@@ -46,6 +48,7 @@ fn on_load_enter(
             tilemap_shader.clone_untyped(),
             light_shader.clone_untyped(),
             world_shader.clone_untyped(),
+            prototypes.clone_untyped(),
         ],
         loaded_handles: Default::default(),
     });
@@ -0,0 +1,699 @@
+use crate::economy::Economy;
+use crate::error::AppError;
+use crate::input_manager::action::InputAction;
+use crate::input_manager::action_state::InputActionState;
+use crate::input_manager::context::{InputContext, InputContexts};
+use crate::pathfinding::{NavigationGrid, NavigationTilemapComponent};
+use crate::prototype::PrototypeRegistry;
+use crate::tilemap::bundle::TilemapComponent;
+use crate::tilemap::picking::{HoveredTile, TileClicked};
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+
+pub mod plugin;
+
+/// Tiles covered by a building, `size` tiles up and right of its location.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BuildingFootprint {
+    pub size: UVec2,
+}
+
+#[derive(Component, Debug, Clone)]
+pub struct BuildingComponent {
+    pub tilemap: Entity,
+    pub location: IVec2,
+    pub footprint: BuildingFootprint,
+    /// Tower prototype the building was bought as.
+    pub prototype: String,
+}
+
+#[derive(Bundle)]
+pub struct BuildingBundle {
+    building: BuildingComponent,
+    #[bundle]
+    spatial: SpatialBundle,
+}
+
+/// Marks the preview of the selected building under the cursor.
+#[derive(Component)]
+pub struct BuildingGhostComponent;
+
+/// Building placed by the `Select` action, nothing is placed when `None`.
+#[derive(Resource, Debug, Default, Clone)]
+pub struct SelectedBuilding(pub Option<BuildingSelection>);
+
+/// Tower prototype to build and the tiles it covers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BuildingSelection {
+    pub prototype: String,
+    pub footprint: BuildingFootprint,
+}
+
+/// Which building covers each tile of each tilemap.
+#[derive(Resource, Debug, Default, Clone)]
+pub struct OccupancyMap {
+    tilemaps: HashMap<Entity, HashMap<IVec2, Entity>>,
+    /// Tilemap and tiles of each building, freed when it is despawned.
+    buildings: HashMap<Entity, (Entity, Vec<IVec2>)>,
+}
+
+/// Places a building if its footprint is free and buildable and its build cost can be paid.
+#[derive(Debug, Clone)]
+pub struct PlaceBuilding {
+    pub tilemap: Entity,
+    pub location: IVec2,
+    pub footprint: BuildingFootprint,
+    pub prototype: String,
+}
+
+/// Removes and sells the building covering a tile of a tilemap, if any.
+#[derive(Debug, Clone)]
+pub struct RemoveBuilding {
+    pub tilemap: Entity,
+    pub location: IVec2,
+}
+
+pub const GHOST_VALID_COLOR: Color = Color::rgba(0.0, 1.0, 0.0, 0.5);
+pub const GHOST_INVALID_COLOR: Color = Color::rgba(1.0, 0.0, 0.0, 0.5);
+
+impl BuildingFootprint {
+    pub fn new(width: u32, height: u32) -> Self {
+        BuildingFootprint {
+            size: UVec2::new(width, height),
+        }
+    }
+
+    pub fn tiles(&self, location: IVec2) -> impl Iterator<Item = IVec2> {
+        let size = self.size.as_ivec2();
+        (0..size.x).flat_map(move |x| (0..size.y).map(move |y| location + IVec2::new(x, y)))
+    }
+
+    /// Location putting the middle of the footprint on `tile`, rounding down and left.
+    pub fn centered_on(&self, tile: IVec2) -> IVec2 {
+        tile - (self.size.as_ivec2() - IVec2::ONE) / 2
+    }
+
+    /// Middle of the footprint in tilemap space.
+    pub fn center(&self, location: IVec2) -> Vec2 {
+        location.as_vec2() + self.size.as_vec2() / 2.0
+    }
+}
+
+impl BuildingBundle {
+    pub fn new(building: BuildingComponent, transform: Transform) -> Self {
+        BuildingBundle {
+            building,
+            spatial: SpatialBundle::from_transform(transform),
+        }
+    }
+}
+
+impl OccupancyMap {
+    pub fn get(&self, tilemap: Entity, location: IVec2) -> Option<Entity> {
+        self.tilemaps.get(&tilemap)?.get(&location).copied()
+    }
+
+    pub fn is_occupied(&self, tilemap: Entity, location: IVec2) -> bool {
+        self.get(tilemap, location).is_some()
+    }
+
+    /// Whether every tile of the footprint is free and buildable.
+    pub fn can_place(
+        &self,
+        tilemap_entity: Entity,
+        tilemap: &TilemapComponent,
+        footprint: &BuildingFootprint,
+        location: IVec2,
+    ) -> bool {
+        footprint
+            .tiles(location)
+            .all(|tile| !self.is_occupied(tilemap_entity, tile) && is_buildable(tilemap, tile))
+    }
+
+    pub fn insert(
+        &mut self,
+        tilemap: Entity,
+        building: Entity,
+        tiles: impl IntoIterator<Item = IVec2>,
+    ) {
+        let tiles: Vec<IVec2> = tiles.into_iter().collect();
+        let occupied = self.tilemaps.entry(tilemap).or_default();
+        for tile in tiles.iter() {
+            occupied.insert(*tile, building);
+        }
+        self.buildings.insert(building, (tilemap, tiles));
+    }
+
+    /// Frees the tiles of a building, returns its tilemap and tiles.
+    pub fn remove(&mut self, building: Entity) -> Option<(Entity, Vec<IVec2>)> {
+        let (tilemap, tiles) = self.buildings.remove(&building)?;
+        if let Some(occupied) = self.tilemaps.get_mut(&tilemap) {
+            for tile in tiles.iter() {
+                if occupied.get(tile) == Some(&building) {
+                    occupied.remove(tile);
+                }
+            }
+            if occupied.is_empty() {
+                self.tilemaps.remove(&tilemap);
+            }
+        }
+        Some((tilemap, tiles))
+    }
+
+    /// Occupied tiles of a tilemap with the building covering them.
+    pub fn iter(&self, tilemap: Entity) -> impl Iterator<Item = (IVec2, Entity)> + '_ {
+        self.tilemaps
+            .get(&tilemap)
+            .into_iter()
+            .flatten()
+            .map(|(location, entity)| (*location, *entity))
+    }
+}
+
+/// Whether some layer has a tile at `location` and every layer having one allows building.
+pub fn is_buildable(tilemap: &TilemapComponent, location: IVec2) -> bool {
+    let mut tiles = tilemap
+        .layers()
+        .iter()
+        .filter_map(|layer| layer.data.get_tile_properties(location))
+        .peekable();
+    tiles.peek().is_some() && tiles.all(|properties| properties.is_buildable())
+}
+
+/// Transform of a building in the world, at the middle of its footprint.
+fn building_transform(
+    tilemap_transform: &GlobalTransform,
+    footprint: &BuildingFootprint,
+    location: IVec2,
+) -> Transform {
+    tilemap_transform
+        .mul_transform(Transform::from_translation(
+            footprint.center(location).extend(1.0),
+        ))
+        .compute_transform()
+}
+
+/// Activates the [`InputContext::BuildMode`] while a building is selected, `Cancel` deselects it.
+pub fn building_mode_system(
+    mut selected_building: ResMut<SelectedBuilding>,
+    input_action_state: Option<Res<InputActionState>>,
+    input_contexts: Option<ResMut<InputContexts>>,
+) {
+    let cancel = input_action_state.map_or(false, |state| state.just_pressed(InputAction::Cancel));
+    if cancel && selected_building.0.is_some() {
+        selected_building.0 = None;
+    }
+    if !selected_building.is_changed() {
+        return;
+    }
+    if let Some(mut input_contexts) = input_contexts {
+        match selected_building.0 {
+            Some(_) => input_contexts.push(InputContext::BuildMode),
+            None => input_contexts.remove(InputContext::BuildMode),
+        }
+    }
+}
+
+pub fn building_select_system(
+    selected_building: Res<SelectedBuilding>,
+    mut tile_clicked_events: EventReader<TileClicked>,
+    mut place_building_events: EventWriter<PlaceBuilding>,
+) {
+    for TileClicked(pick) in tile_clicked_events.iter() {
+        if let Some(selection) = &selected_building.0 {
+            place_building_events.send(PlaceBuilding {
+                tilemap: pick.tilemap,
+                location: selection.footprint.centered_on(pick.location.tile),
+                footprint: selection.footprint,
+                prototype: selection.prototype.clone(),
+            });
+        }
+    }
+}
+
+/// Places and removes buildings, only the buildings of the [`NavigationTilemapComponent`] tilemap
+/// are obstacles of the [`NavigationGrid`]. With an [`Economy`], placing pays the build cost and
+/// removing refunds the sell value.
+#[allow(clippy::too_many_arguments)]
+pub fn building_command_system(
+    mut commands: Commands,
+    mut place_building_events: EventReader<PlaceBuilding>,
+    mut remove_building_events: EventReader<RemoveBuilding>,
+    mut occupancy_map: ResMut<OccupancyMap>,
+    mut navigation_grid: Option<ResMut<NavigationGrid>>,
+    mut economy: Option<ResMut<Economy>>,
+    registry: Option<Res<PrototypeRegistry>>,
+    tilemap_query: Query<(&GlobalTransform, &TilemapComponent)>,
+    navigation_tilemap_query: Query<(), With<NavigationTilemapComponent>>,
+    building_query: Query<&BuildingComponent>,
+) {
+    for event in remove_building_events.iter() {
+        let entity = match occupancy_map.get(event.tilemap, event.location) {
+            Some(entity) => entity,
+            None => continue,
+        };
+        if let (Some(economy), Some(registry), Ok(building)) = (
+            economy.as_mut(),
+            registry.as_ref(),
+            building_query.get(entity),
+        ) {
+            if let Err(err) = economy.sell(registry, &building.prototype) {
+                error!("{}", err);
+            }
+        }
+        free_tiles(
+            &mut occupancy_map,
+            navigation_grid.as_deref_mut(),
+            &navigation_tilemap_query,
+            entity,
+        );
+        commands.entity(entity).despawn_recursive();
+    }
+
+    for event in place_building_events.iter() {
+        let (tilemap_transform, tilemap) = match tilemap_query.get(event.tilemap) {
+            Ok(tilemap) => tilemap,
+            Err(_) => continue,
+        };
+        if !occupancy_map.can_place(event.tilemap, tilemap, &event.footprint, event.location) {
+            continue;
+        }
+        if let Some(economy) = economy.as_mut() {
+            let result = match registry.as_ref() {
+                Some(registry) => economy.build(registry, &event.prototype),
+                None => Err(AppError::UnknownPrototype(event.prototype.clone())),
+            };
+            if let Err(err) = result {
+                info!("{}", err);
+                continue;
+            }
+        }
+        let entity = commands
+            .spawn(BuildingBundle::new(
+                BuildingComponent {
+                    tilemap: event.tilemap,
+                    location: event.location,
+                    footprint: event.footprint,
+                    prototype: event.prototype.clone(),
+                },
+                building_transform(tilemap_transform, &event.footprint, event.location),
+            ))
+            .id();
+        occupancy_map.insert(event.tilemap, entity, event.footprint.tiles(event.location));
+        if let Some(navigation_grid) = navigation_grid
+            .as_mut()
+            .filter(|_| navigation_tilemap_query.contains(event.tilemap))
+        {
+            for tile in event.footprint.tiles(event.location) {
+                navigation_grid.set_obstacle(tile, true);
+            }
+        }
+    }
+}
+
+/// Frees the tiles of buildings despawned some other way than [`RemoveBuilding`], e.g. by dying.
+pub fn building_removed_system(
+    mut removed_buildings: RemovedComponents<BuildingComponent>,
+    mut occupancy_map: ResMut<OccupancyMap>,
+    mut navigation_grid: Option<ResMut<NavigationGrid>>,
+    navigation_tilemap_query: Query<(), With<NavigationTilemapComponent>>,
+) {
+    for entity in removed_buildings.iter() {
+        free_tiles(
+            &mut occupancy_map,
+            navigation_grid.as_deref_mut(),
+            &navigation_tilemap_query,
+            entity,
+        );
+    }
+}
+
+fn free_tiles(
+    occupancy_map: &mut OccupancyMap,
+    navigation_grid: Option<&mut NavigationGrid>,
+    navigation_tilemap_query: &Query<(), With<NavigationTilemapComponent>>,
+    building: Entity,
+) {
+    let (tilemap, tiles) = match occupancy_map.remove(building) {
+        Some(building) => building,
+        None => return,
+    };
+    if let Some(navigation_grid) =
+        navigation_grid.filter(|_| navigation_tilemap_query.contains(tilemap))
+    {
+        for tile in tiles {
+            navigation_grid.set_obstacle(tile, false);
+        }
+    }
+}
+
+/// Shows the selected building under the cursor, green where it can be placed and red elsewhere.
+pub fn building_ghost_system(
+    mut commands: Commands,
+    selected_building: Res<SelectedBuilding>,
+    hovered_tile: Res<HoveredTile>,
+    occupancy_map: Res<OccupancyMap>,
+    tilemap_query: Query<(&GlobalTransform, &TilemapComponent)>,
+    mut ghost_query: Query<(Entity, &mut Sprite, &mut Transform), With<BuildingGhostComponent>>,
+) {
+    let target = selected_building
+        .0
+        .as_ref()
+        .zip(hovered_tile.0)
+        .and_then(|(selection, pick)| {
+            let footprint = selection.footprint;
+            let (tilemap_transform, tilemap) = tilemap_query.get(pick.tilemap).ok()?;
+            let location = footprint.centered_on(pick.location.tile);
+            let color = if occupancy_map.can_place(pick.tilemap, tilemap, &footprint, location) {
+                GHOST_VALID_COLOR
+            } else {
+                GHOST_INVALID_COLOR
+            };
+            Some((
+                Sprite {
+                    color,
+                    custom_size: Some(footprint.size.as_vec2()),
+                    ..Default::default()
+                },
+                building_transform(tilemap_transform, &footprint, location),
+            ))
+        });
+
+    match (target, ghost_query.get_single_mut()) {
+        (Some((sprite, transform)), Ok((_, mut ghost_sprite, mut ghost_transform))) => {
+            *ghost_sprite = sprite;
+            *ghost_transform = transform;
+        }
+        (Some((sprite, transform)), Err(_)) => {
+            commands.spawn((
+                SpriteBundle {
+                    sprite,
+                    transform,
+                    ..Default::default()
+                },
+                BuildingGhostComponent,
+            ));
+        }
+        (None, Ok((entity, _, _))) => {
+            commands.entity(entity).despawn_recursive();
+        }
+        (None, Err(_)) => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::building::plugin::BuildingPlugin;
+    use crate::economy::{Currency, Funds};
+    use crate::pathfinding::tests::make_tilemap;
+    use crate::prototype::PrototypeSet;
+    use crate::tilemap::bundle::{TilemapBundle, TilemapLayer};
+    use crate::tilemap::picking::{TileLocation, TilePick};
+
+    fn make_app() -> (App, Entity) {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .init_resource::<HoveredTile>()
+            .init_resource::<InputContexts>()
+            .add_event::<TileClicked>()
+            .add_plugin(BuildingPlugin);
+        let tilemap = make_tilemap(&[
+            "....", //
+            "....", //
+            "..#.", //
+            "....", //
+        ]);
+        app.insert_resource(NavigationGrid::from_tilemap(&tilemap));
+        let tilemap_entity = app
+            .world
+            .spawn((
+                TilemapBundle::new([TilemapLayer::new(
+                    "ground",
+                    tilemap,
+                    Default::default(),
+                    Default::default(),
+                )]),
+                NavigationTilemapComponent,
+            ))
+            .id();
+        (app, tilemap_entity)
+    }
+
+    fn pick(tilemap: Entity, tile: IVec2) -> TilePick {
+        TilePick {
+            tilemap,
+            world_position: tile.as_vec2(),
+            location: TileLocation::new(tile),
+        }
+    }
+
+    #[test]
+    fn placement() {
+        let (mut app, tilemap_entity) = make_app();
+        let footprint = BuildingFootprint::new(2, 2);
+        app.world.send_event(PlaceBuilding {
+            tilemap: tilemap_entity,
+            location: IVec2::new(0, 0),
+            footprint,
+            prototype: "arrow_tower".to_string(),
+        });
+        // Overlaps the first building.
+        app.world.send_event(PlaceBuilding {
+            tilemap: tilemap_entity,
+            location: IVec2::new(1, 1),
+            footprint,
+            prototype: "arrow_tower".to_string(),
+        });
+        // Overlaps the wall tile.
+        app.world.send_event(PlaceBuilding {
+            tilemap: tilemap_entity,
+            location: IVec2::new(2, 0),
+            footprint,
+            prototype: "arrow_tower".to_string(),
+        });
+        app.update();
+
+        let occupancy_map = app.world.resource::<OccupancyMap>();
+        let building = occupancy_map.get(tilemap_entity, IVec2::new(1, 1)).unwrap();
+        assert_eq!(occupancy_map.iter(tilemap_entity).count(), 4);
+        assert_eq!(
+            occupancy_map.get(tilemap_entity, IVec2::new(0, 0)),
+            Some(building)
+        );
+        assert!(!occupancy_map.is_occupied(tilemap_entity, IVec2::new(2, 0)));
+        assert_eq!(
+            app.world.get::<Transform>(building).unwrap().translation,
+            Vec3::new(1.0, 1.0, 1.0)
+        );
+        let navigation_grid = app.world.resource::<NavigationGrid>();
+        assert!(!navigation_grid.is_walkable(IVec2::new(1, 0)));
+        assert!(navigation_grid.is_walkable(IVec2::new(2, 0)));
+
+        app.world.send_event(RemoveBuilding {
+            tilemap: tilemap_entity,
+            location: IVec2::new(0, 1),
+        });
+        app.update();
+        assert!(app.world.get_entity(building).is_none());
+        assert_eq!(
+            app.world
+                .resource::<OccupancyMap>()
+                .iter(tilemap_entity)
+                .count(),
+            0
+        );
+        assert!(app
+            .world
+            .resource::<NavigationGrid>()
+            .is_walkable(IVec2::new(1, 0)));
+    }
+
+    #[test]
+    fn despawned_buildings() {
+        let (mut app, tilemap_entity) = make_app();
+        let other_tilemap_entity = app
+            .world
+            .spawn(TilemapBundle::new([TilemapLayer::new(
+                "ground",
+                make_tilemap(&["...."]),
+                Default::default(),
+                Default::default(),
+            )]))
+            .id();
+        // Tilemaps are occupied separately.
+        for tilemap in [tilemap_entity, other_tilemap_entity] {
+            app.world.send_event(PlaceBuilding {
+                tilemap,
+                location: IVec2::new(0, 0),
+                footprint: BuildingFootprint::new(1, 1),
+                prototype: "arrow_tower".to_string(),
+            });
+        }
+        app.update();
+        let occupancy_map = app.world.resource::<OccupancyMap>();
+        let building = occupancy_map.get(tilemap_entity, IVec2::ZERO).unwrap();
+        assert!(occupancy_map.is_occupied(other_tilemap_entity, IVec2::ZERO));
+
+        // Killed buildings free their tiles.
+        app.world.despawn(building);
+        app.update();
+        let occupancy_map = app.world.resource::<OccupancyMap>();
+        assert!(!occupancy_map.is_occupied(tilemap_entity, IVec2::ZERO));
+        assert!(occupancy_map.is_occupied(other_tilemap_entity, IVec2::ZERO));
+        assert!(app
+            .world
+            .resource::<NavigationGrid>()
+            .is_walkable(IVec2::ZERO));
+    }
+
+    #[test]
+    fn other_tilemaps() {
+        let (mut app, tilemap_entity) = make_app();
+        let other_tilemap_entity = app
+            .world
+            .spawn(TilemapBundle::new([TilemapLayer::new(
+                "ground",
+                make_tilemap(&["....", "...."]),
+                Default::default(),
+                Default::default(),
+            )]))
+            .id();
+        let place = |app: &mut App, tilemap| {
+            app.world.send_event(PlaceBuilding {
+                tilemap,
+                location: IVec2::new(1, 1),
+                footprint: BuildingFootprint::new(1, 1),
+                prototype: "arrow_tower".to_string(),
+            });
+            app.update();
+            app.world
+                .resource::<OccupancyMap>()
+                .get(tilemap, IVec2::new(1, 1))
+                .unwrap()
+        };
+        let is_walkable = |app: &App| {
+            app.world
+                .resource::<NavigationGrid>()
+                .is_walkable(IVec2::new(1, 1))
+        };
+
+        // Only the buildings of the navigation tilemap block paths.
+        let other_building = place(&mut app, other_tilemap_entity);
+        assert!(is_walkable(&app));
+        place(&mut app, tilemap_entity);
+        assert!(!is_walkable(&app));
+        app.world.despawn(other_building);
+        app.update();
+        assert!(!is_walkable(&app));
+        app.world.send_event(RemoveBuilding {
+            tilemap: tilemap_entity,
+            location: IVec2::new(1, 1),
+        });
+        app.update();
+        assert!(is_walkable(&app));
+    }
+
+    #[test]
+    fn costs() {
+        let (mut app, tilemap_entity) = make_app();
+        let set = PrototypeSet::parse(
+            r#"
+arrow_tower:
+  kind: tower
+  cost:
+    build: {gold: 50}
+    sell: {gold: 35}
+"#,
+        )
+        .unwrap();
+        let mut economy = Economy::default();
+        economy.grant(Funds::gold(60)).unwrap();
+        app.insert_resource(economy)
+            .insert_resource(PrototypeRegistry::from_sets([&set]).unwrap());
+        let place = |app: &mut App, location, prototype: &str| {
+            app.world.send_event(PlaceBuilding {
+                tilemap: tilemap_entity,
+                location,
+                footprint: BuildingFootprint::new(1, 1),
+                prototype: prototype.to_string(),
+            });
+            app.update();
+            app.world
+                .resource::<OccupancyMap>()
+                .is_occupied(tilemap_entity, location)
+        };
+        let gold = |app: &App| app.world.resource::<Economy>().balance(Currency::Gold);
+
+        assert!(place(&mut app, IVec2::new(0, 0), "arrow_tower"));
+        assert_eq!(gold(&app), 10);
+        // Too expensive and unknown buildings aren't placed.
+        assert!(!place(&mut app, IVec2::new(1, 0), "arrow_tower"));
+        assert!(!place(&mut app, IVec2::new(1, 0), "cannon_tower"));
+        assert_eq!(gold(&app), 10);
+
+        app.world.send_event(RemoveBuilding {
+            tilemap: tilemap_entity,
+            location: IVec2::new(0, 0),
+        });
+        app.update();
+        assert_eq!(gold(&app), 45);
+        assert!(!app
+            .world
+            .resource::<OccupancyMap>()
+            .is_occupied(tilemap_entity, IVec2::new(0, 0)));
+    }
+
+    #[test]
+    fn select_and_ghost() {
+        let (mut app, tilemap_entity) = make_app();
+        let footprint = BuildingFootprint::new(3, 1);
+        app.insert_resource(SelectedBuilding(Some(BuildingSelection {
+            prototype: "arrow_tower".to_string(),
+            footprint,
+        })));
+        app.insert_resource(HoveredTile(Some(pick(tilemap_entity, IVec2::new(2, 1)))));
+        app.update();
+        assert!(app
+            .world
+            .resource::<InputContexts>()
+            .is_active(InputContext::BuildMode));
+
+        let mut ghost_query = app
+            .world
+            .query_filtered::<&Sprite, With<BuildingGhostComponent>>();
+        assert_eq!(
+            ghost_query.single(&app.world).color,
+            GHOST_INVALID_COLOR,
+            "the wall is under the footprint"
+        );
+
+        app.world
+            .resource_mut::<HoveredTile>()
+            .0
+            .replace(pick(tilemap_entity, IVec2::new(2, 0)));
+        app.update();
+        assert_eq!(ghost_query.single(&app.world).color, GHOST_VALID_COLOR);
+
+        app.world
+            .send_event(TileClicked(pick(tilemap_entity, IVec2::new(2, 0))));
+        app.update();
+        let occupancy_map = app.world.resource::<OccupancyMap>();
+        assert!(occupancy_map.is_occupied(tilemap_entity, IVec2::new(1, 0)));
+        assert!(occupancy_map.is_occupied(tilemap_entity, IVec2::new(3, 0)));
+        assert_eq!(ghost_query.single(&app.world).color, GHOST_INVALID_COLOR);
+
+        app.world.resource_mut::<SelectedBuilding>().0 = None;
+        app.update();
+        assert!(ghost_query.iter(&app.world).next().is_none());
+        assert!(!app
+            .world
+            .resource::<InputContexts>()
+            .is_active(InputContext::BuildMode));
+
+        let tilemap = app.world.get::<TilemapComponent>(tilemap_entity).unwrap();
+        assert!(is_buildable(tilemap, IVec2::new(0, 3)));
+        assert!(!is_buildable(tilemap, IVec2::new(2, 1)));
+        assert!(!is_buildable(tilemap, IVec2::new(-1, 0)));
+    }
+}
@@ -0,0 +1,28 @@
+use crate::building::{
+    building_command_system, building_ghost_system, building_mode_system, building_removed_system,
+    building_select_system, OccupancyMap, PlaceBuilding, RemoveBuilding, SelectedBuilding,
+};
+use crate::tilemap::picking::tile_picking_system;
+use bevy::prelude::*;
+
+pub struct BuildingPlugin;
+
+impl Plugin for BuildingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<OccupancyMap>()
+            .init_resource::<SelectedBuilding>()
+            .add_event::<PlaceBuilding>()
+            .add_event::<RemoveBuilding>()
+            .add_systems(
+                (
+                    building_mode_system,
+                    building_select_system,
+                    building_command_system,
+                    building_removed_system,
+                    building_ghost_system,
+                )
+                    .chain()
+                    .after(tile_picking_system),
+            );
+    }
+}
@@ -1,5 +1,5 @@
-use crate::input_manager::action::InputAction;
 use crate::input_manager::action_state::InputActionState;
+use crate::input_manager::axis::InputAxis;
 use crate::state::AppState;
 use bevy::core_pipeline::bloom::BloomSettings;
 use bevy::core_pipeline::clear_color::ClearColorConfig;
@@ -74,17 +74,7 @@ fn move_camera(
     // let player_transform = player_query.single();
 
     // camera.target_position = Some(player_transform.translation);
-    let mut dv = Vec3::ZERO;
-    if input_action_state.pressed(InputAction::Left) {
-        dv.x -= 1.0f32;
-    } else if input_action_state.pressed(InputAction::Right) {
-        dv.x += 1.0f32;
-    }
-    if input_action_state.pressed(InputAction::Up) {
-        dv.y += 1.0f32;
-    } else if input_action_state.pressed(InputAction::Down) {
-        dv.y -= 1.0f32;
-    }
+    let dv = input_action_state.dual_axis(InputAxis::Pan).extend(0.0);
     camera_transform.translation += (dv) * CAMERA_BASE_SPEED * time.delta_seconds();
 }
 
@@ -0,0 +1,342 @@
+use crate::actor::HealthComponent;
+use crate::prototype::commands::SpawnPrototypeExt;
+use crate::prototype::PrototypeComponent;
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use serde::Deserialize;
+use std::time::Duration;
+
+pub mod plugin;
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum DamageType {
+    #[default]
+    Physical,
+    Fire,
+    Frost,
+    Lightning,
+    Poison,
+}
+
+/// Flat reduction of physical damage.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct ArmorComponent(pub f32);
+
+/// Multipliers of the damage taken per type, 1 for missing types.
+#[derive(Component, Debug, Clone, Default, PartialEq)]
+pub struct ResistancesComponent(pub HashMap<DamageType, f32>);
+
+/// Ignores damage for `duration` after every hit.
+#[derive(Component, Debug, Clone, PartialEq)]
+pub struct InvulnerabilityComponent {
+    pub duration: Duration,
+    remaining: Duration,
+}
+
+/// Paid and dropped when the entity dies.
+#[derive(Component, Debug, Clone, Default, PartialEq)]
+pub struct BountyComponent {
+    pub bounty: u32,
+    pub drops: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DamageEvent {
+    pub source: Option<Entity>,
+    pub target: Entity,
+    pub amount: f32,
+    pub damage_type: DamageType,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Died {
+    pub entity: Entity,
+    pub prototype: Option<String>,
+    /// Source of the killing blow.
+    pub killer: Option<Entity>,
+    pub position: Vec3,
+    pub bounty: u32,
+    pub drops: Vec<String>,
+}
+
+/// Last entity to damage an entity.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct LastHitComponent(pub Option<Entity>);
+
+/// Systems of the combat pipeline, run in this order on the fixed timestep.
+#[derive(SystemSet, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CombatSet {
+    Damage,
+    Death,
+}
+
+impl ResistancesComponent {
+    pub fn get(&self, damage_type: DamageType) -> f32 {
+        self.0.get(&damage_type).copied().unwrap_or(1.0)
+    }
+}
+
+impl InvulnerabilityComponent {
+    pub fn new(duration: Duration) -> Self {
+        InvulnerabilityComponent {
+            duration,
+            remaining: Duration::ZERO,
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        !self.remaining.is_zero()
+    }
+
+    pub fn remaining(&self) -> Duration {
+        self.remaining
+    }
+}
+
+/// Damage left after resistances and armor.
+pub fn compute_damage(
+    amount: f32,
+    damage_type: DamageType,
+    armor: Option<&ArmorComponent>,
+    resistances: Option<&ResistancesComponent>,
+) -> f32 {
+    let mut damage = amount * resistances.map_or(1.0, |resistances| resistances.get(damage_type));
+    if damage_type == DamageType::Physical {
+        damage -= armor.map_or(0.0, |armor| armor.0);
+    }
+    damage.max(0.0)
+}
+
+pub fn invulnerability_system(
+    fixed_time: Res<FixedTime>,
+    mut query: Query<&mut InvulnerabilityComponent>,
+) {
+    for mut invulnerability in query.iter_mut() {
+        if invulnerability.is_active() {
+            invulnerability.remaining = invulnerability.remaining.saturating_sub(fixed_time.period);
+        }
+    }
+}
+
+/// Applies the damage events in the order they were sent.
+#[allow(clippy::type_complexity)]
+pub fn damage_system(
+    mut commands: Commands,
+    mut damage_events: EventReader<DamageEvent>,
+    mut query: Query<(
+        &mut HealthComponent,
+        Option<&ArmorComponent>,
+        Option<&ResistancesComponent>,
+        Option<&mut InvulnerabilityComponent>,
+    )>,
+) {
+    for event in damage_events.iter() {
+        let (mut health, armor, resistances, invulnerability) = match query.get_mut(event.target) {
+            Ok(target) => target,
+            Err(_) => continue,
+        };
+        if health.current <= 0.0 {
+            continue;
+        }
+        let damage = compute_damage(event.amount, event.damage_type, armor, resistances);
+        if damage <= 0.0 {
+            continue;
+        }
+        if let Some(mut invulnerability) = invulnerability {
+            if invulnerability.is_active() {
+                continue;
+            }
+            invulnerability.remaining = invulnerability.duration;
+        }
+        health.current -= damage;
+        commands
+            .entity(event.target)
+            .insert(LastHitComponent(event.source));
+    }
+}
+
+/// Sends [`Died`] for entities without health left, spawns their drops and despawns them.
+#[allow(clippy::type_complexity)]
+pub fn death_system(
+    mut commands: Commands,
+    query: Query<(
+        Entity,
+        &HealthComponent,
+        Option<&GlobalTransform>,
+        Option<&Transform>,
+        Option<&PrototypeComponent>,
+        Option<&BountyComponent>,
+        Option<&LastHitComponent>,
+    )>,
+    mut died_events: EventWriter<Died>,
+) {
+    let mut dead: Vec<_> = query
+        .iter()
+        .filter(|(_, health, ..)| health.current <= 0.0)
+        .collect();
+    // Entities are sorted so events don't depend on the query order.
+    dead.sort_by_key(|(entity, ..)| *entity);
+    for (entity, _, global_transform, transform, prototype, bounty, last_hit) in dead {
+        // Entities without a transform still die, where they are doesn't matter then.
+        let position = match (global_transform, transform) {
+            (Some(global_transform), _) => global_transform.translation(),
+            (None, Some(transform)) => transform.translation,
+            (None, None) => Vec3::ZERO,
+        };
+        let bounty = bounty.cloned().unwrap_or_default();
+        for drop in bounty.drops.iter() {
+            commands.spawn_prototype(drop, Transform::from_translation(position));
+        }
+        died_events.send(Died {
+            entity,
+            prototype: prototype.map(|prototype| prototype.id.clone()),
+            killer: last_hit.and_then(|last_hit| last_hit.0),
+            position,
+            bounty: bounty.bounty,
+            drops: bounty.drops,
+        });
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::combat::plugin::CombatPlugin;
+    use crate::prototype::commands::InsertPrototypeExt;
+    use crate::prototype::tests::PROTOTYPES;
+    use crate::prototype::{PrototypeRegistry, PrototypeSet};
+    use bevy::ecs::event::ManualEventReader;
+    use bevy::ecs::system::CommandQueue;
+
+    fn make_app() -> App {
+        let mut app = App::new();
+        let set = PrototypeSet::parse(PROTOTYPES).unwrap();
+        app.add_plugins(MinimalPlugins)
+            .add_plugin(CombatPlugin)
+            .insert_resource(FixedTime::new(Duration::from_millis(100)))
+            .insert_resource(PrototypeRegistry::from_sets([&set]).unwrap());
+        app
+    }
+
+    fn spawn(app: &mut App, id: &str, position: Vec3) -> Entity {
+        let mut queue = CommandQueue::default();
+        let entity = Commands::new(&mut queue, &app.world)
+            .spawn(SpatialBundle {
+                global_transform: GlobalTransform::from_translation(position),
+                ..Default::default()
+            })
+            .insert_prototype(id)
+            .id();
+        queue.apply(&mut app.world);
+        entity
+    }
+
+    fn send_damage(app: &mut App, target: Entity, amount: f32, damage_type: DamageType) {
+        app.world.send_event(DamageEvent {
+            source: None,
+            target,
+            amount,
+            damage_type,
+        });
+    }
+
+    #[test]
+    fn damage() {
+        let resistances = ResistancesComponent([(DamageType::Fire, 0.5)].into_iter().collect());
+        let armor = ArmorComponent(2.0);
+        assert_eq!(
+            compute_damage(10.0, DamageType::Physical, Some(&armor), Some(&resistances)),
+            8.0
+        );
+        assert_eq!(
+            compute_damage(10.0, DamageType::Fire, Some(&armor), Some(&resistances)),
+            5.0
+        );
+        assert_eq!(
+            compute_damage(1.0, DamageType::Physical, Some(&armor), None),
+            0.0
+        );
+
+        let mut app = make_app();
+        let slime = spawn(&mut app, "slime", Vec3::ZERO);
+        let health = |app: &App| app.world.get::<HealthComponent>(slime).unwrap().current;
+
+        send_damage(&mut app, slime, 4.0, DamageType::Physical);
+        // Ignored by the invulnerability of the first hit.
+        send_damage(&mut app, slime, 4.0, DamageType::Physical);
+        app.world.run_schedule(CoreSchedule::FixedUpdate);
+        assert_eq!(health(&app), 18.0);
+
+        send_damage(&mut app, slime, 4.0, DamageType::Fire);
+        app.world.run_schedule(CoreSchedule::FixedUpdate);
+        assert_eq!(health(&app), 18.0);
+        // The invulnerability lasts 0.2s.
+        app.world.run_schedule(CoreSchedule::FixedUpdate);
+        send_damage(&mut app, slime, 4.0, DamageType::Fire);
+        app.world.run_schedule(CoreSchedule::FixedUpdate);
+        assert_eq!(health(&app), 16.0);
+    }
+
+    #[test]
+    fn death() {
+        let mut app = make_app();
+        let slime = spawn(&mut app, "slime", Vec3::new(3.0, 4.0, 0.0));
+        let tower = spawn(&mut app, "arrow_tower", Vec3::ZERO);
+
+        app.world.send_event(DamageEvent {
+            source: Some(tower),
+            target: slime,
+            amount: 100.0,
+            damage_type: DamageType::Lightning,
+        });
+        send_damage(&mut app, slime, 100.0, DamageType::Lightning);
+        app.world.run_schedule(CoreSchedule::FixedUpdate);
+        app.world.run_schedule(CoreSchedule::FixedUpdate);
+
+        let mut died_reader = ManualEventReader::<Died>::default();
+        let died: Vec<Died> = died_reader
+            .iter(app.world.resource::<Events<Died>>())
+            .cloned()
+            .collect();
+        assert_eq!(
+            died,
+            vec![Died {
+                entity: slime,
+                prototype: Some("slime".to_string()),
+                killer: Some(tower),
+                position: Vec3::new(3.0, 4.0, 0.0),
+                bounty: 5,
+                drops: vec!["gem".to_string()],
+            }]
+        );
+        assert!(app.world.get_entity(slime).is_none());
+        let mut drop_query = app.world.query::<(&PrototypeComponent, &Transform)>();
+        let drops: Vec<_> = drop_query
+            .iter(&app.world)
+            .filter(|(prototype, _)| prototype.id == "gem")
+            .map(|(_, transform)| transform.translation)
+            .collect();
+        assert_eq!(drops, vec![Vec3::new(3.0, 4.0, 0.0)]);
+
+        // Entities without a global transform die too.
+        let bare = app
+            .world
+            .spawn((
+                HealthComponent {
+                    current: 0.0,
+                    max: 1.0,
+                },
+                Transform::from_xyz(1.0, 2.0, 0.0),
+            ))
+            .id();
+        app.world.run_schedule(CoreSchedule::FixedUpdate);
+        let died: Vec<(Entity, Vec3)> = died_reader
+            .iter(app.world.resource::<Events<Died>>())
+            .map(|died| (died.entity, died.position))
+            .collect();
+        assert_eq!(died, vec![(bare, Vec3::new(1.0, 2.0, 0.0))]);
+        assert!(app.world.get_entity(bare).is_none());
+    }
+}
@@ -0,0 +1,23 @@
+use crate::combat::{
+    damage_system, death_system, invulnerability_system, CombatSet, DamageEvent, Died,
+};
+use bevy::prelude::*;
+
+pub struct CombatPlugin;
+
+impl Plugin for CombatPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<DamageEvent>()
+            .add_event::<Died>()
+            .add_systems(
+                (
+                    invulnerability_system.in_set(CombatSet::Damage),
+                    damage_system.in_set(CombatSet::Damage),
+                    apply_system_buffers,
+                    death_system.in_set(CombatSet::Death),
+                )
+                    .chain()
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            );
+    }
+}
@@ -0,0 +1,399 @@
+use crate::combat::Died;
+use crate::error::{AppError, AppResult};
+use crate::prototype::{CostDefinition, PrototypeRegistry};
+use crate::wave::{WaveCleared, WaveSchedule, WaveSpawnerComponent};
+use bevy::prelude::*;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+pub mod plugin;
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum Currency {
+    Gold,
+    Crystal,
+}
+
+/// Amounts of each currency, 0 for missing currencies.
+///
+/// Ordered by currency so that errors and logs don't depend on hashing.
+#[derive(Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(transparent)]
+pub struct Funds(pub BTreeMap<Currency, u32>);
+
+/// A change of the balances, see [`Economy::apply`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transaction {
+    pub reason: TransactionReason,
+    /// Added to the balances.
+    pub credit: Funds,
+    /// Removed from the balances, the transaction fails when a balance is too low.
+    pub debit: Funds,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TransactionReason {
+    /// Funds given outside of play, like the starting funds.
+    Grant,
+    /// Placing a tower of this prototype.
+    Build(String),
+    /// Upgrading a tower of a prototype.
+    Upgrade {
+        prototype: String,
+        upgrade: String,
+    },
+    /// Selling a tower of this prototype.
+    Sell(String),
+    Bounty {
+        entity: Entity,
+        prototype: Option<String>,
+    },
+    WaveIncome {
+        spawner: Entity,
+        wave: usize,
+    },
+}
+
+/// Balances of the player, only changed through transactions which are all kept in a log.
+///
+/// Replaying the log with [`Economy::replay`] gives back the same balances.
+#[derive(Resource, Debug, Clone, Default, PartialEq)]
+pub struct Economy {
+    balances: Funds,
+    log: Vec<Transaction>,
+}
+
+impl Funds {
+    pub fn new(amounts: impl IntoIterator<Item = (Currency, u32)>) -> Self {
+        Funds(amounts.into_iter().collect())
+    }
+
+    pub fn gold(amount: u32) -> Self {
+        Funds::new([(Currency::Gold, amount)])
+    }
+
+    pub fn get(&self, currency: Currency) -> u32 {
+        self.0.get(&currency).copied().unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.values().all(|amount| *amount == 0)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Currency, u32)> + '_ {
+        self.0.iter().map(|(currency, amount)| (*currency, *amount))
+    }
+}
+
+impl Transaction {
+    pub fn credit(reason: TransactionReason, credit: Funds) -> Self {
+        Transaction {
+            reason,
+            credit,
+            debit: Funds::default(),
+        }
+    }
+
+    pub fn debit(reason: TransactionReason, debit: Funds) -> Self {
+        Transaction {
+            reason,
+            credit: Funds::default(),
+            debit,
+        }
+    }
+}
+
+impl Economy {
+    pub fn replay(transactions: impl IntoIterator<Item = Transaction>) -> AppResult<Self> {
+        let mut economy = Economy::default();
+        for transaction in transactions {
+            economy.apply(transaction)?;
+        }
+        Ok(economy)
+    }
+
+    pub fn balance(&self, currency: Currency) -> u32 {
+        self.balances.get(currency)
+    }
+
+    pub fn balances(&self) -> &Funds {
+        &self.balances
+    }
+
+    /// Every applied transaction, oldest first.
+    pub fn log(&self) -> &[Transaction] {
+        &self.log
+    }
+
+    /// Fails with [`AppError::InsufficientFunds`] for the first currency lacking.
+    pub fn check(&self, debit: &Funds) -> AppResult<()> {
+        for (currency, required) in debit.iter() {
+            let available = self.balance(currency);
+            if available < required {
+                return Err(AppError::InsufficientFunds {
+                    currency,
+                    required,
+                    available,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Applies and logs a transaction, nothing changes when it fails.
+    pub fn apply(&mut self, transaction: Transaction) -> AppResult<()> {
+        self.check(&transaction.debit)?;
+        for (currency, amount) in transaction.debit.iter() {
+            *self.balances.0.entry(currency).or_default() -= amount;
+        }
+        for (currency, amount) in transaction.credit.iter() {
+            let balance = self.balances.0.entry(currency).or_default();
+            *balance = balance.saturating_add(amount);
+        }
+        self.log.push(transaction);
+        Ok(())
+    }
+
+    pub fn grant(&mut self, funds: Funds) -> AppResult<()> {
+        self.apply(Transaction::credit(TransactionReason::Grant, funds))
+    }
+
+    /// Pays the build cost of a tower prototype.
+    pub fn build(&mut self, registry: &PrototypeRegistry, id: &str) -> AppResult<()> {
+        let cost = prototype_cost(registry, id)?;
+        self.apply(Transaction::debit(
+            TransactionReason::Build(id.to_string()),
+            cost.build.clone(),
+        ))
+    }
+
+    /// Pays for an upgrade of a tower prototype, see
+    /// [`apply_upgrade`](crate::upgrade::apply_upgrade) to also apply it.
+    pub fn upgrade(
+        &mut self,
+        registry: &PrototypeRegistry,
+        prototype: &str,
+        upgrade: &str,
+    ) -> AppResult<()> {
+        let cost = registry
+            .get(prototype)
+            .ok_or_else(|| AppError::UnknownPrototype(prototype.to_string()))?
+            .definition
+            .upgrade(upgrade)
+            .ok_or_else(|| AppError::UnknownUpgrade {
+                prototype: prototype.to_string(),
+                upgrade: upgrade.to_string(),
+            })?
+            .cost
+            .clone();
+        self.apply(Transaction::debit(
+            TransactionReason::Upgrade {
+                prototype: prototype.to_string(),
+                upgrade: upgrade.to_string(),
+            },
+            cost,
+        ))
+    }
+
+    /// Refunds the sell value of a tower prototype.
+    pub fn sell(&mut self, registry: &PrototypeRegistry, id: &str) -> AppResult<()> {
+        let cost = prototype_cost(registry, id)?;
+        self.apply(Transaction::credit(
+            TransactionReason::Sell(id.to_string()),
+            cost.sell.clone(),
+        ))
+    }
+}
+
+fn prototype_cost<'a>(registry: &'a PrototypeRegistry, id: &str) -> AppResult<&'a CostDefinition> {
+    registry
+        .get(id)
+        .map(|prototype| &prototype.definition.cost)
+        .ok_or_else(|| AppError::UnknownPrototype(id.to_string()))
+}
+
+/// Pays the bounty of dead enemies in gold.
+pub fn bounty_system(mut economy: ResMut<Economy>, mut died_events: EventReader<Died>) {
+    for died in died_events.iter() {
+        if died.bounty == 0 {
+            continue;
+        }
+        let transaction = Transaction::credit(
+            TransactionReason::Bounty {
+                entity: died.entity,
+                prototype: died.prototype.clone(),
+            },
+            Funds::gold(died.bounty),
+        );
+        if let Err(err) = economy.apply(transaction) {
+            error!("{}", err);
+        }
+    }
+}
+
+/// Pays the income of cleared waves.
+pub fn wave_income_system(
+    mut economy: ResMut<Economy>,
+    mut wave_cleared_events: EventReader<WaveCleared>,
+    schedules: Res<Assets<WaveSchedule>>,
+    spawner_query: Query<&WaveSpawnerComponent>,
+) {
+    for cleared in wave_cleared_events.iter() {
+        let income = match spawner_query
+            .get(cleared.spawner)
+            .ok()
+            .and_then(|spawner| schedules.get(&spawner.schedule))
+            .and_then(|schedule| schedule.waves.get(cleared.wave))
+        {
+            Some(wave) => wave.income.clone(),
+            None => continue,
+        };
+        if income.is_empty() {
+            continue;
+        }
+        let transaction = Transaction::credit(
+            TransactionReason::WaveIncome {
+                spawner: cleared.spawner,
+                wave: cleared.wave,
+            },
+            income,
+        );
+        if let Err(err) = economy.apply(transaction) {
+            error!("{}", err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::combat::plugin::CombatPlugin;
+    use crate::economy::plugin::EconomyPlugin;
+    use crate::prototype::PrototypeSet;
+    use crate::wave::plugin::WavePlugin;
+    use std::time::Duration;
+
+    const PROTOTYPES: &str = r#"
+arrow_tower:
+  kind: tower
+  cost:
+    build: {gold: 50}
+    sell: {gold: 35}
+crystal_tower:
+  kind: tower
+  cost:
+    build: {gold: 100, crystal: 2}
+    sell: {gold: 70, crystal: 1}
+  upgrades:
+    - {id: focus, tier: 1, cost: {crystal: 1}}
+"#;
+
+    #[test]
+    fn transactions() {
+        let set = PrototypeSet::parse(PROTOTYPES).unwrap();
+        let registry = PrototypeRegistry::from_sets([&set]).unwrap();
+        let mut economy = Economy::default();
+        economy.grant(Funds::gold(120)).unwrap();
+        economy.build(&registry, "arrow_tower").unwrap();
+        assert_eq!(economy.balance(Currency::Gold), 70);
+
+        assert!(matches!(
+            economy.build(&registry, "crystal_tower"),
+            Err(AppError::InsufficientFunds {
+                currency: Currency::Gold,
+                required: 100,
+                available: 70,
+            })
+        ));
+        economy.sell(&registry, "arrow_tower").unwrap();
+        assert!(matches!(
+            economy.build(&registry, "crystal_tower"),
+            Err(AppError::InsufficientFunds {
+                currency: Currency::Crystal,
+                required: 2,
+                available: 0,
+            })
+        ));
+        assert!(matches!(
+            economy.upgrade(&registry, "cannon_tower", "focus"),
+            Err(AppError::UnknownPrototype(id)) if id == "cannon_tower"
+        ));
+        assert!(matches!(
+            economy.upgrade(&registry, "crystal_tower", "blur"),
+            Err(AppError::UnknownUpgrade { .. })
+        ));
+        economy.grant(Funds::new([(Currency::Crystal, 3)])).unwrap();
+        economy.build(&registry, "crystal_tower").unwrap();
+        economy
+            .upgrade(&registry, "crystal_tower", "focus")
+            .unwrap();
+        assert_eq!(
+            economy.balances(),
+            &Funds::new([(Currency::Gold, 5), (Currency::Crystal, 0)])
+        );
+
+        // Failed transactions aren't logged.
+        let reasons: Vec<_> = economy
+            .log()
+            .iter()
+            .map(|transaction| transaction.reason.clone())
+            .collect();
+        assert_eq!(
+            reasons,
+            vec![
+                TransactionReason::Grant,
+                TransactionReason::Build("arrow_tower".to_string()),
+                TransactionReason::Sell("arrow_tower".to_string()),
+                TransactionReason::Grant,
+                TransactionReason::Build("crystal_tower".to_string()),
+                TransactionReason::Upgrade {
+                    prototype: "crystal_tower".to_string(),
+                    upgrade: "focus".to_string(),
+                },
+            ]
+        );
+        assert_eq!(
+            Economy::replay(economy.log().iter().cloned()).unwrap(),
+            economy
+        );
+    }
+
+    #[test]
+    fn income() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_plugin(AssetPlugin::default())
+            .add_plugin(CombatPlugin)
+            .add_plugin(WavePlugin)
+            .add_plugin(EconomyPlugin)
+            .insert_resource(FixedTime::new(Duration::from_millis(100)));
+        let schedule = WaveSchedule::parse(
+            "waves: [{delay: 1000, groups: [], income: {gold: 10, crystal: 1}}]",
+        )
+        .unwrap();
+        let schedule = app
+            .world
+            .resource_mut::<Assets<WaveSchedule>>()
+            .add(schedule);
+        let spawner = app.world.spawn(WaveSpawnerComponent::new(schedule)).id();
+
+        app.world.send_event(Died {
+            entity: Entity::from_raw(42),
+            prototype: Some("slime".to_string()),
+            killer: None,
+            position: Vec3::ZERO,
+            bounty: 5,
+            drops: Vec::new(),
+        });
+        app.world.send_event(WaveCleared { spawner, wave: 0 });
+        app.world.run_schedule(CoreSchedule::FixedUpdate);
+
+        let economy = app.world.resource::<Economy>();
+        assert_eq!(
+            economy.balances(),
+            &Funds::new([(Currency::Gold, 15), (Currency::Crystal, 1)])
+        );
+        assert_eq!(economy.log().len(), 2);
+    }
+}
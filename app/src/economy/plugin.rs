@@ -0,0 +1,18 @@
+use crate::combat::CombatSet;
+use crate::economy::{bounty_system, wave_income_system, Economy};
+use crate::wave::wave_spawner_system;
+use bevy::prelude::*;
+
+pub struct EconomyPlugin;
+
+impl Plugin for EconomyPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Economy>().add_systems(
+            (
+                bounty_system.after(CombatSet::Death),
+                wave_income_system.after(wave_spawner_system),
+            )
+                .in_schedule(CoreSchedule::FixedUpdate),
+        );
+    }
+}
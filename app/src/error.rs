@@ -1,3 +1,6 @@
+use crate::economy::Currency;
+use crate::input_manager::action::InputAction;
+use bevy::prelude::Entity;
 use config::ConfigError;
 use thiserror::Error;
 
@@ -5,6 +8,39 @@ use thiserror::Error;
 pub enum AppError {
     #[error("internal error: {0}")]
     Internal(#[from] Box<dyn std::error::Error + Send + Sync>),
+    #[error("invalid prototypes: {0}")]
+    PrototypeSyntax(serde_yaml::Error),
+    #[error("prototype `{0}` is defined more than once")]
+    DuplicatePrototype(String),
+    #[error("unknown parent `{parent}` of prototype `{id}`")]
+    UnknownPrototypeParent { id: String, parent: String },
+    #[error("prototype `{0}` inherits from itself")]
+    PrototypeCycle(String),
+    #[error("invalid prototype `{id}`: {message}")]
+    InvalidPrototype { id: String, message: String },
+    #[error("unknown prototype `{0}`")]
+    UnknownPrototype(String),
+    #[error("unknown upgrade `{upgrade}` of prototype `{prototype}`")]
+    UnknownUpgrade { prototype: String, upgrade: String },
+    #[error("upgrade `{upgrade}` isn't available for {entity:?}")]
+    UnavailableUpgrade { entity: Entity, upgrade: String },
+    #[error("not enough {currency:?}: {required} required, {available} available")]
+    InsufficientFunds {
+        currency: Currency,
+        required: u32,
+        available: u32,
+    },
+    #[error("invalid wave schedule: {0}")]
+    WaveSyntax(serde_yaml::Error),
+    #[error("invalid wave {wave}: {message}")]
+    InvalidWave { wave: usize, message: String },
+    #[error("invalid input bindings: {0}")]
+    BindingsSyntax(serde_yaml::Error),
+    #[error("input of {action:?} is already bound to {conflict:?}")]
+    BindingConflict {
+        action: InputAction,
+        conflict: InputAction,
+    },
 }
 
 macro_rules! impl_internal_errors {
@@ -18,6 +54,6 @@ macro_rules! impl_internal_errors {
         )*
     };
 }
-impl_internal_errors!(ConfigError,);
+impl_internal_errors!(ConfigError, std::io::Error);
 
 pub type AppResult<T> = Result<T, AppError>;
@@ -1,15 +1,33 @@
 use array_init::array_init;
-use bevy::input::keyboard::KeyboardInput;
+use bevy::ecs::system::SystemParam;
 use bevy::prelude::*;
-use bevy::utils::HashMap;
+use bevy::utils::{HashMap, HashSet};
+use bevy::window::PrimaryWindow;
 
 use num_enum::TryFromPrimitive;
+use serde::{Deserialize, Serialize};
 
-use crate::input_manager::action_state::{
-    ActionDataArray, InputActionData, InputActionTriggerState,
+use crate::error::{AppError, AppResult};
+use crate::input_manager::action_state::{ActionDataArray, InputActionTriggerState};
+use crate::input_manager::axis::{
+    AxisBinding, AxisDataArray, AxisDefinition, DualAxisBinding, InputAxis,
 };
+use crate::input_manager::gamepad::ActiveGamepad;
 
-#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, TryFromPrimitive)]
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Hash,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    TryFromPrimitive,
+    Serialize,
+    Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
 #[repr(u8)]
 pub enum InputAction {
     // Movement
@@ -19,57 +37,681 @@ pub enum InputAction {
     Down,
     // Interaction
     Select,
+    /// Adds to the selection instead of replacing it.
+    SelectMore,
+    Undo,
+    /// Leaves the build mode or closes a menu.
+    Cancel,
+    ToggleConsole,
 }
 
 // TODO: optimize storage
+/// Bindings of every action, an action is active while any of its bindings is.
 #[derive(Resource)]
-pub struct InputActionMap(HashMap<InputAction, InputActionTrigger>);
+pub struct InputActionMap {
+    bindings: HashMap<InputAction, Vec<InputBinding>>,
+    axes: HashMap<InputAxis, AxisDefinition>,
+    /// Analog inputs below it are ignored, the rest of their range is scaled to `0.0..=1.0`.
+    deadzone: f32,
+    capture: Option<InputCapture>,
+}
+
+/// Pending capture of the next input, see [`InputActionMap::capture`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InputCapture {
+    pub action: InputAction,
+    /// Index of the replaced binding, the binding is added past the last one.
+    pub slot: usize,
+}
 
+/// An input captured for an action.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BindingCaptured {
+    pub action: InputAction,
+    pub binding: InputBinding,
+    /// Other actions already bound to the input, the binding isn't assigned when there are any.
+    pub conflicts: Vec<InputAction>,
+}
+
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
 pub enum InputActionTrigger {
     KeyCode(KeyCode),
     MouseButton(MouseButton),
+    /// Analog for the triggers of the gamepad.
+    GamepadButton(GamepadButtonType),
+    /// Pressed while the axis is past the deadzone in the direction.
+    GamepadAxis(GamepadAxisType, AxisDirection),
+}
+
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AxisDirection {
+    Positive,
+    Negative,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "BindingDefinition", into = "BindingDefinition")]
+pub enum InputBinding {
+    Trigger(InputActionTrigger),
+    /// Active while any of the bindings is, like either control key.
+    AnyOf(Vec<InputBinding>),
+    /// Active while all of the bindings are, like the Ctrl+Z chord. Never active when empty.
+    AllOf(Vec<InputBinding>),
+}
+
+/// Serialized form of an [`InputBinding`], like `{key: A}` or `{all_of: [{key: LShift}, {mouse: Left}]}`.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+enum BindingDefinition {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    GamepadButton(GamepadButtonType),
+    GamepadAxis {
+        axis: GamepadAxisType,
+        direction: AxisDirection,
+    },
+    AnyOf(Vec<InputBinding>),
+    AllOf(Vec<InputBinding>),
+}
+
+/// Keys only captured alone when released without another input.
+const MODIFIER_KEYS: [KeyCode; 6] = [
+    KeyCode::LShift,
+    KeyCode::RShift,
+    KeyCode::LControl,
+    KeyCode::RControl,
+    KeyCode::LAlt,
+    KeyCode::RAlt,
+];
+
+/// Triggers holding a binding active and its analog value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ActiveBinding {
+    pub triggers: Vec<InputActionTrigger>,
+    /// In `0.0..=1.0`, 1 for digital inputs.
+    pub value: f32,
+}
+
+/// Actions active during an update, see [`InputActionMap::active_actions`].
+#[derive(Debug, Default)]
+pub(crate) struct ActiveActions {
+    pub bindings: HashMap<InputAction, ActiveBinding>,
+    /// Actions whose binding is active but shadowed by a longer chord.
+    pub shadowed: HashSet<InputAction>,
+}
+
+pub const DEFAULT_DEADZONE: f32 = 0.2;
+
+/// Axis values past it are captured, so that a resting stick isn't.
+const CAPTURE_AXIS_THRESHOLD: f32 = 0.5;
+
+/// Inputs the triggers are read from.
+pub struct InputSources<'a> {
+    pub key_codes: &'a Input<KeyCode>,
+    pub mouse_buttons: &'a Input<MouseButton>,
+    /// `None` without a connected gamepad, gamepad triggers are then released.
+    pub gamepad: Option<GamepadSources<'a>>,
+    /// `None` while the cursor is outside of the window.
+    pub cursor: Option<Cursor>,
+}
+
+pub struct GamepadSources<'a> {
+    pub gamepad: Gamepad,
+    pub buttons: &'a Input<GamepadButton>,
+    pub button_axes: &'a Axis<GamepadButton>,
+    pub axes: &'a Axis<GamepadAxis>,
+}
+
+pub struct Cursor {
+    pub position: Vec2,
+    pub window_size: Vec2,
+}
+
+/// Resources of the [`InputSources`] for systems.
+#[derive(SystemParam)]
+pub struct InputDevices<'w, 's> {
+    window_query: Query<'w, 's, &'static Window, With<PrimaryWindow>>,
+    key_codes: Res<'w, Input<KeyCode>>,
+    mouse_buttons: Res<'w, Input<MouseButton>>,
+    gamepad_buttons: Res<'w, Input<GamepadButton>>,
+    gamepad_button_axes: Res<'w, Axis<GamepadButton>>,
+    gamepad_axes: Res<'w, Axis<GamepadAxis>>,
+    active_gamepad: Res<'w, ActiveGamepad>,
 }
 
 impl InputActionMap {
+    /// Map without bindings nor axes.
+    pub fn empty() -> Self {
+        InputActionMap {
+            bindings: HashMap::default(),
+            axes: HashMap::default(),
+            deadzone: DEFAULT_DEADZONE,
+            capture: None,
+        }
+    }
+
+    pub fn from_bindings(
+        bindings: impl IntoIterator<Item = (InputAction, Vec<InputBinding>)>,
+    ) -> Self {
+        let mut map = InputActionMap::empty();
+        for (action, bindings) in bindings {
+            map.set_bindings(action, bindings);
+        }
+        map
+    }
+
+    pub fn bindings(&self, action: InputAction) -> &[InputBinding] {
+        self.bindings.get(&action).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (InputAction, &[InputBinding])> + '_ {
+        self.bindings
+            .iter()
+            .map(|(action, bindings)| (*action, bindings.as_slice()))
+    }
+
+    pub fn set_bindings(&mut self, action: InputAction, bindings: Vec<InputBinding>) {
+        self.bindings.insert(action, bindings);
+    }
+
+    pub fn axis(&self, axis: InputAxis) -> Option<&AxisDefinition> {
+        self.axes.get(&axis)
+    }
+
+    pub fn axes(&self) -> impl Iterator<Item = (InputAxis, &AxisDefinition)> + '_ {
+        self.axes
+            .iter()
+            .map(|(axis, definition)| (*axis, definition))
+    }
+
+    pub fn set_axis(&mut self, axis: InputAxis, definition: AxisDefinition) {
+        self.axes.insert(axis, definition);
+    }
+
+    pub fn deadzone(&self) -> f32 {
+        self.deadzone
+    }
+
+    pub fn set_deadzone(&mut self, deadzone: f32) {
+        self.deadzone = deadzone.clamp(0.0, 0.95);
+    }
+
+    /// Other actions having `binding`.
+    pub fn conflicts(&self, action: InputAction, binding: &InputBinding) -> Vec<InputAction> {
+        let mut conflicts: Vec<InputAction> = self
+            .iter()
+            .filter(|(other, bindings)| *other != action && bindings.contains(binding))
+            .map(|(other, _)| other)
+            .collect();
+        conflicts.sort();
+        conflicts
+    }
+
+    /// Sets the binding of `action` at `slot`, or adds it past the last binding.
+    ///
+    /// Fails with [`AppError::BindingConflict`] when another action has the binding.
+    pub fn bind(
+        &mut self,
+        action: InputAction,
+        slot: usize,
+        binding: impl Into<InputBinding>,
+    ) -> AppResult<()> {
+        let binding = binding.into();
+        if let Some(conflict) = self.conflicts(action, &binding).first() {
+            return Err(AppError::BindingConflict {
+                action,
+                conflict: *conflict,
+            });
+        }
+        self.bind_replacing(action, slot, binding);
+        Ok(())
+    }
+
+    /// Like [`InputActionMap::bind`], removing the binding from the other actions.
+    pub fn bind_replacing(
+        &mut self,
+        action: InputAction,
+        slot: usize,
+        binding: impl Into<InputBinding>,
+    ) {
+        let binding = binding.into();
+        for (other, bindings) in self.bindings.iter_mut() {
+            if *other != action {
+                bindings.retain(|other| *other != binding);
+            }
+        }
+        let bindings = self.bindings.entry(action).or_default();
+        match bindings.get_mut(slot) {
+            Some(current) => *current = binding,
+            None => bindings.push(binding),
+        }
+    }
+
+    /// Binds the next input to `action` at `slot`, see [`InputActionMap::bind`].
+    ///
+    /// Held modifiers make a chord with the input, a modifier is bound alone when released
+    /// without another input.
+    pub fn capture(&mut self, action: InputAction, slot: usize) {
+        self.capture = Some(InputCapture { action, slot });
+    }
+
+    pub fn cancel_capture(&mut self) {
+        self.capture = None;
+    }
+
+    pub fn capturing(&self) -> Option<InputCapture> {
+        self.capture
+    }
+
+    /// Ends the pending capture when an input was pressed.
+    pub fn capture_input(&mut self, sources: &InputSources) -> Option<BindingCaptured> {
+        let capture = self.capture?;
+        let modifier = |key_code: &KeyCode| MODIFIER_KEYS.contains(key_code);
+        let trigger = sources
+            .key_codes
+            .get_just_pressed()
+            .find(|key_code| !modifier(key_code))
+            .map(|key_code| InputActionTrigger::KeyCode(*key_code))
+            .or_else(|| {
+                sources
+                    .mouse_buttons
+                    .get_just_pressed()
+                    .next()
+                    .map(|mouse_button| InputActionTrigger::MouseButton(*mouse_button))
+            })
+            .or_else(|| {
+                let gamepad = sources.gamepad.as_ref()?;
+                gamepad
+                    .buttons
+                    .get_just_pressed()
+                    .find(|button| button.gamepad == gamepad.gamepad)
+                    .map(|button| InputActionTrigger::GamepadButton(button.button_type))
+                    .or_else(|| {
+                        gamepad.axes.devices().find_map(|axis| {
+                            let value = gamepad.axes.get(*axis).unwrap_or(0.0);
+                            (axis.gamepad == gamepad.gamepad
+                                && value.abs() > CAPTURE_AXIS_THRESHOLD)
+                                .then(|| {
+                                    InputActionTrigger::GamepadAxis(
+                                        axis.axis_type,
+                                        AxisDirection::of(value),
+                                    )
+                                })
+                        })
+                    })
+            });
+        let binding = match trigger {
+            Some(trigger) => {
+                let held = |key_codes: [KeyCode; 2]| {
+                    key_codes
+                        .iter()
+                        .any(|key_code| sources.key_codes.pressed(*key_code))
+                };
+                let mut chord: Vec<InputBinding> = [
+                    (
+                        [KeyCode::LControl, KeyCode::RControl],
+                        InputBinding::control(),
+                    ),
+                    ([KeyCode::LShift, KeyCode::RShift], InputBinding::shift()),
+                    ([KeyCode::LAlt, KeyCode::RAlt], InputBinding::alt()),
+                ]
+                .into_iter()
+                .filter(|(key_codes, _)| held(*key_codes))
+                .map(|(_, binding)| binding)
+                .collect();
+                if chord.is_empty() {
+                    trigger.into()
+                } else {
+                    chord.push(trigger.into());
+                    InputBinding::AllOf(chord)
+                }
+            }
+            None => {
+                let released = sources
+                    .key_codes
+                    .get_just_released()
+                    .find(|key_code| modifier(key_code))?;
+                if sources.key_codes.get_pressed().next().is_some()
+                    || sources.mouse_buttons.get_pressed().next().is_some()
+                {
+                    return None;
+                }
+                (*released).into()
+            }
+        };
+
+        self.capture = None;
+        let conflicts = self.conflicts(capture.action, &binding);
+        if conflicts.is_empty() {
+            self.bind_replacing(capture.action, capture.slot, binding.clone());
+        }
+        Some(BindingCaptured {
+            action: capture.action,
+            binding,
+            conflicts,
+        })
+    }
+
+    /// States of the actions following `previous`, the states of the last update.
     pub fn get_states(
         &self,
-        input_key_codes: &Input<KeyCode>,
-        input_mouse_buttons: &Input<MouseButton>,
+        sources: &InputSources,
+        previous: &ActionDataArray,
     ) -> ActionDataArray {
-        let mut state: ActionDataArray = array_init(|_| Default::default());
-        for (action, trigger) in self.0.iter() {
-            match trigger {
-                InputActionTrigger::KeyCode(key_code) => {
-                    if input_key_codes.pressed(*key_code) {
-                        state[*action as usize].state = InputActionTriggerState::JustPressed;
+        let active = self.active_actions(|trigger| trigger.value(sources, self.deadzone));
+        action_states(&active, previous)
+    }
+
+    /// Values of the axes, `actions` being the states of the actions.
+    pub fn get_axes(&self, sources: &InputSources, actions: &ActionDataArray) -> AxisDataArray {
+        let mut axes = AxisDataArray::default();
+        for (axis, definition) in self.axes.iter() {
+            axes[*axis as usize] = definition.value(sources, actions, self.deadzone);
+        }
+        axes
+    }
+
+    /// Active actions, the actions shadowed by a longer chord being apart so that Shift+click
+    /// doesn't also trigger the action bound to click.
+    pub(crate) fn active_actions(
+        &self,
+        value_of: impl Fn(&InputActionTrigger) -> f32,
+    ) -> ActiveActions {
+        let active: HashMap<InputAction, ActiveBinding> = self
+            .bindings
+            .iter()
+            .filter_map(|(action, bindings)| {
+                let active = ActiveBinding::any(
+                    bindings
+                        .iter()
+                        .filter_map(|binding| binding.active(&value_of)),
+                )?;
+                Some((*action, active))
+            })
+            .collect();
+        let mut actions = ActiveActions::default();
+        for (action, binding) in active.iter() {
+            let shadowed = active.values().any(|other| {
+                other.triggers.len() > binding.triggers.len()
+                    && binding
+                        .triggers
+                        .iter()
+                        .all(|trigger| other.triggers.contains(trigger))
+            });
+            if shadowed {
+                actions.shadowed.insert(*action);
+            } else {
+                actions.bindings.insert(*action, binding.clone());
+            }
+        }
+        actions
+    }
+}
+
+/// States of the actions following `previous`, `active` being the actions active now.
+///
+/// A shadowed action stays released until its inputs are, so that releasing Shift before the
+/// click of a Shift+click doesn't press the action bound to click.
+pub(crate) fn action_states(active: &ActiveActions, previous: &ActionDataArray) -> ActionDataArray {
+    let mut state: ActionDataArray = array_init(|_| Default::default());
+    for (i, data) in state.iter_mut().enumerate() {
+        let action = match InputAction::try_from(i as u8) {
+            Ok(action) => action,
+            Err(_) => break,
+        };
+        let current = active.bindings.get(&action);
+        data.shadowed =
+            active.shadowed.contains(&action) || (previous[i].shadowed && current.is_some());
+        let current = current.filter(|_| !data.shadowed);
+        data.state = match (previous[i].state.pressed(), current.is_some()) {
+            (false, true) => InputActionTriggerState::JustPressed,
+            (true, true) => InputActionTriggerState::Pressed,
+            (true, false) => InputActionTriggerState::JustReleased,
+            (false, false) => InputActionTriggerState::Released,
+        };
+        data.value = current.map_or(0.0, |binding| binding.value);
+    }
+    state
+}
+
+impl<'w, 's> InputDevices<'w, 's> {
+    pub fn sources(&self) -> InputSources {
+        let cursor = self.window_query.get_single().ok().and_then(|window| {
+            Some(Cursor {
+                position: window.cursor_position()?,
+                window_size: Vec2::new(window.width(), window.height()),
+            })
+        });
+        InputSources {
+            cursor,
+            key_codes: &self.key_codes,
+            mouse_buttons: &self.mouse_buttons,
+            gamepad: self.active_gamepad.0.map(|gamepad| GamepadSources {
+                gamepad,
+                buttons: &self.gamepad_buttons,
+                button_axes: &self.gamepad_button_axes,
+                axes: &self.gamepad_axes,
+            }),
+        }
+    }
+}
+
+impl InputActionTrigger {
+    /// Value in `0.0..=1.0`, 0 when released.
+    pub fn value(&self, sources: &InputSources, deadzone: f32) -> f32 {
+        let pressed = |pressed: bool| if pressed { 1.0 } else { 0.0 };
+        match self {
+            InputActionTrigger::KeyCode(key_code) => pressed(sources.key_codes.pressed(*key_code)),
+            InputActionTrigger::MouseButton(mouse_button) => {
+                pressed(sources.mouse_buttons.pressed(*mouse_button))
+            }
+            InputActionTrigger::GamepadButton(button_type) => match &sources.gamepad {
+                Some(gamepad) => {
+                    let button = GamepadButton::new(gamepad.gamepad, *button_type);
+                    match gamepad.button_axes.get(button) {
+                        Some(value) => live_value(value, deadzone),
+                        None => pressed(gamepad.buttons.pressed(button)),
                     }
                 }
-                InputActionTrigger::MouseButton(mouse_button) => {
-                    if input_mouse_buttons.pressed(*mouse_button) {
-                        state[*action as usize].state = InputActionTriggerState::JustPressed;
-                    }
+                None => 0.0,
+            },
+            InputActionTrigger::GamepadAxis(axis_type, direction) => match &sources.gamepad {
+                Some(gamepad) => {
+                    let value = gamepad
+                        .axes
+                        .get(GamepadAxis::new(gamepad.gamepad, *axis_type))
+                        .unwrap_or(0.0);
+                    live_value(value * direction.sign(), deadzone)
                 }
+                None => 0.0,
+            },
+        }
+    }
+}
+
+impl AxisDirection {
+    pub fn of(value: f32) -> Self {
+        if value < 0.0 {
+            AxisDirection::Negative
+        } else {
+            AxisDirection::Positive
+        }
+    }
+
+    pub fn sign(&self) -> f32 {
+        match self {
+            AxisDirection::Positive => 1.0,
+            AxisDirection::Negative => -1.0,
+        }
+    }
+}
+
+impl ActiveBinding {
+    /// The binding with the most triggers, at the highest value of the bindings.
+    fn any(bindings: impl IntoIterator<Item = ActiveBinding>) -> Option<Self> {
+        bindings.into_iter().reduce(|a, b| {
+            let value = a.value.max(b.value);
+            let triggers = if b.triggers.len() > a.triggers.len() {
+                b.triggers
+            } else {
+                a.triggers
+            };
+            ActiveBinding { triggers, value }
+        })
+    }
+}
+
+/// Value past the deadzone scaled to `0.0..=1.0`.
+pub(crate) fn live_value(value: f32, deadzone: f32) -> f32 {
+    if value <= deadzone {
+        0.0
+    } else {
+        ((value - deadzone) / (1.0 - deadzone)).min(1.0)
+    }
+}
+
+impl InputBinding {
+    /// Chord of the bindings.
+    pub fn all_of(bindings: impl IntoIterator<Item = impl Into<InputBinding>>) -> Self {
+        InputBinding::AllOf(bindings.into_iter().map(Into::into).collect())
+    }
+
+    pub fn any_of(bindings: impl IntoIterator<Item = impl Into<InputBinding>>) -> Self {
+        InputBinding::AnyOf(bindings.into_iter().map(Into::into).collect())
+    }
+
+    pub fn control() -> Self {
+        InputBinding::any_of([KeyCode::LControl, KeyCode::RControl])
+    }
+
+    pub fn shift() -> Self {
+        InputBinding::any_of([KeyCode::LShift, KeyCode::RShift])
+    }
+
+    pub fn alt() -> Self {
+        InputBinding::any_of([KeyCode::LAlt, KeyCode::RAlt])
+    }
+
+    /// Triggers keeping the binding active and its value, `None` when it isn't.
+    ///
+    /// Chords are as strong as their weakest input.
+    pub fn active(&self, value_of: &impl Fn(&InputActionTrigger) -> f32) -> Option<ActiveBinding> {
+        match self {
+            InputBinding::Trigger(trigger) => {
+                let value = value_of(trigger);
+                (value > 0.0).then(|| ActiveBinding {
+                    triggers: vec![*trigger],
+                    value,
+                })
+            }
+            InputBinding::AnyOf(bindings) => ActiveBinding::any(
+                bindings
+                    .iter()
+                    .filter_map(|binding| binding.active(value_of)),
+            ),
+            InputBinding::AllOf(bindings) if bindings.is_empty() => None,
+            InputBinding::AllOf(bindings) => {
+                let mut all = ActiveBinding {
+                    triggers: Vec::new(),
+                    value: 1.0,
+                };
+                for binding in bindings {
+                    let active = binding.active(value_of)?;
+                    all.triggers.extend(active.triggers);
+                    all.value = all.value.min(active.value);
+                }
+                Some(all)
             }
         }
-        state
     }
 }
 
 impl Default for InputActionMap {
     fn default() -> Self {
-        InputActionMap(
-            [
+        InputActionMap {
+            bindings: [
                 // Movement
-                (InputAction::Left, KeyCode::A.into()),
-                (InputAction::Right, KeyCode::D.into()),
-                (InputAction::Up, KeyCode::W.into()),
-                (InputAction::Down, KeyCode::S.into()),
-                // Combat
-                (InputAction::Select, MouseButton::Left.into()),
+                (
+                    InputAction::Left,
+                    vec![
+                        KeyCode::A.into(),
+                        KeyCode::Left.into(),
+                        GamepadButtonType::DPadLeft.into(),
+                        (GamepadAxisType::LeftStickX, AxisDirection::Negative).into(),
+                    ],
+                ),
+                (
+                    InputAction::Right,
+                    vec![
+                        KeyCode::D.into(),
+                        KeyCode::Right.into(),
+                        GamepadButtonType::DPadRight.into(),
+                        (GamepadAxisType::LeftStickX, AxisDirection::Positive).into(),
+                    ],
+                ),
+                (
+                    InputAction::Up,
+                    vec![
+                        KeyCode::W.into(),
+                        KeyCode::Up.into(),
+                        GamepadButtonType::DPadUp.into(),
+                        (GamepadAxisType::LeftStickY, AxisDirection::Positive).into(),
+                    ],
+                ),
+                (
+                    InputAction::Down,
+                    vec![
+                        KeyCode::S.into(),
+                        KeyCode::Down.into(),
+                        GamepadButtonType::DPadDown.into(),
+                        (GamepadAxisType::LeftStickY, AxisDirection::Negative).into(),
+                    ],
+                ),
+                // Interaction
+                (
+                    InputAction::Select,
+                    vec![MouseButton::Left.into(), GamepadButtonType::South.into()],
+                ),
+                (
+                    InputAction::SelectMore,
+                    vec![InputBinding::all_of([
+                        InputBinding::shift(),
+                        MouseButton::Left.into(),
+                    ])],
+                ),
+                (
+                    InputAction::Undo,
+                    vec![
+                        InputBinding::all_of([InputBinding::control(), KeyCode::Z.into()]),
+                        GamepadButtonType::West.into(),
+                    ],
+                ),
+                (InputAction::ToggleConsole, vec![KeyCode::Grave.into()]),
             ]
-                .into_iter()
-                .collect(),
-        )
+            .into_iter()
+            .collect(),
+            axes: [(
+                InputAxis::Pan,
+                AxisDefinition::new(vec![
+                    DualAxisBinding::Axes {
+                        x: Some(AxisBinding::Actions {
+                            negative: InputAction::Left,
+                            positive: InputAction::Right,
+                        }),
+                        y: Some(AxisBinding::Actions {
+                            negative: InputAction::Down,
+                            positive: InputAction::Up,
+                        }),
+                    },
+                    DualAxisBinding::MouseEdge { margin: 8.0 },
+                ]),
+            )]
+            .into_iter()
+            .collect(),
+            deadzone: DEFAULT_DEADZONE,
+            capture: None,
+        }
     }
 }
 
@@ -84,3 +726,256 @@ impl From<MouseButton> for InputActionTrigger {
         InputActionTrigger::MouseButton(mouse_button)
     }
 }
+
+impl From<GamepadButtonType> for InputActionTrigger {
+    fn from(button_type: GamepadButtonType) -> Self {
+        InputActionTrigger::GamepadButton(button_type)
+    }
+}
+
+impl From<(GamepadAxisType, AxisDirection)> for InputActionTrigger {
+    fn from((axis_type, direction): (GamepadAxisType, AxisDirection)) -> Self {
+        InputActionTrigger::GamepadAxis(axis_type, direction)
+    }
+}
+
+impl From<InputActionTrigger> for InputBinding {
+    fn from(trigger: InputActionTrigger) -> Self {
+        InputBinding::Trigger(trigger)
+    }
+}
+
+impl TryFrom<BindingDefinition> for InputBinding {
+    type Error = &'static str;
+
+    fn try_from(definition: BindingDefinition) -> Result<Self, Self::Error> {
+        Ok(match definition {
+            BindingDefinition::Key(key_code) => key_code.into(),
+            BindingDefinition::Mouse(mouse_button) => mouse_button.into(),
+            BindingDefinition::GamepadButton(button_type) => button_type.into(),
+            BindingDefinition::GamepadAxis { axis, direction } => (axis, direction).into(),
+            BindingDefinition::AnyOf(bindings) => InputBinding::AnyOf(bindings),
+            BindingDefinition::AllOf(bindings) if bindings.is_empty() => {
+                return Err("`all_of` can't be empty")
+            }
+            BindingDefinition::AllOf(bindings) => InputBinding::AllOf(bindings),
+        })
+    }
+}
+
+impl From<InputBinding> for BindingDefinition {
+    fn from(binding: InputBinding) -> Self {
+        match binding {
+            InputBinding::Trigger(InputActionTrigger::KeyCode(key_code)) => {
+                BindingDefinition::Key(key_code)
+            }
+            InputBinding::Trigger(InputActionTrigger::MouseButton(mouse_button)) => {
+                BindingDefinition::Mouse(mouse_button)
+            }
+            InputBinding::Trigger(InputActionTrigger::GamepadButton(button_type)) => {
+                BindingDefinition::GamepadButton(button_type)
+            }
+            InputBinding::Trigger(InputActionTrigger::GamepadAxis(axis, direction)) => {
+                BindingDefinition::GamepadAxis { axis, direction }
+            }
+            InputBinding::AnyOf(bindings) => BindingDefinition::AnyOf(bindings),
+            InputBinding::AllOf(bindings) => BindingDefinition::AllOf(bindings),
+        }
+    }
+}
+
+impl From<KeyCode> for InputBinding {
+    fn from(key_code: KeyCode) -> Self {
+        InputBinding::Trigger(key_code.into())
+    }
+}
+
+impl From<MouseButton> for InputBinding {
+    fn from(mouse_button: MouseButton) -> Self {
+        InputBinding::Trigger(mouse_button.into())
+    }
+}
+
+impl From<GamepadButtonType> for InputBinding {
+    fn from(button_type: GamepadButtonType) -> Self {
+        InputBinding::Trigger(button_type.into())
+    }
+}
+
+impl From<(GamepadAxisType, AxisDirection)> for InputBinding {
+    fn from(axis: (GamepadAxisType, AxisDirection)) -> Self {
+        InputBinding::Trigger(axis.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chords() {
+        let map = InputActionMap::default();
+        let mut key_codes = Input::<KeyCode>::default();
+        let mut mouse_buttons = Input::<MouseButton>::default();
+        let mut previous: ActionDataArray = array_init(|_| Default::default());
+        let mut states = |key_codes: &mut Input<KeyCode>,
+                          mouse_buttons: &mut Input<MouseButton>,
+                          actions: &[InputAction]| {
+            let states = map.get_states(
+                &InputSources {
+                    key_codes,
+                    mouse_buttons,
+                    gamepad: None,
+                    cursor: None,
+                },
+                &previous,
+            );
+            previous = states.clone();
+            key_codes.clear();
+            mouse_buttons.clear();
+            actions
+                .iter()
+                .map(|action| states[*action as usize].state)
+                .collect::<Vec<_>>()
+        };
+        use InputActionTriggerState::*;
+
+        // Either binding of an action.
+        key_codes.press(KeyCode::Left);
+        assert_eq!(
+            states(&mut key_codes, &mut mouse_buttons, &[InputAction::Left]),
+            [JustPressed]
+        );
+        key_codes.press(KeyCode::A);
+        assert_eq!(
+            states(&mut key_codes, &mut mouse_buttons, &[InputAction::Left]),
+            [Pressed]
+        );
+        key_codes.release(KeyCode::Left);
+        key_codes.release(KeyCode::A);
+        assert_eq!(
+            states(&mut key_codes, &mut mouse_buttons, &[InputAction::Left]),
+            [JustReleased]
+        );
+
+        // The Shift+click chord shadows click.
+        let select = [InputAction::Select, InputAction::SelectMore];
+        mouse_buttons.press(MouseButton::Left);
+        assert_eq!(
+            states(&mut key_codes, &mut mouse_buttons, &select),
+            [JustPressed, Released]
+        );
+        key_codes.press(KeyCode::RShift);
+        assert_eq!(
+            states(&mut key_codes, &mut mouse_buttons, &select),
+            [JustReleased, JustPressed]
+        );
+        assert_eq!(
+            states(&mut key_codes, &mut mouse_buttons, &select),
+            [Released, Pressed]
+        );
+        // Click stays released until pressed again.
+        key_codes.release(KeyCode::RShift);
+        assert_eq!(
+            states(&mut key_codes, &mut mouse_buttons, &select),
+            [Released, JustReleased]
+        );
+        assert_eq!(
+            states(&mut key_codes, &mut mouse_buttons, &select),
+            [Released, Released]
+        );
+        mouse_buttons.release(MouseButton::Left);
+        assert_eq!(
+            states(&mut key_codes, &mut mouse_buttons, &select),
+            [Released, Released]
+        );
+        mouse_buttons.press(MouseButton::Left);
+        assert_eq!(
+            states(&mut key_codes, &mut mouse_buttons, &select),
+            [JustPressed, Released]
+        );
+        mouse_buttons.release(MouseButton::Left);
+        states(&mut key_codes, &mut mouse_buttons, &select);
+
+        // Ctrl+Z needs both keys.
+        key_codes.press(KeyCode::Z);
+        assert_eq!(
+            states(&mut key_codes, &mut mouse_buttons, &[InputAction::Undo]),
+            [Released]
+        );
+        key_codes.press(KeyCode::LControl);
+        assert_eq!(
+            states(&mut key_codes, &mut mouse_buttons, &[InputAction::Undo]),
+            [JustPressed]
+        );
+    }
+
+    #[test]
+    fn empty_chords() {
+        let value_of = |_: &InputActionTrigger| 1.0;
+        assert_eq!(InputBinding::AllOf(Vec::new()).active(&value_of), None);
+        assert!(InputBinding::all_of([KeyCode::A])
+            .active(&value_of)
+            .is_some());
+    }
+
+    #[test]
+    fn gamepad() {
+        let map = InputActionMap::default();
+        let key_codes = Input::<KeyCode>::default();
+        let mouse_buttons = Input::<MouseButton>::default();
+        let buttons = Input::<GamepadButton>::default();
+        let mut button_axes = Axis::<GamepadButton>::default();
+        let mut axes = Axis::<GamepadAxis>::default();
+        let gamepad = Gamepad::new(1);
+        let mut previous: ActionDataArray = array_init(|_| Default::default());
+        let mut states =
+            |button_axes: &Axis<GamepadButton>, axes: &Axis<GamepadAxis>, connected: bool| {
+                let states = map.get_states(
+                    &InputSources {
+                        key_codes: &key_codes,
+                        mouse_buttons: &mouse_buttons,
+                        gamepad: connected.then_some(GamepadSources {
+                            gamepad,
+                            buttons: &buttons,
+                            button_axes,
+                            axes,
+                        }),
+                        cursor: None,
+                    },
+                    &previous,
+                );
+                previous = states.clone();
+                [InputAction::Left, InputAction::Right, InputAction::Select].map(|action| {
+                    let data = &states[action as usize];
+                    (data.state, (data.value * 100.0).round() / 100.0)
+                })
+            };
+        use InputActionTriggerState::*;
+
+        // Sticks are analog past the deadzone.
+        axes.set(GamepadAxis::new(gamepad, GamepadAxisType::LeftStickX), -0.1);
+        assert_eq!(
+            states(&button_axes, &axes, true),
+            [(Released, 0.0), (Released, 0.0), (Released, 0.0)]
+        );
+        axes.set(GamepadAxis::new(gamepad, GamepadAxisType::LeftStickX), -0.6);
+        button_axes.set(GamepadButton::new(gamepad, GamepadButtonType::South), 1.0);
+        assert_eq!(
+            states(&button_axes, &axes, true),
+            [(JustPressed, 0.5), (Released, 0.0), (JustPressed, 1.0)]
+        );
+        // Other gamepads are ignored.
+        let other = Gamepad::new(2);
+        axes.set(GamepadAxis::new(other, GamepadAxisType::LeftStickX), 1.0);
+        assert_eq!(
+            states(&button_axes, &axes, true),
+            [(Pressed, 0.5), (Released, 0.0), (Pressed, 1.0)]
+        );
+        // Disconnecting releases the actions.
+        assert_eq!(
+            states(&button_axes, &axes, false),
+            [(JustReleased, 0.0), (Released, 0.0), (JustReleased, 0.0)]
+        );
+    }
+}
@@ -3,19 +3,26 @@ use bevy::input::keyboard::KeyboardInput;
 use bevy::input::ButtonState;
 use bevy::prelude::*;
 
-use crate::input_manager::action::{InputAction, InputActionMap};
+use crate::input_manager::action::{InputAction, InputActionMap, InputDevices};
+use crate::input_manager::axis::{AxisDataArray, InputAxis};
+use crate::input_manager::context::{InputContexts, UiMouseCapture};
 
 pub type ActionDataArray = [InputActionData; 16usize];
 
 #[derive(Resource)]
 pub struct InputActionState {
     actions: ActionDataArray,
+    axes: AxisDataArray,
 }
 
 #[derive(Debug, Clone)]
 pub struct InputActionData {
     pub state: InputActionTriggerState,
+    /// Analog value in `0.0..=1.0`, 1 for digital inputs while pressed.
+    pub value: f32,
     pub consumed: bool,
+    /// Shadowed by a longer chord, stays released until its inputs are.
+    pub shadowed: bool,
 }
 
 #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
@@ -31,6 +38,7 @@ impl Default for InputActionState {
     fn default() -> Self {
         InputActionState {
             actions: array_init(|_| Default::default()),
+            axes: Default::default(),
         }
     }
 }
@@ -38,17 +46,22 @@ impl Default for InputActionState {
 impl InputActionState {
     fn update(&mut self, next_actions: ActionDataArray) {
         for (i, next_action) in next_actions.into_iter().enumerate() {
-            let action = if let Ok(action) = InputAction::try_from(i as u8) {
-                action
-            } else {
+            if InputAction::try_from(i as u8).is_err() {
                 break;
-            };
-            match next_action.state {
-                InputActionTriggerState::Pressed => self.press(action),
-                InputActionTriggerState::JustPressed => self.press(action),
-                InputActionTriggerState::Released => self.release(action),
-                InputActionTriggerState::JustReleased => self.release(action),
             }
+            let action_data = &mut self.actions[i];
+            action_data.shadowed = next_action.shadowed;
+            // A consumed action stays released until its inputs are.
+            if action_data.consumed {
+                if next_action.state.pressed() {
+                    action_data.state.tick();
+                    action_data.value = 0.0;
+                    continue;
+                }
+                action_data.consumed = false;
+            }
+            action_data.state = next_action.state;
+            action_data.value = next_action.value;
         }
     }
 
@@ -56,24 +69,21 @@ impl InputActionState {
         &mut self.actions[action as usize]
     }
 
-    fn press(&mut self, action: InputAction) {
-        let action_data = &mut self.actions[action as usize];
-        if action_data.consumed {
-            return;
-        }
-        action_data.state.press();
-    }
-
-    fn release(&mut self, action: InputAction) {
-        let action_data = &mut self.actions[action as usize];
-        action_data.consumed = false;
-        action_data.state.release();
-    }
-
     pub fn consume(&mut self, action: InputAction) {
         let action_data = &mut self.actions[action as usize];
         action_data.consumed = true;
         action_data.state.release();
+        action_data.value = 0.0;
+    }
+
+    /// Consumes every action, see [`InputActionState::consume`], and zeroes the axes.
+    pub fn consume_all(&mut self) {
+        for action_data in self.actions.iter_mut() {
+            action_data.consumed = true;
+            action_data.state.release();
+            action_data.value = 0.0;
+        }
+        self.axes = Default::default();
     }
 
     pub fn pressed(&self, action: InputAction) -> bool {
@@ -91,6 +101,20 @@ impl InputActionState {
     pub fn just_released(&self, action: InputAction) -> bool {
         self.actions[action as usize].state.just_released()
     }
+
+    /// Value of a single axis.
+    pub fn axis(&self, axis: InputAxis) -> f32 {
+        self.axes[axis as usize].x
+    }
+
+    pub fn dual_axis(&self, axis: InputAxis) -> Vec2 {
+        self.axes[axis as usize]
+    }
+
+    /// Analog value of the action, 0 when released.
+    pub fn value(&self, action: InputAction) -> f32 {
+        self.actions[action as usize].value
+    }
 }
 
 impl InputActionTriggerState {
@@ -137,7 +161,9 @@ impl Default for InputActionData {
     fn default() -> Self {
         InputActionData {
             state: InputActionTriggerState::Released,
+            value: 0.0,
             consumed: false,
+            shadowed: false,
         }
     }
 }
@@ -145,20 +171,22 @@ impl Default for InputActionData {
 pub fn keyboard_input_system(
     mut input_action_state: ResMut<InputActionState>,
     input_action_map: Res<InputActionMap>,
-    input_key_codes: Res<Input<KeyCode>>,
-    input_mouse_buttons: Res<Input<MouseButton>>,
+    input_contexts: Res<InputContexts>,
+    ui_mouse_capture: Res<UiMouseCapture>,
+    input_devices: InputDevices,
     // mut event_reader: EventReader<KeyboardInput>,
 ) {
-    for action_data in input_action_state
-        .bypass_change_detection()
-        .actions
-        .iter_mut()
-    {
-        action_data.state.tick();
-    }
-
-    let state = input_action_map.get_states(&input_key_codes, &input_mouse_buttons);
+    let sources = input_devices.sources();
+    let state = input_contexts.get_states(
+        &input_action_map,
+        &sources,
+        &ui_mouse_capture,
+        &input_action_state.actions,
+    );
     input_action_state.update(state);
+    // Axes made from actions follow their consumption.
+    input_action_state.axes =
+        input_contexts.get_axes(&input_action_map, &sources, &input_action_state.actions);
     // for event in event_reader.iter() {
     //     if let Some(key_code) = event.key_code {
     //         // let action = input_action_state.get_action_data_mut()
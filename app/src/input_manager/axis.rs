@@ -0,0 +1,293 @@
+use bevy::prelude::*;
+use num_enum::TryFromPrimitive;
+use serde::{Deserialize, Serialize};
+
+use crate::input_manager::action::{live_value, InputAction, InputBinding, InputSources};
+use crate::input_manager::action_state::ActionDataArray;
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Hash,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    TryFromPrimitive,
+    Serialize,
+    Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+#[repr(u8)]
+pub enum InputAxis {
+    /// Moves the camera.
+    Pan,
+}
+
+pub type AxisDataArray = [Vec2; 4usize];
+
+/// Bindings of an axis, single axes only use `x`.
+///
+/// The values of the bindings add up and are clamped to a length of 1, so that diagonals aren't
+/// faster while a half pushed stick stays half as fast.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AxisDefinition {
+    pub bindings: Vec<DualAxisBinding>,
+    #[serde(default = "default_sensitivity")]
+    pub sensitivity: f32,
+    #[serde(default)]
+    pub invert_x: bool,
+    #[serde(default)]
+    pub invert_y: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum DualAxisBinding {
+    Axes {
+        #[serde(default)]
+        x: Option<AxisBinding>,
+        #[serde(default)]
+        y: Option<AxisBinding>,
+    },
+    /// Both axes of a stick, with a radial deadzone.
+    GamepadStick(GamepadStick),
+    /// Points toward the edges of the window the cursor is within `margin` pixels of.
+    MouseEdge { margin: f32 },
+}
+
+/// Binding of a single axis in `-1.0..=1.0`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum AxisBinding {
+    /// Values of two actions, so that rebinding them rebinds the axis.
+    Actions {
+        negative: InputAction,
+        positive: InputAction,
+    },
+    /// Values of two bindings, like a pair of keys.
+    Pair {
+        negative: InputBinding,
+        positive: InputBinding,
+    },
+    GamepadAxis(GamepadAxisType),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GamepadStick {
+    Left,
+    Right,
+}
+
+fn default_sensitivity() -> f32 {
+    1.0
+}
+
+impl AxisDefinition {
+    pub fn new(bindings: Vec<DualAxisBinding>) -> Self {
+        AxisDefinition {
+            bindings,
+            sensitivity: default_sensitivity(),
+            invert_x: false,
+            invert_y: false,
+        }
+    }
+
+    /// Value of the axis, `actions` being the states of the actions.
+    pub fn value(&self, sources: &InputSources, actions: &ActionDataArray, deadzone: f32) -> Vec2 {
+        let value = self
+            .bindings
+            .iter()
+            .map(|binding| binding.value(sources, actions, deadzone))
+            .sum::<Vec2>()
+            .clamp_length_max(1.0);
+        let invert = |invert: bool| if invert { -1.0 } else { 1.0 };
+        value * Vec2::new(invert(self.invert_x), invert(self.invert_y)) * self.sensitivity
+    }
+}
+
+impl DualAxisBinding {
+    pub fn value(&self, sources: &InputSources, actions: &ActionDataArray, deadzone: f32) -> Vec2 {
+        match self {
+            DualAxisBinding::Axes { x, y } => {
+                let value = |binding: &Option<AxisBinding>| {
+                    binding
+                        .as_ref()
+                        .map_or(0.0, |binding| binding.value(sources, actions, deadzone))
+                };
+                Vec2::new(value(x), value(y))
+            }
+            DualAxisBinding::GamepadStick(stick) => {
+                let gamepad = match &sources.gamepad {
+                    Some(gamepad) => gamepad,
+                    None => return Vec2::ZERO,
+                };
+                let (x, y) = stick.axes();
+                let axis = |axis_type| {
+                    gamepad
+                        .axes
+                        .get(GamepadAxis::new(gamepad.gamepad, axis_type))
+                        .unwrap_or(0.0)
+                };
+                let value = Vec2::new(axis(x), axis(y));
+                value.normalize_or_zero() * live_value(value.length(), deadzone)
+            }
+            DualAxisBinding::MouseEdge { margin } => {
+                let cursor = match &sources.cursor {
+                    Some(cursor) => cursor,
+                    None => return Vec2::ZERO,
+                };
+                // The cursor position starts at the bottom left corner.
+                let edge = |position: f32, size: f32| {
+                    if position < *margin {
+                        -1.0
+                    } else if position > size - margin {
+                        1.0
+                    } else {
+                        0.0
+                    }
+                };
+                Vec2::new(
+                    edge(cursor.position.x, cursor.window_size.x),
+                    edge(cursor.position.y, cursor.window_size.y),
+                )
+            }
+        }
+    }
+}
+
+impl AxisBinding {
+    pub fn value(&self, sources: &InputSources, actions: &ActionDataArray, deadzone: f32) -> f32 {
+        match self {
+            AxisBinding::Actions { negative, positive } => {
+                actions[*positive as usize].value - actions[*negative as usize].value
+            }
+            AxisBinding::Pair { negative, positive } => {
+                let value = |binding: &InputBinding| {
+                    binding
+                        .active(&|trigger| trigger.value(sources, deadzone))
+                        .map_or(0.0, |active| active.value)
+                };
+                value(positive) - value(negative)
+            }
+            AxisBinding::GamepadAxis(axis_type) => match &sources.gamepad {
+                Some(gamepad) => {
+                    let value = gamepad
+                        .axes
+                        .get(GamepadAxis::new(gamepad.gamepad, *axis_type))
+                        .unwrap_or(0.0);
+                    value.signum() * live_value(value.abs(), deadzone)
+                }
+                None => 0.0,
+            },
+        }
+    }
+}
+
+impl GamepadStick {
+    pub fn axes(&self) -> (GamepadAxisType, GamepadAxisType) {
+        match self {
+            GamepadStick::Left => (GamepadAxisType::LeftStickX, GamepadAxisType::LeftStickY),
+            GamepadStick::Right => (GamepadAxisType::RightStickX, GamepadAxisType::RightStickY),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input_manager::action::{Cursor, GamepadSources};
+    use crate::input_manager::action_state::InputActionTriggerState;
+    use array_init::array_init;
+
+    #[test]
+    fn pan() {
+        let key_codes = Input::<KeyCode>::default();
+        let mouse_buttons = Input::<MouseButton>::default();
+        let buttons = Input::<GamepadButton>::default();
+        let button_axes = Axis::<GamepadButton>::default();
+        let mut axes = Axis::<GamepadAxis>::default();
+        let gamepad = Gamepad::new(0);
+        let mut actions: ActionDataArray = array_init(|_| Default::default());
+        let press = |actions: &mut ActionDataArray, action: InputAction| {
+            actions[action as usize].state = InputActionTriggerState::Pressed;
+            actions[action as usize].value = 1.0;
+        };
+        let mut pan = AxisDefinition::new(vec![
+            DualAxisBinding::Axes {
+                x: Some(AxisBinding::Actions {
+                    negative: InputAction::Left,
+                    positive: InputAction::Right,
+                }),
+                y: Some(AxisBinding::Actions {
+                    negative: InputAction::Down,
+                    positive: InputAction::Up,
+                }),
+            },
+            DualAxisBinding::GamepadStick(GamepadStick::Left),
+            DualAxisBinding::MouseEdge { margin: 10.0 },
+        ]);
+        let value = |pan: &AxisDefinition,
+                     actions: &ActionDataArray,
+                     axes: &Axis<GamepadAxis>,
+                     cursor: Option<Vec2>| {
+            let value = pan.value(
+                &InputSources {
+                    key_codes: &key_codes,
+                    mouse_buttons: &mouse_buttons,
+                    gamepad: Some(GamepadSources {
+                        gamepad,
+                        buttons: &buttons,
+                        button_axes: &button_axes,
+                        axes,
+                    }),
+                    cursor: cursor.map(|position| Cursor {
+                        position,
+                        window_size: Vec2::new(800.0, 600.0),
+                    }),
+                },
+                actions,
+                0.2,
+            );
+            (value * 1000.0).round() / 1000.0
+        };
+
+        // Opposite directions cancel out and diagonals are normalized.
+        press(&mut actions, InputAction::Left);
+        press(&mut actions, InputAction::Right);
+        assert_eq!(value(&pan, &actions, &axes, None), Vec2::ZERO);
+        press(&mut actions, InputAction::Up);
+        assert_eq!(value(&pan, &actions, &axes, None), Vec2::Y);
+        actions[InputAction::Left as usize] = Default::default();
+        assert_eq!(value(&pan, &actions, &axes, None), Vec2::new(0.707, 0.707));
+
+        // Sticks are analog with a radial deadzone.
+        actions = array_init(|_| Default::default());
+        axes.set(GamepadAxis::new(gamepad, GamepadAxisType::LeftStickX), 0.1);
+        axes.set(GamepadAxis::new(gamepad, GamepadAxisType::LeftStickY), 0.1);
+        assert_eq!(value(&pan, &actions, &axes, None), Vec2::ZERO);
+        axes.set(GamepadAxis::new(gamepad, GamepadAxisType::LeftStickX), 0.0);
+        axes.set(GamepadAxis::new(gamepad, GamepadAxisType::LeftStickY), -0.6);
+        assert_eq!(value(&pan, &actions, &axes, None), Vec2::new(0.0, -0.5));
+
+        // Edge scrolling, inverted and sensitive.
+        axes.set(GamepadAxis::new(gamepad, GamepadAxisType::LeftStickY), 0.0);
+        pan.invert_y = true;
+        pan.sensitivity = 2.0;
+        assert_eq!(
+            value(&pan, &actions, &axes, Some(Vec2::new(400.0, 300.0))),
+            Vec2::ZERO
+        );
+        assert_eq!(
+            value(&pan, &actions, &axes, Some(Vec2::new(795.0, 300.0))),
+            Vec2::new(2.0, 0.0)
+        );
+        assert_eq!(
+            value(&pan, &actions, &axes, Some(Vec2::new(400.0, 5.0))),
+            Vec2::new(0.0, 2.0)
+        );
+    }
+}
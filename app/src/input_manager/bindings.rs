@@ -0,0 +1,306 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{AppError, AppResult};
+use crate::input_manager::action::{
+    BindingCaptured, InputAction, InputActionMap, InputBinding, InputDevices,
+};
+use crate::input_manager::action_state::InputActionState;
+use crate::input_manager::axis::{AxisDefinition, InputAxis};
+
+/// User file the bindings are loaded from at startup and saved to when they change.
+#[derive(Resource, Debug, Clone)]
+pub struct InputBindingsFile(pub PathBuf);
+
+/// Saves the bindings to the [`InputBindingsFile`], sent after binding actions through
+/// [`InputActionMap::bind`]. Captured bindings are saved without it.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SaveBindings;
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+struct BindingsConfig {
+    #[serde(default)]
+    deadzone: Option<f32>,
+    /// Written as `{key: A}` rather than YAML tags, like the other config files.
+    #[serde(default, with = "serde_yaml::with::singleton_map_recursive")]
+    bindings: BTreeMap<InputAction, Vec<InputBinding>>,
+    #[serde(default, with = "serde_yaml::with::singleton_map_recursive")]
+    axes: BTreeMap<InputAxis, AxisDefinition>,
+}
+
+impl Default for InputBindingsFile {
+    fn default() -> Self {
+        InputBindingsFile(Path::new("config").join("bindings.yaml"))
+    }
+}
+
+impl InputActionMap {
+    /// Reads bindings written by [`InputActionMap::to_yaml`], actions missing from them keep
+    /// their default bindings.
+    pub fn parse(yaml: &str) -> AppResult<Self> {
+        let config: BindingsConfig =
+            serde_yaml::from_str(yaml).map_err(AppError::BindingsSyntax)?;
+        let mut map = InputActionMap::default();
+        if let Some(deadzone) = config.deadzone {
+            map.set_deadzone(deadzone);
+        }
+        for (action, bindings) in config.bindings {
+            map.set_bindings(action, bindings);
+        }
+        for (axis, definition) in config.axes {
+            map.set_axis(axis, definition);
+        }
+        Ok(map)
+    }
+
+    /// Loads the bindings of a user file, the default bindings when there is none.
+    pub fn load(path: impl AsRef<Path>) -> AppResult<Self> {
+        match fs::read_to_string(path) {
+            Ok(yaml) => InputActionMap::parse(&yaml),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(InputActionMap::default()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    pub fn to_yaml(&self) -> AppResult<String> {
+        let config = BindingsConfig {
+            deadzone: Some(self.deadzone()),
+            bindings: self
+                .iter()
+                .map(|(action, bindings)| (action, bindings.to_vec()))
+                .collect(),
+            axes: self
+                .axes()
+                .map(|(axis, definition)| (axis, definition.clone()))
+                .collect(),
+        };
+        serde_yaml::to_string(&config).map_err(AppError::BindingsSyntax)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> AppResult<()> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, self.to_yaml()?)?;
+        Ok(())
+    }
+}
+
+pub fn load_bindings_system(
+    bindings_file: Res<InputBindingsFile>,
+    mut input_action_map: ResMut<InputActionMap>,
+) {
+    match InputActionMap::load(&bindings_file.0) {
+        Ok(map) => *input_action_map.bypass_change_detection() = map,
+        Err(err) => error!("{}: {}", bindings_file.0.display(), err),
+    }
+}
+
+/// Saves the bindings when a binding was captured or on [`SaveBindings`].
+pub fn save_bindings_system(
+    bindings_file: Res<InputBindingsFile>,
+    input_action_map: Res<InputActionMap>,
+    mut binding_captured_events: EventReader<BindingCaptured>,
+    mut save_bindings_events: EventReader<SaveBindings>,
+) {
+    // Every event is read, so that none is left for the next frame.
+    let assigned = binding_captured_events
+        .iter()
+        .filter(|captured| captured.conflicts.is_empty())
+        .count()
+        > 0;
+    let requested = save_bindings_events.iter().count() > 0;
+    if !assigned && !requested {
+        return;
+    }
+    if let Err(err) = input_action_map.save(&bindings_file.0) {
+        error!("{}: {}", bindings_file.0.display(), err);
+    }
+}
+
+/// Ends pending captures, actions don't trigger while capturing.
+pub fn capture_input_system(
+    mut input_action_map: ResMut<InputActionMap>,
+    mut input_action_state: ResMut<InputActionState>,
+    input_devices: InputDevices,
+    mut binding_captured_events: EventWriter<BindingCaptured>,
+) {
+    if input_action_map.capturing().is_none() {
+        return;
+    }
+    input_action_state.consume_all();
+    // The map only changes when a binding is assigned, not on every frame of the capture.
+    let captured = input_action_map
+        .bypass_change_detection()
+        .capture_input(&input_devices.sources());
+    if let Some(captured) = captured {
+        if captured.conflicts.is_empty() {
+            input_action_map.set_changed();
+        }
+        binding_captured_events.send(captured);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input_manager::action::{AxisDirection, InputSources};
+    use crate::input_manager::InputManagerPlugin;
+    use bevy::input::keyboard::KeyboardInput;
+    use bevy::input::{ButtonState, InputPlugin};
+
+    #[test]
+    fn bindings() {
+        let mut map = InputActionMap::default();
+        let mut key_codes = Input::<KeyCode>::default();
+        let mouse_buttons = Input::<MouseButton>::default();
+
+        // Held modifiers make a chord.
+        map.capture(InputAction::Undo, 1);
+        key_codes.press(KeyCode::RControl);
+        let capture = |map: &mut InputActionMap, key_codes: &mut Input<KeyCode>| {
+            let captured = map.capture_input(&InputSources {
+                key_codes,
+                mouse_buttons: &mouse_buttons,
+                gamepad: None,
+                cursor: None,
+            });
+            key_codes.clear();
+            captured
+        };
+        assert_eq!(capture(&mut map, &mut key_codes), None);
+        key_codes.press(KeyCode::Y);
+        let redo = InputBinding::all_of([InputBinding::control(), KeyCode::Y.into()]);
+        assert_eq!(
+            capture(&mut map, &mut key_codes),
+            Some(BindingCaptured {
+                action: InputAction::Undo,
+                binding: redo.clone(),
+                conflicts: Vec::new(),
+            })
+        );
+        key_codes.release_all();
+        // Inputs of other actions conflict.
+        map.capture(InputAction::Select, 0);
+        key_codes.press(KeyCode::A);
+        assert_eq!(
+            capture(&mut map, &mut key_codes).unwrap().conflicts,
+            [InputAction::Left]
+        );
+        assert_eq!(
+            map.bindings(InputAction::Select),
+            [MouseButton::Left.into(), GamepadButtonType::South.into()]
+        );
+        assert!(matches!(
+            map.bind(InputAction::Select, 0, KeyCode::A),
+            Err(AppError::BindingConflict {
+                action: InputAction::Select,
+                conflict: InputAction::Left,
+            })
+        ));
+        map.bind_replacing(InputAction::Select, 0, KeyCode::A);
+        assert!(!map.bindings(InputAction::Left).contains(&KeyCode::A.into()));
+
+        // Saved bindings override the defaults.
+        let yaml = map.to_yaml().unwrap();
+        assert!(yaml.contains("undo:\n  - all_of:"));
+        let loaded = InputActionMap::parse(&yaml).unwrap();
+        assert_eq!(
+            loaded.axis(InputAxis::Pan),
+            InputActionMap::default().axis(InputAxis::Pan)
+        );
+        assert_eq!(loaded.bindings(InputAction::Undo)[1..], [redo]);
+        assert_eq!(
+            loaded.bindings(InputAction::Select),
+            [KeyCode::A.into(), GamepadButtonType::South.into()]
+        );
+        let partial = InputActionMap::parse(
+            "deadzone: 0.3\nbindings:\n  up:\n    - gamepad_axis: {axis: RightStickY, direction: positive}\n",
+        )
+        .unwrap();
+        assert_eq!(partial.deadzone(), 0.3);
+        assert_eq!(
+            partial.axis(InputAxis::Pan),
+            InputActionMap::default().axis(InputAxis::Pan)
+        );
+        assert_eq!(
+            partial.bindings(InputAction::Up),
+            [(GamepadAxisType::RightStickY, AxisDirection::Positive).into()]
+        );
+        assert_eq!(
+            partial.bindings(InputAction::Down),
+            InputActionMap::default().bindings(InputAction::Down)
+        );
+        assert!(matches!(
+            InputActionMap::parse("bindings: {jump: [{key: Space}]}"),
+            Err(AppError::BindingsSyntax(_))
+        ));
+        assert!(matches!(
+            InputActionMap::parse("bindings: {undo: [{all_of: []}]}"),
+            Err(AppError::BindingsSyntax(_))
+        ));
+    }
+
+    #[test]
+    fn save() {
+        let path = std::env::temp_dir().join(format!("bindings-{}.yaml", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_plugin(InputPlugin)
+            .add_event::<CursorMoved>()
+            .insert_resource(InputBindingsFile(path.clone()))
+            .add_plugin(InputManagerPlugin);
+        let press = |app: &mut App, key_code: KeyCode| {
+            app.world.send_event(KeyboardInput {
+                scan_code: 0,
+                key_code: Some(key_code),
+                state: ButtonState::Pressed,
+            });
+        };
+
+        // Nothing is saved while waiting for the input.
+        app.update();
+        app.world
+            .resource_mut::<InputActionMap>()
+            .capture(InputAction::Undo, 1);
+        for _ in 0..3 {
+            app.update();
+        }
+        assert!(!path.exists());
+        press(&mut app, KeyCode::Y);
+        app.update();
+        let saved = InputActionMap::load(&path).unwrap();
+        assert_eq!(saved.bindings(InputAction::Undo)[1], KeyCode::Y.into());
+
+        // Conflicting captures aren't assigned nor saved.
+        fs::remove_file(&path).unwrap();
+        app.world
+            .resource_mut::<InputActionMap>()
+            .capture(InputAction::Select, 0);
+        press(&mut app, KeyCode::A);
+        app.update();
+        app.update();
+        assert!(!path.exists());
+
+        app.world
+            .resource_mut::<InputActionMap>()
+            .bind(InputAction::Select, 1, KeyCode::Space)
+            .unwrap();
+        app.world.send_event(SaveBindings);
+        app.update();
+        let saved = InputActionMap::load(&path).unwrap();
+        assert_eq!(
+            saved.bindings(InputAction::Select)[1],
+            KeyCode::Space.into()
+        );
+        fs::remove_file(&path).unwrap();
+    }
+}
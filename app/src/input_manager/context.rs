@@ -0,0 +1,452 @@
+use bevy::prelude::*;
+use bevy::utils::{HashMap, HashSet};
+
+use crate::input_manager::action::{
+    action_states, ActiveActions, InputAction, InputActionMap, InputActionTrigger, InputSources,
+};
+use crate::input_manager::action_state::ActionDataArray;
+use crate::input_manager::axis::AxisDataArray;
+
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub enum InputContext {
+    /// Always active below the other contexts, uses the [`InputActionMap`] resource so that
+    /// players can rebind it.
+    Gameplay,
+    /// Active while a building is selected, see
+    /// [`building_mode_system`](crate::building::building_mode_system).
+    BuildMode,
+    Menu,
+    Console,
+}
+
+/// Bindings of a context, see [`InputContexts`].
+pub struct InputLayer {
+    pub map: InputActionMap,
+    /// Higher priorities get the inputs first.
+    pub priority: i32,
+    /// Whether lower contexts get the inputs this one doesn't use, they get none otherwise.
+    pub pass_through: bool,
+    /// Actions lower contexts can't trigger even when passing through.
+    pub block: HashSet<InputAction>,
+}
+
+/// Stack of the active input contexts.
+///
+/// Contexts resolve their actions from the highest priority down, the inputs used by an active
+/// action are taken from the contexts below so that a key opening the console doesn't also move
+/// the camera. Axes come from the highest context defining them.
+#[derive(Resource)]
+pub struct InputContexts {
+    layers: HashMap<InputContext, InputLayer>,
+    /// Sorted by decreasing priority.
+    active: Vec<InputContext>,
+}
+
+/// Mouse buttons pressed over the UI, kept from the contexts until released so that clicks
+/// don't leak into the world.
+#[derive(Resource, Debug, Default)]
+pub struct UiMouseCapture(pub HashSet<MouseButton>);
+
+impl InputContexts {
+    pub fn layer(&self, context: InputContext) -> Option<&InputLayer> {
+        self.layers.get(&context)
+    }
+
+    pub fn layer_mut(&mut self, context: InputContext) -> Option<&mut InputLayer> {
+        self.layers.get_mut(&context)
+    }
+
+    pub fn set_layer(&mut self, context: InputContext, layer: InputLayer) {
+        self.layers.insert(context, layer);
+        self.sort();
+    }
+
+    /// Activates a context, contexts without a layer are ignored.
+    pub fn push(&mut self, context: InputContext) {
+        if !self.layers.contains_key(&context) {
+            warn!("no input layer for {:?}", context);
+            return;
+        }
+        if !self.active.contains(&context) {
+            self.active.push(context);
+            self.sort();
+        }
+    }
+
+    pub fn remove(&mut self, context: InputContext) {
+        self.active.retain(|active| *active != context);
+    }
+
+    pub fn is_active(&self, context: InputContext) -> bool {
+        context == InputContext::Gameplay || self.active.contains(&context)
+    }
+
+    /// The active context with the highest priority.
+    pub fn top(&self) -> InputContext {
+        self.active
+            .first()
+            .copied()
+            .unwrap_or(InputContext::Gameplay)
+    }
+
+    /// States of the actions of every active context, see [`InputActionMap::get_states`].
+    pub fn get_states(
+        &self,
+        gameplay: &InputActionMap,
+        sources: &InputSources,
+        ui_mouse_capture: &UiMouseCapture,
+        previous: &ActionDataArray,
+    ) -> ActionDataArray {
+        let mut taken: HashSet<InputActionTrigger> = ui_mouse_capture
+            .0
+            .iter()
+            .map(|mouse_button| InputActionTrigger::MouseButton(*mouse_button))
+            .collect();
+        let mut active = ActiveActions::default();
+        let mut blocked: HashSet<InputAction> = HashSet::new();
+        for (map, pass_through, block) in self.maps(gameplay) {
+            let layer_active = map.active_actions(|trigger| {
+                if taken.contains(trigger) {
+                    0.0
+                } else {
+                    trigger.value(sources, map.deadzone())
+                }
+            });
+            // Actions resolve in the highest context having them, shadowed or not.
+            for (action, binding) in layer_active.bindings {
+                if blocked.contains(&action) || active.shadowed.contains(&action) {
+                    continue;
+                }
+                taken.extend(binding.triggers.iter().copied());
+                active.bindings.entry(action).or_insert(binding);
+            }
+            for action in layer_active.shadowed {
+                if !blocked.contains(&action) && !active.bindings.contains_key(&action) {
+                    active.shadowed.insert(action);
+                }
+            }
+            if !pass_through {
+                break;
+            }
+            blocked.extend(block.into_iter().flatten().copied());
+        }
+        action_states(&active, previous)
+    }
+
+    /// Values of the axes of every active context, see [`InputActionMap::get_axes`].
+    pub fn get_axes(
+        &self,
+        gameplay: &InputActionMap,
+        sources: &InputSources,
+        actions: &ActionDataArray,
+    ) -> AxisDataArray {
+        let mut axes = AxisDataArray::default();
+        let mut defined = HashSet::new();
+        for (map, pass_through, _) in self.maps(gameplay) {
+            for (axis, definition) in map.axes() {
+                if defined.insert(axis) {
+                    axes[axis as usize] = definition.value(sources, actions, map.deadzone());
+                }
+            }
+            if !pass_through {
+                break;
+            }
+        }
+        axes
+    }
+
+    /// Maps of the active contexts from the highest priority, with whether they pass through
+    /// and the actions they block.
+    fn maps<'a>(
+        &'a self,
+        gameplay: &'a InputActionMap,
+    ) -> impl Iterator<Item = (&'a InputActionMap, bool, Option<&'a HashSet<InputAction>>)> + 'a
+    {
+        self.active
+            .iter()
+            .filter_map(|context| self.layers.get(context))
+            .map(|layer| (&layer.map, layer.pass_through, Some(&layer.block)))
+            .chain([(gameplay, false, None)])
+    }
+
+    fn sort(&mut self) {
+        let layers = &self.layers;
+        self.active
+            .sort_by_key(|context| std::cmp::Reverse(layers[context].priority));
+    }
+}
+
+impl Default for InputContexts {
+    fn default() -> Self {
+        let layers = [
+            (
+                InputContext::BuildMode,
+                InputLayer {
+                    map: InputActionMap::from_bindings([
+                        (
+                            InputAction::Select,
+                            vec![MouseButton::Left.into(), GamepadButtonType::South.into()],
+                        ),
+                        (
+                            InputAction::Cancel,
+                            vec![
+                                KeyCode::Escape.into(),
+                                MouseButton::Right.into(),
+                                GamepadButtonType::East.into(),
+                            ],
+                        ),
+                    ]),
+                    priority: 10,
+                    pass_through: true,
+                    block: HashSet::default(),
+                },
+            ),
+            (
+                InputContext::Menu,
+                InputLayer {
+                    map: InputActionMap::from_bindings([
+                        (
+                            InputAction::Up,
+                            vec![KeyCode::Up.into(), GamepadButtonType::DPadUp.into()],
+                        ),
+                        (
+                            InputAction::Down,
+                            vec![KeyCode::Down.into(), GamepadButtonType::DPadDown.into()],
+                        ),
+                        (
+                            InputAction::Select,
+                            vec![
+                                MouseButton::Left.into(),
+                                KeyCode::Return.into(),
+                                GamepadButtonType::South.into(),
+                            ],
+                        ),
+                        (
+                            InputAction::Cancel,
+                            vec![KeyCode::Escape.into(), GamepadButtonType::East.into()],
+                        ),
+                    ]),
+                    priority: 20,
+                    pass_through: false,
+                    block: HashSet::default(),
+                },
+            ),
+            (
+                InputContext::Console,
+                InputLayer {
+                    map: InputActionMap::from_bindings([
+                        (InputAction::ToggleConsole, vec![KeyCode::Grave.into()]),
+                        (InputAction::Cancel, vec![KeyCode::Escape.into()]),
+                    ]),
+                    priority: 30,
+                    pass_through: false,
+                    block: HashSet::default(),
+                },
+            ),
+        ];
+        InputContexts {
+            layers: layers.into_iter().collect(),
+            active: Vec::new(),
+        }
+    }
+}
+
+/// Captures the mouse buttons pressed while the pointer is over a UI node.
+pub fn ui_mouse_capture_system(
+    mut ui_mouse_capture: ResMut<UiMouseCapture>,
+    input_mouse_buttons: Res<Input<MouseButton>>,
+    interaction_query: Query<&Interaction>,
+) {
+    let hovered = interaction_query
+        .iter()
+        .any(|interaction| *interaction != Interaction::None);
+    if hovered {
+        ui_mouse_capture
+            .0
+            .extend(input_mouse_buttons.get_just_pressed().copied());
+    }
+    if !ui_mouse_capture.0.is_empty() {
+        ui_mouse_capture
+            .0
+            .retain(|mouse_button| input_mouse_buttons.pressed(*mouse_button));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input_manager::action::InputBinding;
+    use crate::input_manager::action_state::{InputActionState, InputActionTriggerState};
+    use crate::input_manager::axis::InputAxis;
+    use crate::input_manager::bindings::InputBindingsFile;
+    use crate::input_manager::InputManagerPlugin;
+    use array_init::array_init;
+    use bevy::input::mouse::MouseButtonInput;
+    use bevy::input::{ButtonState, InputPlugin, InputSystem};
+    use bevy::ui::UiSystem;
+
+    #[test]
+    fn contexts() {
+        let gameplay = InputActionMap::default();
+        let mut contexts = InputContexts::default();
+        let mut key_codes = Input::<KeyCode>::default();
+        let mut mouse_buttons = Input::<MouseButton>::default();
+        let mut ui_mouse_capture = UiMouseCapture::default();
+        let states = |contexts: &InputContexts,
+                      key_codes: &Input<KeyCode>,
+                      mouse_buttons: &Input<MouseButton>,
+                      ui_mouse_capture: &UiMouseCapture| {
+            let sources = InputSources {
+                key_codes,
+                mouse_buttons,
+                gamepad: None,
+                cursor: None,
+            };
+            let previous: ActionDataArray = array_init(|_| Default::default());
+            let actions = contexts.get_states(&gameplay, &sources, ui_mouse_capture, &previous);
+            let pan = contexts.get_axes(&gameplay, &sources, &actions)[InputAxis::Pan as usize];
+            let pressed: Vec<InputAction> = actions
+                .iter()
+                .enumerate()
+                .filter(|(_, data)| data.state == InputActionTriggerState::JustPressed)
+                .map(|(i, _)| InputAction::try_from(i as u8).unwrap())
+                .collect();
+            (pressed, pan)
+        };
+
+        key_codes.press(KeyCode::Escape);
+        key_codes.press(KeyCode::D);
+        mouse_buttons.press(MouseButton::Left);
+        assert_eq!(
+            states(&contexts, &key_codes, &mouse_buttons, &ui_mouse_capture),
+            (vec![InputAction::Right, InputAction::Select], Vec2::X)
+        );
+
+        // Build mode adds Cancel and passes the rest through.
+        contexts.push(InputContext::BuildMode);
+        assert_eq!(contexts.top(), InputContext::BuildMode);
+        assert_eq!(
+            states(&contexts, &key_codes, &mouse_buttons, &ui_mouse_capture),
+            (
+                vec![InputAction::Right, InputAction::Select, InputAction::Cancel],
+                Vec2::X
+            )
+        );
+
+        // Clicks on the UI don't reach the contexts.
+        ui_mouse_capture.0.insert(MouseButton::Left);
+        assert_eq!(
+            states(&contexts, &key_codes, &mouse_buttons, &ui_mouse_capture),
+            (vec![InputAction::Right, InputAction::Cancel], Vec2::X)
+        );
+
+        // Blocked actions don't pass through, nor do the axes made from them.
+        let block = &mut contexts.layer_mut(InputContext::BuildMode).unwrap().block;
+        block.insert(InputAction::Right);
+        assert_eq!(
+            states(&contexts, &key_codes, &mouse_buttons, &ui_mouse_capture),
+            (vec![InputAction::Cancel], Vec2::ZERO)
+        );
+
+        // The console blocks the contexts below whatever the push order.
+        contexts.push(InputContext::Console);
+        contexts.push(InputContext::Menu);
+        assert_eq!(contexts.top(), InputContext::Console);
+        assert_eq!(
+            states(&contexts, &key_codes, &mouse_buttons, &ui_mouse_capture),
+            (vec![InputAction::Cancel], Vec2::ZERO)
+        );
+        contexts.remove(InputContext::Console);
+        contexts.remove(InputContext::Menu);
+        assert!(!contexts.is_active(InputContext::Console));
+        assert!(contexts.is_active(InputContext::BuildMode));
+    }
+
+    #[test]
+    fn layer_shadowing() {
+        let gameplay = InputActionMap::from_bindings([
+            (InputAction::Select, vec![KeyCode::Space.into()]),
+            (
+                InputAction::Undo,
+                vec![InputBinding::all_of([KeyCode::LControl, KeyCode::Space])],
+            ),
+        ]);
+        let mut contexts = InputContexts::default();
+        contexts.push(InputContext::BuildMode);
+        let mut key_codes = Input::<KeyCode>::default();
+        let mut mouse_buttons = Input::<MouseButton>::default();
+        key_codes.press(KeyCode::LControl);
+        key_codes.press(KeyCode::Space);
+        mouse_buttons.press(MouseButton::Left);
+        let sources = InputSources {
+            key_codes: &key_codes,
+            mouse_buttons: &mouse_buttons,
+            gamepad: None,
+            cursor: None,
+        };
+        let previous: ActionDataArray = array_init(|_| Default::default());
+        let actions =
+            contexts.get_states(&gameplay, &sources, &UiMouseCapture::default(), &previous);
+
+        // The chord of the gameplay shadows its own Select, not the one of the build mode.
+        assert!(actions[InputAction::Select as usize].state.pressed());
+        assert!(!actions[InputAction::Select as usize].shadowed);
+        assert!(actions[InputAction::Undo as usize].state.pressed());
+    }
+
+    /// Whether the cursor is over the button, applied by the stand-in of bevy_ui's focus system.
+    #[derive(Resource, Default)]
+    struct CursorOverButton(bool);
+
+    fn focus_system(
+        cursor_over_button: Res<CursorOverButton>,
+        mut interaction_query: Query<&mut Interaction>,
+    ) {
+        for mut interaction in interaction_query.iter_mut() {
+            *interaction = if cursor_over_button.0 {
+                Interaction::Hovered
+            } else {
+                Interaction::None
+            };
+        }
+    }
+
+    #[test]
+    fn ui_mouse_capture() {
+        let path = std::env::temp_dir().join(format!("ui-bindings-{}.yaml", std::process::id()));
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_plugin(InputPlugin)
+            .add_event::<CursorMoved>()
+            .insert_resource(InputBindingsFile(path))
+            .init_resource::<CursorOverButton>()
+            .add_plugin(InputManagerPlugin)
+            .configure_set(UiSystem::Focus.in_base_set(CoreSet::PreUpdate))
+            .add_system(focus_system.in_set(UiSystem::Focus).after(InputSystem));
+        app.world.spawn(Interaction::None);
+        let click = |app: &mut App, cursor_over_button: bool| {
+            app.world.resource_mut::<CursorOverButton>().0 = cursor_over_button;
+            app.world.send_event(MouseButtonInput {
+                button: MouseButton::Left,
+                state: ButtonState::Pressed,
+            });
+            app.update();
+            let select = app
+                .world
+                .resource::<InputActionState>()
+                .just_pressed(InputAction::Select);
+            app.world.send_event(MouseButtonInput {
+                button: MouseButton::Left,
+                state: ButtonState::Released,
+            });
+            app.update();
+            select
+        };
+        app.update();
+
+        // Clicks on the frame the cursor reaches the button or leaves it follow that frame.
+        assert!(!click(&mut app, true));
+        assert!(click(&mut app, false));
+        assert!(!click(&mut app, true));
+    }
+}
@@ -0,0 +1,20 @@
+use bevy::prelude::*;
+
+/// Gamepad the gamepad triggers are read from.
+#[derive(Resource, Debug, Default)]
+pub struct ActiveGamepad(pub Option<Gamepad>);
+
+/// Picks the first connected gamepad when there is no active one, or when it disconnects.
+pub fn active_gamepad_system(mut active_gamepad: ResMut<ActiveGamepad>, gamepads: Res<Gamepads>) {
+    if matches!(active_gamepad.0, Some(gamepad) if gamepads.contains(gamepad)) {
+        return;
+    }
+    let gamepad = gamepads.iter().next();
+    if active_gamepad.0 != gamepad {
+        match gamepad {
+            Some(gamepad) => info!("using {:?}", gamepad),
+            None => info!("no gamepad connected"),
+        }
+        active_gamepad.0 = gamepad;
+    }
+}
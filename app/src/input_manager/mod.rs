@@ -1,12 +1,23 @@
 use bevy::input::InputSystem;
 use bevy::prelude::*;
+use bevy::ui::UiSystem;
 
-use crate::input_manager::action::InputActionMap;
+use crate::input_manager::action::{BindingCaptured, InputActionMap};
 use crate::input_manager::action_state::{keyboard_input_system, InputActionState};
+use crate::input_manager::bindings::{
+    capture_input_system, load_bindings_system, save_bindings_system, InputBindingsFile,
+    SaveBindings,
+};
+use crate::input_manager::context::{ui_mouse_capture_system, InputContexts, UiMouseCapture};
+use crate::input_manager::gamepad::{active_gamepad_system, ActiveGamepad};
 use crate::input_manager::mouse::{mouse_position_system, MousePosition};
 
 pub mod action;
 pub mod action_state;
+pub mod axis;
+pub mod bindings;
+pub mod context;
+pub mod gamepad;
 pub mod mouse;
 
 pub struct InputManagerPlugin;
@@ -16,10 +27,27 @@ impl Plugin for InputManagerPlugin {
         app.init_resource::<MousePosition>()
             .init_resource::<InputActionMap>()
             .init_resource::<InputActionState>()
+            .init_resource::<InputBindingsFile>()
+            .init_resource::<ActiveGamepad>()
+            .init_resource::<InputContexts>()
+            .init_resource::<UiMouseCapture>()
+            .add_event::<BindingCaptured>()
+            .add_event::<SaveBindings>()
+            .add_startup_system(load_bindings_system)
             .add_systems(
-                (keyboard_input_system, mouse_position_system)
+                (
+                    active_gamepad_system,
+                    ui_mouse_capture_system,
+                    keyboard_input_system,
+                    capture_input_system,
+                    mouse_position_system,
+                )
                     .chain()
-                    .in_set(InputSystem),
-            );
+                    .in_base_set(CoreSet::PreUpdate)
+                    .after(InputSystem)
+                    // The UI capture reads the `Interaction`s of this frame.
+                    .after(UiSystem::Focus),
+            )
+            .add_system(save_bindings_system);
     }
 }
@@ -1,11 +1,20 @@
+pub mod actor;
 pub mod asset;
+pub mod building;
 pub mod camera;
+pub mod combat;
 pub mod config;
+pub mod economy;
 pub mod error;
 pub mod input_manager;
 pub mod lighting;
+pub mod pathfinding;
 pub mod plugin;
 pub mod prototype;
+pub mod spatial;
 pub mod state;
+pub mod targeting;
 pub mod tilemap;
+pub mod upgrade;
+pub mod wave;
 pub mod world_material;
@@ -0,0 +1,169 @@
+use crate::pathfinding::{NavigationGrid, SearchNode};
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use std::collections::BinaryHeap;
+
+/// Octile distance, exact on open floors when movement costs are at least 1.
+pub fn heuristic(from: IVec2, to: IVec2) -> f32 {
+    let delta = (to - from).abs();
+    let (min, max) = (delta.min_element() as f32, delta.max_element() as f32);
+    max + (std::f32::consts::SQRT_2 - 1.0) * min
+}
+
+/// Cheapest path from `start` to `goal` including both, `None` when the goal can't be reached.
+pub fn find_path(grid: &NavigationGrid, start: IVec2, goal: IVec2) -> Option<Vec<IVec2>> {
+    if !grid.is_walkable(goal) {
+        return None;
+    }
+
+    let mut open = BinaryHeap::new();
+    let mut costs: HashMap<IVec2, f32> = HashMap::default();
+    let mut previous: HashMap<IVec2, IVec2> = HashMap::default();
+    open.push(SearchNode {
+        cost: heuristic(start, goal),
+        location: start,
+    });
+    costs.insert(start, 0.0);
+
+    while let Some(SearchNode { cost, location }) = open.pop() {
+        if location == goal {
+            let mut path = vec![goal];
+            let mut location = goal;
+            while let Some(previous_location) = previous.get(&location) {
+                location = *previous_location;
+                path.push(location);
+            }
+            path.reverse();
+            return Some(path);
+        }
+        let location_cost = costs[&location];
+        if cost > location_cost + heuristic(location, goal) {
+            // Already reached more cheaply.
+            continue;
+        }
+
+        for (neighbour, step_cost) in grid.neighbours(location) {
+            let neighbour_cost = location_cost + step_cost;
+            if costs
+                .get(&neighbour)
+                .map_or(false, |cost| *cost <= neighbour_cost)
+            {
+                continue;
+            }
+            costs.insert(neighbour, neighbour_cost);
+            previous.insert(neighbour, location);
+            open.push(SearchNode {
+                cost: neighbour_cost + heuristic(neighbour, goal),
+                location: neighbour,
+            });
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pathfinding::tests::{make_tilemap, WALL};
+    use crate::tilemap::data::TileData;
+
+    #[test]
+    fn paths() {
+        let mut tilemap = make_tilemap(&[
+            "#######", //
+            "#.....#", //
+            "#.###.#", //
+            "#.#...#", //
+            "#.#.#.#", //
+            "#...#.#", //
+            "#######", //
+        ]);
+        let mut grid = NavigationGrid::from_tilemap(&tilemap);
+
+        let path = find_path(&grid, IVec2::new(1, 1), IVec2::new(3, 2)).unwrap();
+        assert_eq!(
+            path,
+            vec![
+                IVec2::new(1, 1),
+                IVec2::new(2, 1),
+                IVec2::new(3, 1),
+                IVec2::new(3, 2),
+            ]
+        );
+
+        let path = find_path(&grid, IVec2::new(1, 1), IVec2::new(5, 1)).unwrap();
+        assert_eq!(
+            path,
+            vec![
+                IVec2::new(1, 1),
+                IVec2::new(2, 1),
+                IVec2::new(3, 1),
+                IVec2::new(3, 2),
+                IVec2::new(3, 3),
+                IVec2::new(4, 3),
+                IVec2::new(5, 3),
+                IVec2::new(5, 2),
+                IVec2::new(5, 1),
+            ]
+        );
+        assert!(grid.is_path_clear(&path));
+
+        // A building in the middle leaves the way along the top.
+        grid.set_obstacle(IVec2::new(4, 3), true);
+        assert!(!grid.is_path_clear(&path));
+        assert_eq!(
+            find_path(&grid, IVec2::new(1, 1), IVec2::new(5, 1))
+                .unwrap()
+                .len(),
+            13
+        );
+        grid.set_obstacle(IVec2::new(3, 5), true);
+        assert_eq!(find_path(&grid, IVec2::new(1, 1), IVec2::new(5, 1)), None);
+
+        tilemap.set_tile(IVec2::new(4, 1), TileData::new(0));
+        grid.update_tile(&tilemap, IVec2::new(4, 1));
+        assert_eq!(
+            find_path(&grid, IVec2::new(1, 1), IVec2::new(5, 1))
+                .unwrap()
+                .len(),
+            5
+        );
+
+        tilemap.set_tile(IVec2::new(5, 1), TileData::new(WALL));
+        grid.update_tile(&tilemap, IVec2::new(5, 1));
+        assert_eq!(find_path(&grid, IVec2::new(1, 1), IVec2::new(5, 1)), None);
+    }
+
+    #[test]
+    fn costs() {
+        let tilemap = make_tilemap(&[
+            "#####", //
+            "#...#", //
+            "#~#.#", //
+            "#...#", //
+            "#####", //
+        ]);
+        let grid = NavigationGrid::from_tilemap(&tilemap);
+        // Crossing the mud is cheaper than walking around the wall.
+        let path = find_path(&grid, IVec2::new(1, 1), IVec2::new(1, 3)).unwrap();
+        assert_eq!(
+            path,
+            vec![IVec2::new(1, 1), IVec2::new(1, 2), IVec2::new(1, 3)]
+        );
+
+        // Mud is avoided when there is a cheaper way around it.
+        let tilemap = make_tilemap(&[
+            "#####", //
+            "#...#", //
+            "#~.~#", //
+            "#...#", //
+            "#####", //
+        ]);
+        let grid = NavigationGrid::from_tilemap(&tilemap);
+        let path = find_path(&grid, IVec2::new(1, 1), IVec2::new(1, 3)).unwrap();
+        assert_eq!(
+            path,
+            vec![IVec2::new(1, 1), IVec2::new(2, 2), IVec2::new(1, 3)]
+        );
+    }
+}
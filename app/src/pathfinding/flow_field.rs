@@ -0,0 +1,171 @@
+use crate::pathfinding::{NavigationGrid, SearchNode};
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use std::collections::BinaryHeap;
+
+/// Cost of reaching the nearest goal from every reachable tile, shared by any number of agents.
+#[derive(Debug, Clone, Default)]
+pub struct FlowField {
+    goals: Vec<IVec2>,
+    costs: HashMap<IVec2, f32>,
+    version: u64,
+}
+
+impl FlowField {
+    /// Runs Dijkstra outwards from the walkable goals.
+    pub fn new(grid: &NavigationGrid, goals: impl IntoIterator<Item = IVec2>) -> Self {
+        let mut flow_field = FlowField {
+            goals: goals.into_iter().collect(),
+            ..Default::default()
+        };
+        flow_field.update(grid);
+        flow_field
+    }
+
+    pub fn goals(&self) -> &[IVec2] {
+        &self.goals
+    }
+
+    /// Whether the grid changed since the flow field was made.
+    pub fn is_outdated(&self, grid: &NavigationGrid) -> bool {
+        self.version != grid.version()
+    }
+
+    /// Recomputes the costs from the current grid.
+    pub fn update(&mut self, grid: &NavigationGrid) {
+        self.costs.clear();
+        self.version = grid.version();
+
+        // Searching from the goals backwards, stepping from a neighbour costs the tile expanded.
+        let mut open = BinaryHeap::new();
+        for goal in self.goals.iter().copied() {
+            if grid.is_walkable(goal) {
+                self.costs.insert(goal, 0.0);
+                open.push(SearchNode {
+                    cost: 0.0,
+                    location: goal,
+                });
+            }
+        }
+        while let Some(SearchNode { cost, location }) = open.pop() {
+            if cost > self.costs[&location] {
+                continue;
+            }
+            for (neighbour, _) in grid.neighbours(location) {
+                let step_cost = grid.get_cost(location).unwrap_or_default()
+                    * (neighbour - location).as_vec2().length();
+                let neighbour_cost = cost + step_cost;
+                if self
+                    .costs
+                    .get(&neighbour)
+                    .map_or(false, |cost| *cost <= neighbour_cost)
+                {
+                    continue;
+                }
+                self.costs.insert(neighbour, neighbour_cost);
+                open.push(SearchNode {
+                    cost: neighbour_cost,
+                    location: neighbour,
+                });
+            }
+        }
+    }
+
+    /// Cost of reaching the nearest goal, `None` when no goal can be reached.
+    pub fn get_cost(&self, location: IVec2) -> Option<f32> {
+        self.costs.get(&location).copied()
+    }
+
+    /// Neighbour to step to from `location` towards the nearest goal, `None` at a goal or when
+    /// no goal can be reached.
+    pub fn get_next(&self, grid: &NavigationGrid, location: IVec2) -> Option<IVec2> {
+        let cost = self.get_cost(location)?;
+        let mut next = None;
+        let mut next_cost = cost;
+        for (neighbour, _) in grid.neighbours(location) {
+            if let Some(neighbour_cost) = self.get_cost(neighbour) {
+                if neighbour_cost < next_cost {
+                    next = Some(neighbour);
+                    next_cost = neighbour_cost;
+                }
+            }
+        }
+        next
+    }
+
+    /// Direction to move from `location` towards the nearest goal.
+    pub fn get_direction(&self, grid: &NavigationGrid, location: IVec2) -> Option<IVec2> {
+        self.get_next(grid, location).map(|next| next - location)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pathfinding::astar::find_path;
+    use crate::pathfinding::tests::make_tilemap;
+
+    #[test]
+    fn flow_field() {
+        let tilemap = make_tilemap(&[
+            "#######", //
+            "#.....#", //
+            "#.###.#", //
+            "#.#...#", //
+            "#.#.#.#", //
+            "#...#.#", //
+            "#######", //
+        ]);
+        let mut grid = NavigationGrid::from_tilemap(&tilemap);
+        let goal = IVec2::new(5, 1);
+        let mut flow_field = FlowField::new(&grid, [goal]);
+
+        assert_eq!(flow_field.get_cost(goal), Some(0.0));
+        assert_eq!(flow_field.get_next(&grid, goal), None);
+        assert_eq!(flow_field.get_cost(IVec2::new(5, 3)), Some(2.0));
+        assert_eq!(flow_field.get_cost(IVec2::new(0, 0)), None);
+        assert_eq!(
+            flow_field.get_direction(&grid, IVec2::new(1, 1)),
+            Some(IVec2::new(1, 0))
+        );
+
+        // Following the field walks the same path as A*.
+        let start = IVec2::new(1, 1);
+        let mut path = vec![start];
+        while let Some(next) = flow_field.get_next(&grid, *path.last().unwrap()) {
+            path.push(next);
+        }
+        assert_eq!(Some(path), find_path(&grid, start, goal));
+
+        grid.set_obstacle(IVec2::new(4, 3), true);
+        assert!(flow_field.is_outdated(&grid));
+        flow_field.update(&grid);
+        assert!(!flow_field.is_outdated(&grid));
+        assert_eq!(flow_field.get_cost(start), Some(12.0));
+        assert_eq!(
+            flow_field.get_direction(&grid, start),
+            Some(IVec2::new(0, 1))
+        );
+    }
+
+    #[test]
+    fn goals() {
+        let tilemap = make_tilemap(&[
+            "#######", //
+            "#.....#", //
+            "#######", //
+        ]);
+        let grid = NavigationGrid::from_tilemap(&tilemap);
+        let flow_field = FlowField::new(&grid, [IVec2::new(1, 1), IVec2::new(5, 1)]);
+        assert_eq!(flow_field.get_cost(IVec2::new(2, 1)), Some(1.0));
+        assert_eq!(flow_field.get_cost(IVec2::new(3, 1)), Some(2.0));
+        assert_eq!(
+            flow_field.get_direction(&grid, IVec2::new(4, 1)),
+            Some(IVec2::new(1, 0))
+        );
+        assert_eq!(
+            flow_field.get_direction(&grid, IVec2::new(2, 1)),
+            Some(IVec2::new(-1, 0))
+        );
+    }
+}
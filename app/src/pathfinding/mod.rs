@@ -0,0 +1,432 @@
+use crate::tilemap::bundle::TilemapComponent;
+use crate::tilemap::data::{ChunkData, TilemapData};
+use crate::tilemap::properties::{ChunkBitset, TileFlags};
+use crate::tilemap::TILEMAP_CHUNK_SIZE;
+use bevy::prelude::*;
+use bevy::utils::{HashMap, HashSet};
+use std::cmp::Ordering;
+
+pub mod astar;
+pub mod flow_field;
+pub mod plugin;
+
+/// Marks the tilemap the [`NavigationGrid`] is kept in sync with, see [`navigation_grid_system`].
+#[derive(Component, Debug, Default)]
+pub struct NavigationTilemapComponent;
+
+/// Walkability and movement costs of a tilemap, split in the same chunks.
+///
+/// Tiles outside of the tilemap chunks and tiles blocked by an obstacle can't be walked on.
+#[derive(Resource, Debug, Clone, Default)]
+pub struct NavigationGrid {
+    chunks: HashMap<IVec2, NavigationChunk>,
+    obstacles: HashSet<IVec2>,
+    version: u64,
+}
+
+#[derive(Debug, Clone)]
+struct NavigationChunk {
+    walkable: ChunkBitset,
+    costs: Vec<f32>,
+}
+
+/// Neighbour offsets, orthogonal ones first so ties resolve to straight moves.
+pub const NEIGHBOURS: [IVec2; 8] = [
+    IVec2::new(0, 1),
+    IVec2::new(1, 0),
+    IVec2::new(0, -1),
+    IVec2::new(-1, 0),
+    IVec2::new(1, 1),
+    IVec2::new(1, -1),
+    IVec2::new(-1, -1),
+    IVec2::new(-1, 1),
+];
+
+impl NavigationGrid {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn from_tilemap(tilemap: &TilemapData) -> Self {
+        Self::from_layers([tilemap])
+    }
+
+    /// Grid of the tiles of several layers, see [`NavigationGrid::update_chunk_layers`].
+    pub fn from_layers<'a>(layers: impl IntoIterator<Item = &'a TilemapData> + Clone) -> Self {
+        let mut grid = NavigationGrid::new();
+        grid.rebuild(layers);
+        grid
+    }
+
+    /// Rereads every chunk of the layers, keeping the obstacles.
+    pub fn rebuild<'a>(&mut self, layers: impl IntoIterator<Item = &'a TilemapData> + Clone) {
+        self.chunks.clear();
+        let locations: HashSet<IVec2> = layers
+            .clone()
+            .into_iter()
+            .flat_map(|tilemap| tilemap.iter_chunks().map(|(location, _)| location))
+            .collect();
+        for location in locations {
+            self.update_chunk_layers(layers.clone(), location);
+        }
+        self.version += 1;
+    }
+
+    /// Incremented by every change, paths and flow fields made before a change may be outdated.
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// Rereads a chunk of the tilemap, removing it when the tilemap doesn't have it anymore.
+    pub fn update_chunk(&mut self, tilemap: &TilemapData, location: IVec2) {
+        self.update_chunk_layers([tilemap], location);
+    }
+
+    /// Rereads a chunk of several layers, a tile is walkable when some layer has it and every
+    /// layer having it allows it, and costs the most of their costs.
+    pub fn update_chunk_layers<'a>(
+        &mut self,
+        layers: impl IntoIterator<Item = &'a TilemapData>,
+        location: IVec2,
+    ) {
+        let mut navigation_chunk: Option<NavigationChunk> = None;
+        let mut tiles = ChunkBitset::default();
+        for tilemap in layers {
+            let (chunk, walkable) = match (
+                tilemap.get_chunk(location),
+                tilemap.get_chunk_flags(location, TileFlags::WALKABLE),
+            ) {
+                (Some(chunk), Some(walkable)) => (chunk, walkable),
+                _ => continue,
+            };
+            // Empty tiles let the tiles of the other layers decide.
+            let layer_tiles = ChunkBitset::tiles(chunk);
+            let walkable = walkable | !layer_tiles;
+            tiles = tiles | layer_tiles;
+            let properties = tilemap.properties();
+            let costs = chunk.tiles.iter().map(|tile| {
+                if tile.is_empty() {
+                    0.0
+                } else {
+                    properties.get(tile.atlas_index).movement_cost
+                }
+            });
+            match navigation_chunk.as_mut() {
+                Some(navigation_chunk) => {
+                    navigation_chunk.walkable = navigation_chunk.walkable & walkable;
+                    for (cost, layer_cost) in navigation_chunk.costs.iter_mut().zip(costs) {
+                        *cost = cost.max(layer_cost);
+                    }
+                }
+                None => {
+                    navigation_chunk = Some(NavigationChunk {
+                        walkable,
+                        costs: costs.collect(),
+                    })
+                }
+            }
+        }
+        match navigation_chunk {
+            Some(mut navigation_chunk) => {
+                navigation_chunk.walkable = navigation_chunk.walkable & tiles;
+                self.chunks.insert(location, navigation_chunk);
+            }
+            None => {
+                self.chunks.remove(&location);
+            }
+        }
+        self.version += 1;
+    }
+
+    /// Rereads a single tile of the tilemap.
+    pub fn update_tile(&mut self, tilemap: &TilemapData, location: IVec2) {
+        let chunk_location = TilemapData::tilemap_to_chunk(location);
+        let (navigation_chunk, properties) = match (
+            self.chunks.get_mut(&chunk_location),
+            tilemap.get_tile_properties(location),
+        ) {
+            (Some(navigation_chunk), Some(properties)) => (navigation_chunk, properties),
+            _ => return self.update_chunk(tilemap, chunk_location),
+        };
+        let tile_location = ChunkData::tilemap_to_chunk_tile(location);
+        navigation_chunk
+            .walkable
+            .set(tile_location, properties.is_walkable());
+        navigation_chunk.costs[ChunkData::tile_index(tile_location)] = properties.movement_cost;
+        self.version += 1;
+    }
+
+    /// Blocks or unblocks a tile regardless of the tilemap, e.g. for buildings.
+    pub fn set_obstacle(&mut self, location: IVec2, blocked: bool) {
+        let changed = if blocked {
+            self.obstacles.insert(location)
+        } else {
+            self.obstacles.remove(&location)
+        };
+        if changed {
+            self.version += 1;
+        }
+    }
+
+    pub fn is_obstacle(&self, location: IVec2) -> bool {
+        self.obstacles.contains(&location)
+    }
+
+    pub fn is_walkable(&self, location: IVec2) -> bool {
+        self.get_cost(location).is_some()
+    }
+
+    /// Cost of moving onto a walkable tile.
+    pub fn get_cost(&self, location: IVec2) -> Option<f32> {
+        if self.obstacles.contains(&location) {
+            return None;
+        }
+        let chunk = self.chunks.get(&TilemapData::tilemap_to_chunk(location))?;
+        let tile_location = ChunkData::tilemap_to_chunk_tile(location);
+        if !chunk.walkable.get(tile_location) {
+            return None;
+        }
+        Some(chunk.costs[ChunkData::tile_index(tile_location)])
+    }
+
+    /// Walkable neighbours with the cost of moving to them, diagonals can't cut corners.
+    pub fn neighbours(&self, location: IVec2) -> impl Iterator<Item = (IVec2, f32)> + '_ {
+        NEIGHBOURS.into_iter().filter_map(move |offset| {
+            let neighbour = location + offset;
+            let cost = self.get_cost(neighbour)?;
+            if offset.x != 0 && offset.y != 0 {
+                if !self.is_walkable(location + IVec2::new(offset.x, 0))
+                    || !self.is_walkable(location + IVec2::new(0, offset.y))
+                {
+                    return None;
+                }
+                return Some((neighbour, cost * std::f32::consts::SQRT_2));
+            }
+            Some((neighbour, cost))
+        })
+    }
+
+    /// Whether every tile of a path can still be walked on.
+    pub fn is_path_clear(&self, path: &[IVec2]) -> bool {
+        path.iter().all(|location| self.is_walkable(*location))
+    }
+
+    /// Bounds of the chunks in tiles, inclusive.
+    pub fn get_bounds(&self) -> Option<(IVec2, IVec2)> {
+        let min = self.chunks.keys().copied().reduce(IVec2::min)?;
+        let max = self.chunks.keys().copied().reduce(IVec2::max)?;
+        Some((
+            min * TILEMAP_CHUNK_SIZE as i32,
+            (max + IVec2::ONE) * TILEMAP_CHUNK_SIZE as i32 - IVec2::ONE,
+        ))
+    }
+}
+
+/// Applies the chunks of the navigation tilemap edited since the last frame to the grid.
+///
+/// Runs before [`crate::tilemap::chunk::tilemap_chunk_system`] takes the dirty chunks.
+pub fn navigation_grid_system(
+    mut navigation_grid: ResMut<NavigationGrid>,
+    added_query: Query<(), Added<NavigationTilemapComponent>>,
+    tilemap_query: Query<&TilemapComponent, With<NavigationTilemapComponent>>,
+) {
+    let tilemap = match tilemap_query.get_single() {
+        Ok(tilemap) => tilemap,
+        Err(_) => return,
+    };
+    let layers = tilemap.layers().iter().map(|layer| &layer.data);
+    if !added_query.is_empty() {
+        navigation_grid.rebuild(layers);
+        return;
+    }
+    let dirty_chunks: HashSet<IVec2> = layers
+        .clone()
+        .flat_map(|tilemap| tilemap.dirty_chunks().iter().copied())
+        .collect();
+    for location in dirty_chunks {
+        navigation_grid.update_chunk_layers(layers.clone(), location);
+    }
+}
+
+/// Entry of the open sets of the searches, the smallest cost first and then the smallest location
+/// so equal costs always resolve the same way.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct SearchNode {
+    cost: f32,
+    location: IVec2,
+}
+
+impl Eq for SearchNode {}
+
+impl Ord for SearchNode {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.cost.total_cmp(&self.cost).then_with(|| {
+            (other.location.x, other.location.y).cmp(&(self.location.x, self.location.y))
+        })
+    }
+}
+
+impl PartialOrd for SearchNode {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::pathfinding::plugin::NavigationPlugin;
+    use crate::tilemap::bundle::{TilemapBundle, TilemapLayer};
+    use crate::tilemap::chunk::tilemap_chunk_system;
+    use crate::tilemap::data::TileData;
+    use crate::tilemap::properties::{TileProperties, TilesetProperties};
+    use bevy::asset::AssetPlugin;
+
+    pub const FLOOR: usize = 0;
+    pub const WALL: usize = 1;
+    pub const MUD: usize = 2;
+
+    /// Tilemap of `.` floors, `#` walls and `~` mud costing 3 in a chunk of walls, the first row is
+    /// the top.
+    pub fn make_tilemap(rows: &[&str]) -> TilemapData {
+        let mut properties = TilesetProperties::default();
+        properties.set(
+            WALL,
+            TileProperties {
+                flags: TileFlags::empty(),
+                movement_cost: 1.0,
+            },
+        );
+        properties.set(
+            MUD,
+            TileProperties {
+                flags: TileFlags::WALKABLE,
+                movement_cost: 3.0,
+            },
+        );
+        let mut tilemap = TilemapData::new().with_properties(properties);
+        let mut chunk = ChunkData::new();
+        chunk.tiles.fill(TileData::new(WALL));
+        tilemap.insert_chunk(IVec2::ZERO, chunk);
+        for (y, row) in rows.iter().rev().enumerate() {
+            for (x, c) in row.chars().enumerate() {
+                let atlas_index = match c {
+                    '#' => WALL,
+                    '~' => MUD,
+                    _ => FLOOR,
+                };
+                tilemap.set_tile(IVec2::new(x as i32, y as i32), TileData::new(atlas_index));
+            }
+        }
+        tilemap
+    }
+
+    #[test]
+    fn grid() {
+        let mut tilemap = make_tilemap(&[
+            "..#", //
+            ".~.", //
+        ]);
+        let mut grid = NavigationGrid::from_tilemap(&tilemap);
+        assert!(grid.is_walkable(IVec2::new(0, 0)));
+        assert!(!grid.is_walkable(IVec2::new(2, 1)));
+        assert_eq!(grid.get_cost(IVec2::new(1, 0)), Some(3.0));
+        assert!(!grid.is_walkable(IVec2::new(5, 5)));
+        assert!(!grid.is_walkable(IVec2::new(-1, 0)));
+
+        let version = grid.version();
+        tilemap.set_tile(IVec2::new(0, 0), TileData::new(WALL));
+        grid.update_tile(&tilemap, IVec2::new(0, 0));
+        assert!(!grid.is_walkable(IVec2::new(0, 0)));
+        assert!(grid.version() > version);
+
+        grid.set_obstacle(IVec2::new(1, 1), true);
+        assert!(!grid.is_walkable(IVec2::new(1, 1)));
+        grid.set_obstacle(IVec2::new(1, 1), false);
+        assert!(grid.is_walkable(IVec2::new(1, 1)));
+
+        // Moving diagonally past the walls at (0, 0) and (2, 1) would cut their corners.
+        let neighbours: Vec<IVec2> = grid
+            .neighbours(IVec2::new(1, 1))
+            .map(|(location, _)| location)
+            .collect();
+        assert_eq!(neighbours, vec![IVec2::new(1, 0), IVec2::new(0, 1)]);
+    }
+
+    #[test]
+    fn layers() {
+        let ground = make_tilemap(&[
+            "...", //
+            "...", //
+        ]);
+        let mut decals = make_tilemap(&[
+            "...", //
+            ".~#", //
+        ]);
+        let grid = NavigationGrid::from_layers([&ground, &decals]);
+        assert!(grid.is_walkable(IVec2::new(0, 1)));
+        assert_eq!(grid.get_cost(IVec2::new(1, 0)), Some(3.0));
+        assert!(!grid.is_walkable(IVec2::new(2, 0)));
+
+        // Empty tiles show the layers below, tiles no layer has can't be walked on.
+        decals.set_tile(IVec2::new(2, 0), TileData::EMPTY);
+        let grid = NavigationGrid::from_layers([&ground, &decals]);
+        assert!(grid.is_walkable(IVec2::new(2, 0)));
+        assert_eq!(grid.get_cost(IVec2::new(2, 0)), Some(1.0));
+        let mut holed = ground.clone();
+        holed.set_tile(IVec2::new(2, 0), TileData::EMPTY);
+        let grid = NavigationGrid::from_layers([&holed, &decals]);
+        assert!(grid.is_walkable(IVec2::new(1, 1)));
+        assert!(!grid.is_walkable(IVec2::new(2, 0)));
+
+        // Chunks only some layers have follow those.
+        decals.remove_chunk(IVec2::ZERO);
+        decals.set_tile(IVec2::new(-1, 0), TileData::new(MUD));
+        let grid = NavigationGrid::from_layers([&ground, &decals]);
+        assert!(grid.is_walkable(IVec2::new(2, 0)));
+        assert_eq!(grid.get_cost(IVec2::new(-1, 0)), Some(3.0));
+    }
+
+    #[test]
+    fn sync_with_tilemap() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_plugin(AssetPlugin::default())
+            .add_asset::<Mesh>()
+            .add_plugin(NavigationPlugin)
+            .add_system(tilemap_chunk_system.in_base_set(CoreSet::PostUpdate));
+        let tilemap_entity = app
+            .world
+            .spawn((
+                TilemapBundle::new([TilemapLayer::new(
+                    "ground",
+                    make_tilemap(&["..#"]),
+                    Default::default(),
+                    Default::default(),
+                )]),
+                NavigationTilemapComponent,
+            ))
+            .id();
+        app.update();
+        let grid = app.world.resource::<NavigationGrid>();
+        assert!(grid.is_walkable(IVec2::new(0, 0)));
+        assert!(!grid.is_walkable(IVec2::new(2, 0)));
+
+        // Edits reach the grid before the chunk meshes take the dirty chunks.
+        let version = grid.version();
+        let mut tilemap = app
+            .world
+            .get_mut::<TilemapComponent>(tilemap_entity)
+            .unwrap();
+        let layer = tilemap.get_layer_mut("ground").unwrap();
+        layer.data.set_tile(IVec2::new(0, 0), TileData::new(WALL));
+        layer.data.set_tile(IVec2::new(40, 0), TileData::new(FLOOR));
+        app.update();
+        let grid = app.world.resource::<NavigationGrid>();
+        assert!(!grid.is_walkable(IVec2::new(0, 0)));
+        assert!(grid.is_walkable(IVec2::new(40, 0)));
+        assert!(grid.version() > version);
+        let tilemap = app.world.get::<TilemapComponent>(tilemap_entity).unwrap();
+        assert!(tilemap.layers()[0].data.dirty_chunks().is_empty());
+    }
+}
@@ -0,0 +1,17 @@
+use crate::pathfinding::{navigation_grid_system, NavigationGrid};
+use crate::tilemap::chunk::tilemap_chunk_system;
+use bevy::prelude::*;
+
+/// Keeps the [`NavigationGrid`] in sync with the tilemap having a
+/// [`crate::pathfinding::NavigationTilemapComponent`].
+pub struct NavigationPlugin;
+
+impl Plugin for NavigationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<NavigationGrid>().add_system(
+            navigation_grid_system
+                .in_base_set(CoreSet::PostUpdate)
+                .before(tilemap_chunk_system),
+        );
+    }
+}
@@ -2,11 +2,18 @@ use bevy::app::PluginGroupBuilder;
 use bevy::prelude::*;
 
 use crate::asset::load::AssetLoadPlugin;
+use crate::building::plugin::BuildingPlugin;
 use crate::camera::MainCameraPlugin;
+use crate::combat::plugin::CombatPlugin;
+use crate::economy::plugin::EconomyPlugin;
 use crate::input_manager::InputManagerPlugin;
 use crate::lighting::LightingPlugin;
+use crate::pathfinding::plugin::NavigationPlugin;
+use crate::prototype::plugin::PrototypePlugin;
 use crate::state::AppState;
+use crate::targeting::plugin::TargetingPlugin;
 use crate::tilemap::plugin::TilemapPlugin;
+use crate::wave::plugin::WavePlugin;
 use crate::world_material::plugin::WorldMaterialPlugin;
 
 pub struct AppCorePlugin;
@@ -18,7 +25,14 @@ impl Plugin for AppCorePlugin {
             .add_plugin(InputManagerPlugin)
             .add_plugin(WorldMaterialPlugin)
             .add_plugin(LightingPlugin)
+            .add_plugin(PrototypePlugin)
             .add_plugin(TilemapPlugin)
+            .add_plugin(NavigationPlugin)
+            .add_plugin(BuildingPlugin)
+            .add_plugin(WavePlugin)
+            .add_plugin(CombatPlugin)
+            .add_plugin(TargetingPlugin)
+            .add_plugin(EconomyPlugin)
             .add_plugin(MainCameraPlugin);
     }
 }
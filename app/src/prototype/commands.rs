@@ -0,0 +1,275 @@
+use crate::actor::{ColliderComponent, HealthComponent, SpeedComponent};
+use crate::combat::{
+    ArmorComponent, BountyComponent, InvulnerabilityComponent, ResistancesComponent,
+};
+use crate::lighting::LightComponent;
+use crate::prototype::{PrototypeComponent, PrototypeRegistry};
+use crate::targeting::{WeaponComponent, WeaponDefinition};
+use crate::upgrade::{modify_stat, Stat, StatModifier, UpgradesComponent};
+use crate::wave::EnemyComponent;
+use bevy::ecs::system::{Command, EntityCommands};
+use bevy::prelude::*;
+use bevy::sprite::Mesh2dHandle;
+use std::time::Duration;
+
+/// Inserts the components of a prototype into an entity.
+///
+/// When the entity already has a [`HealthComponent`] only its maximum is updated, so reapplying
+/// a reloaded prototype doesn't heal. The [`EnemyComponent`] modifiers scale health, speed and
+/// bounty, and the upgrades in the [`UpgradesComponent`] modify stats and visuals.
+pub struct ApplyPrototype {
+    pub entity: Entity,
+    pub id: String,
+}
+
+pub trait SpawnPrototypeExt<'w, 's> {
+    /// Spawns an entity made from a prototype, see [`ApplyPrototype`].
+    fn spawn_prototype<'a>(
+        &'a mut self,
+        id: impl Into<String>,
+        transform: Transform,
+    ) -> EntityCommands<'w, 's, 'a>;
+}
+
+pub trait InsertPrototypeExt {
+    fn insert_prototype(&mut self, id: impl Into<String>) -> &mut Self;
+}
+
+impl<'w, 's> SpawnPrototypeExt<'w, 's> for Commands<'w, 's> {
+    fn spawn_prototype<'a>(
+        &'a mut self,
+        id: impl Into<String>,
+        transform: Transform,
+    ) -> EntityCommands<'w, 's, 'a> {
+        let mut entity_commands = self.spawn(SpatialBundle::from_transform(transform));
+        entity_commands.insert_prototype(id);
+        entity_commands
+    }
+}
+
+impl<'w, 's, 'a> InsertPrototypeExt for EntityCommands<'w, 's, 'a> {
+    fn insert_prototype(&mut self, id: impl Into<String>) -> &mut Self {
+        let entity = self.id();
+        self.commands().add(ApplyPrototype {
+            entity,
+            id: id.into(),
+        });
+        self
+    }
+}
+
+impl Command for ApplyPrototype {
+    fn write(self, world: &mut World) {
+        let prototype = match world
+            .get_resource::<PrototypeRegistry>()
+            .and_then(|registry| registry.get(&self.id))
+        {
+            Some(prototype) => prototype.clone(),
+            None => {
+                error!("unknown prototype `{}`", self.id);
+                return;
+            }
+        };
+        let mut entity = match world.get_entity_mut(self.entity) {
+            Some(entity) => entity,
+            None => return,
+        };
+        let definition = &prototype.definition;
+        let upgrades = entity
+            .get::<UpgradesComponent>()
+            .cloned()
+            .unwrap_or_default();
+        let modifiers: Vec<&StatModifier> = definition
+            .applied_upgrades(&upgrades)
+            .flat_map(|upgrade| upgrade.modifiers.iter())
+            .collect();
+        let modify = |stat, base| modify_stat(stat, base, modifiers.iter().copied());
+
+        entity.insert(PrototypeComponent {
+            id: prototype.id.clone(),
+        });
+        // The last upgrade changing visuals wins.
+        let visual = upgrades
+            .applied
+            .iter()
+            .rev()
+            .find_map(|id| prototype.upgrade_visuals.get(id))
+            .or(prototype.visual.as_ref());
+        if let Some(visual) = visual {
+            entity.insert((Mesh2dHandle(visual.mesh.clone()), visual.material.clone()));
+        }
+        match &definition.light {
+            Some(light) => {
+                entity.insert(LightComponent {
+                    scale: light.scale,
+                    color: Color::from(light.color),
+                });
+            }
+            None => {
+                entity.remove::<LightComponent>();
+            }
+        }
+        // Enemies of a wave can be stronger than their prototype.
+        let enemy_modifiers = entity
+            .get::<EnemyComponent>()
+            .map(|enemy| enemy.modifiers)
+            .unwrap_or_default();
+        match (
+            definition
+                .health
+                .map(|health| modify(Stat::Health, health * enemy_modifiers.health)),
+            entity.get_mut::<HealthComponent>(),
+        ) {
+            (Some(max), Some(mut health)) => {
+                health.current = health.current.min(max);
+                health.max = max;
+            }
+            (Some(max), None) => {
+                entity.insert(HealthComponent::new(max));
+            }
+            (None, _) => {
+                entity.remove::<HealthComponent>();
+            }
+        }
+        match definition.speed {
+            Some(speed) => {
+                entity.insert(SpeedComponent(speed * enemy_modifiers.speed));
+            }
+            None => {
+                entity.remove::<SpeedComponent>();
+            }
+        }
+        match definition.armor {
+            Some(armor) => {
+                entity.insert(ArmorComponent(armor));
+            }
+            None => {
+                entity.remove::<ArmorComponent>();
+            }
+        }
+        entity.insert(ResistancesComponent(definition.resistances.clone()));
+        match definition.invulnerability {
+            Some(seconds) => {
+                entity.insert(InvulnerabilityComponent::new(Duration::from_secs_f32(
+                    seconds,
+                )));
+            }
+            None => {
+                entity.remove::<InvulnerabilityComponent>();
+            }
+        }
+        entity.insert(BountyComponent {
+            bounty: (definition.bounty as f32 * enemy_modifiers.reward).round() as u32,
+            drops: definition.drops.clone(),
+        });
+        match &definition.collider {
+            Some(collider) => {
+                entity.insert(ColliderComponent {
+                    radius: collider.radius,
+                });
+            }
+            None => {
+                entity.remove::<ColliderComponent>();
+            }
+        }
+        // An unchanged weapon keeps its cooldown.
+        let weapon = definition.weapon.clone().map(|weapon| WeaponDefinition {
+            range: modify(Stat::Range, weapon.range),
+            cooldown: modify(Stat::Cooldown, weapon.cooldown).max(0.0),
+            damage: modify(Stat::Damage, weapon.damage),
+            ..weapon
+        });
+        match (weapon, entity.get::<WeaponComponent>()) {
+            (Some(weapon), Some(current)) if current.definition == weapon => {}
+            (Some(weapon), _) => {
+                entity.insert(WeaponComponent::new(weapon));
+            }
+            (None, _) => {
+                entity.remove::<WeaponComponent>();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prototype::tests::PROTOTYPES;
+    use crate::prototype::PrototypeSet;
+    use crate::wave::EnemyModifiers;
+
+    #[test]
+    fn spawn() {
+        let mut world = World::new();
+        let set = PrototypeSet::parse(PROTOTYPES).unwrap();
+        world.insert_resource(PrototypeRegistry::from_sets([&set]).unwrap());
+
+        let mut commands_queue = bevy::ecs::system::CommandQueue::default();
+        let mut commands = Commands::new(&mut commands_queue, &world);
+        let tower = commands
+            .spawn_prototype("cannon_tower", Transform::from_xyz(1.0, 2.0, 0.0))
+            .id();
+        let slime = commands
+            .spawn(SpatialBundle::default())
+            .insert_prototype("slime")
+            .id();
+        let unknown = commands
+            .spawn_prototype("unknown", Transform::IDENTITY)
+            .id();
+        let fast_slime = commands
+            .spawn((
+                SpatialBundle::default(),
+                EnemyComponent {
+                    enemy: "slime".to_string(),
+                    modifiers: EnemyModifiers {
+                        speed: 2.0,
+                        ..Default::default()
+                    },
+                    spawner: Entity::PLACEHOLDER,
+                    wave: 0,
+                },
+            ))
+            .insert_prototype("slime")
+            .id();
+        commands_queue.apply(&mut world);
+
+        assert_eq!(
+            world.get::<PrototypeComponent>(tower).unwrap().id,
+            "cannon_tower"
+        );
+        assert_eq!(
+            world.get::<HealthComponent>(tower),
+            Some(&HealthComponent::new(250.0))
+        );
+        assert_eq!(world.get::<LightComponent>(tower).unwrap().scale, 4.0);
+        assert!(world.get::<ColliderComponent>(tower).is_none());
+        assert_eq!(
+            world.get::<Transform>(tower).unwrap().translation,
+            Vec3::new(1.0, 2.0, 0.0)
+        );
+        assert_eq!(
+            world.get::<ColliderComponent>(slime),
+            Some(&ColliderComponent { radius: 0.4 })
+        );
+        assert!(world.get::<LightComponent>(slime).is_none());
+        assert_eq!(
+            world.get::<SpeedComponent>(slime),
+            Some(&SpeedComponent(1.5))
+        );
+        assert_eq!(
+            world.get::<SpeedComponent>(fast_slime),
+            Some(&SpeedComponent(3.0))
+        );
+        assert!(world.get::<SpeedComponent>(tower).is_none());
+        assert!(world.get::<PrototypeComponent>(unknown).is_none());
+
+        // Reapplying keeps the damage taken.
+        world.get_mut::<HealthComponent>(slime).unwrap().current = 5.0;
+        ApplyPrototype {
+            entity: slime,
+            id: "slime".to_string(),
+        }
+        .write(&mut world);
+        assert_eq!(world.get::<HealthComponent>(slime).unwrap().current, 5.0);
+    }
+}
@@ -0,0 +1,23 @@
+use crate::prototype::PrototypeSet;
+use bevy::asset::{AssetLoader, BoxedFuture, LoadContext, LoadedAsset};
+
+#[derive(Default)]
+pub struct PrototypeSetLoader;
+
+impl AssetLoader for PrototypeSetLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let set = PrototypeSet::parse(std::str::from_utf8(bytes)?)?;
+            load_context.set_default_asset(LoadedAsset::new(set));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["prototypes.yaml"]
+    }
+}
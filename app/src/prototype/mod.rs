@@ -1 +1,626 @@
+use crate::combat::DamageType;
+use crate::economy::Funds;
+use crate::error::{AppError, AppResult};
+use crate::targeting::projectile::ProjectileDefinition;
+use crate::targeting::WeaponDefinition;
+use crate::upgrade::UpgradeDefinition;
+use crate::world_material::material::WorldMaterial;
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use bevy::render::mesh::VertexAttributeValues;
+use bevy::utils::HashMap;
+use serde::Deserialize;
+use serde_yaml::{Mapping, Value};
 
+pub mod commands;
+pub mod loader;
+pub mod plugin;
+
+/// Contents of a `*.prototypes.yaml` file, prototype definitions keyed by id.
+///
+/// Definitions are kept as YAML until every file is loaded, since parents can come from other
+/// files.
+#[derive(TypeUuid, Debug, Clone, Default)]
+#[uuid = "0b7c5a3e-2d41-4f8e-a6c9-5e1d8b2f7a93"]
+pub struct PrototypeSet {
+    definitions: Vec<(String, Value)>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum PrototypeKind {
+    Tower,
+    Enemy,
+    Projectile,
+    Prop,
+}
+
+/// A prototype with its parents merged in.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct PrototypeDefinition {
+    pub kind: PrototypeKind,
+    #[serde(default)]
+    pub sprite: Option<SpriteDefinition>,
+    #[serde(default)]
+    pub light: Option<LightDefinition>,
+    #[serde(default)]
+    pub health: Option<f32>,
+    #[serde(default)]
+    pub collider: Option<ColliderDefinition>,
+    /// Tiles covered by a tower, `[width, height]`.
+    #[serde(default)]
+    pub footprint: Option<[u32; 2]>,
+    /// Tiles per second.
+    #[serde(default)]
+    pub speed: Option<f32>,
+    /// Subtracted from physical damage.
+    #[serde(default)]
+    pub armor: Option<f32>,
+    /// Damage multipliers, 1 for missing damage types.
+    #[serde(default)]
+    pub resistances: HashMap<DamageType, f32>,
+    /// Seconds during which no damage is taken after a hit.
+    #[serde(default)]
+    pub invulnerability: Option<f32>,
+    /// Paid for killing the entity.
+    #[serde(default)]
+    pub bounty: u32,
+    /// Prototypes spawned where the entity dies.
+    #[serde(default)]
+    pub drops: Vec<String>,
+    /// Makes a tower shoot at enemies in range.
+    #[serde(default)]
+    pub weapon: Option<WeaponDefinition>,
+    /// Movement of a projectile fired by a weapon.
+    #[serde(default)]
+    pub projectile: Option<ProjectileDefinition>,
+    #[serde(default)]
+    pub cost: CostDefinition,
+    /// Upgrade tree of a tower.
+    #[serde(default)]
+    pub upgrades: Vec<UpgradeDefinition>,
+}
+
+/// What a tower costs, see [`Economy`](crate::economy::Economy).
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct CostDefinition {
+    /// Paid to place the tower.
+    #[serde(default)]
+    pub build: Funds,
+    /// Refunded when the tower is sold.
+    #[serde(default)]
+    pub sell: Funds,
+}
+
+/// Quad drawn with a [`WorldMaterial`], textures are asset paths.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct SpriteDefinition {
+    #[serde(default)]
+    pub texture: Option<String>,
+    #[serde(default)]
+    pub normal_texture: Option<String>,
+    #[serde(default)]
+    pub emissive_texture: Option<String>,
+    #[serde(default = "default_color")]
+    pub color: [f32; 4],
+    #[serde(default)]
+    pub emissive: [f32; 4],
+    #[serde(default = "default_size")]
+    pub size: [f32; 2],
+    /// Draws a single cell of the textures when they are a grid of sprites.
+    #[serde(default)]
+    pub atlas: Option<AtlasDefinition>,
+}
+
+/// Grid of cells in a texture, indexed row by row from the top left.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct AtlasDefinition {
+    pub columns: u32,
+    pub rows: u32,
+    #[serde(default)]
+    pub index: u32,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct LightDefinition {
+    pub scale: f32,
+    #[serde(default = "default_color")]
+    pub color: [f32; 4],
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ColliderDefinition {
+    pub radius: f32,
+}
+
+fn default_color() -> [f32; 4] {
+    [1.0; 4]
+}
+
+fn default_size() -> [f32; 2] {
+    [1.0; 2]
+}
+
+#[derive(Debug, Clone)]
+pub struct Prototype {
+    pub id: String,
+    pub definition: PrototypeDefinition,
+    /// Made by [`plugin::prototype_registry_system`] when the app has the asset resources.
+    pub visual: Option<PrototypeVisual>,
+    /// Visuals of the upgrades changing the sprite, keyed by upgrade id.
+    pub upgrade_visuals: HashMap<String, PrototypeVisual>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PrototypeVisual {
+    pub mesh: Handle<Mesh>,
+    pub material: Handle<WorldMaterial>,
+}
+
+/// Resolved prototypes of every loaded [`PrototypeSet`], rebuilt when one changes.
+#[derive(Resource, Debug, Clone, Default)]
+pub struct PrototypeRegistry {
+    prototypes: HashMap<String, Prototype>,
+}
+
+/// Id of the prototype an entity was spawned from.
+#[derive(Component, Debug, Clone, PartialEq, Eq)]
+pub struct PrototypeComponent {
+    pub id: String,
+}
+
+const PARENT_KEY: &str = "parent";
+const ABSTRACT_KEY: &str = "abstract";
+
+impl PrototypeSet {
+    pub fn parse(source: &str) -> AppResult<Self> {
+        let mapping: Mapping = serde_yaml::from_str(source).map_err(AppError::PrototypeSyntax)?;
+        let mut definitions = Vec::with_capacity(mapping.len());
+        for (id, definition) in mapping {
+            let id = match id {
+                Value::String(id) => id,
+                id => {
+                    return Err(AppError::InvalidPrototype {
+                        id: format!("{:?}", id),
+                        message: "ids must be strings".to_string(),
+                    })
+                }
+            };
+            if !definition.is_mapping() {
+                return Err(AppError::InvalidPrototype {
+                    id,
+                    message: "definitions must be mappings".to_string(),
+                });
+            }
+            definitions.push((id, definition));
+        }
+        Ok(PrototypeSet { definitions })
+    }
+}
+
+impl PrototypeDefinition {
+    pub fn footprint_size(&self) -> Option<UVec2> {
+        self.footprint.map(UVec2::from)
+    }
+}
+
+impl SpriteDefinition {
+    /// Quad of `size` showing the atlas cell, or the whole textures without an atlas.
+    pub fn make_mesh(&self) -> Mesh {
+        let mut mesh = Mesh::from(shape::Quad::new(Vec2::from(self.size)));
+        if let Some(atlas) = &self.atlas {
+            let cell = UVec2::new(atlas.index % atlas.columns, atlas.index / atlas.columns);
+            let grid = Vec2::new(atlas.columns as f32, atlas.rows as f32);
+            if let Some(VertexAttributeValues::Float32x2(uvs)) =
+                mesh.attribute_mut(Mesh::ATTRIBUTE_UV_0)
+            {
+                for uv in uvs.iter_mut() {
+                    *uv = ((cell.as_vec2() + Vec2::from(*uv)) / grid).into();
+                }
+            }
+        }
+        mesh
+    }
+
+    pub fn make_material(&self, asset_server: &AssetServer) -> WorldMaterial {
+        WorldMaterial {
+            base_color: Color::from(self.color),
+            base_color_texture: self.texture.as_ref().map(|path| asset_server.load(path)),
+            normal_texture: self
+                .normal_texture
+                .as_ref()
+                .map(|path| asset_server.load(path)),
+            emissive: Color::from(self.emissive),
+            emissive_texture: self
+                .emissive_texture
+                .as_ref()
+                .map(|path| asset_server.load(path)),
+            alpha_mode: AlphaMode::Blend,
+            ..Default::default()
+        }
+    }
+}
+
+impl PrototypeRegistry {
+    /// Merges every definition with its parents and checks the result.
+    ///
+    /// Prototypes marked `abstract: true` are only used as parents and don't need to be complete.
+    pub fn from_sets<'a>(sets: impl IntoIterator<Item = &'a PrototypeSet>) -> AppResult<Self> {
+        let mut definitions: HashMap<&str, &Value> = HashMap::default();
+        // Sorted so errors don't depend on the hash map order.
+        let mut ids = Vec::new();
+        for set in sets {
+            for (id, definition) in set.definitions.iter() {
+                if definitions.insert(id, definition).is_some() {
+                    return Err(AppError::DuplicatePrototype(id.clone()));
+                }
+                ids.push(id.as_str());
+            }
+        }
+        ids.sort_unstable();
+
+        let mut resolved = HashMap::default();
+        let mut prototypes = HashMap::default();
+        for id in ids {
+            let value = resolve(id, &definitions, &mut resolved, &mut Vec::new())?;
+            if is_abstract(definitions[id]) {
+                continue;
+            }
+            let definition: PrototypeDefinition =
+                serde_yaml::from_value(value).map_err(|err| AppError::InvalidPrototype {
+                    id: id.to_string(),
+                    message: err.to_string(),
+                })?;
+            validate(id, &definition)?;
+            prototypes.insert(
+                id.to_string(),
+                Prototype {
+                    id: id.to_string(),
+                    definition,
+                    visual: None,
+                    upgrade_visuals: HashMap::default(),
+                },
+            );
+        }
+        Ok(PrototypeRegistry { prototypes })
+    }
+
+    pub fn get(&self, id: &str) -> Option<&Prototype> {
+        self.prototypes.get(id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Prototype> {
+        self.prototypes.values()
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Prototype> {
+        self.prototypes.values_mut()
+    }
+
+    pub fn len(&self) -> usize {
+        self.prototypes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.prototypes.is_empty()
+    }
+}
+
+fn is_abstract(definition: &Value) -> bool {
+    definition
+        .get(ABSTRACT_KEY)
+        .and_then(Value::as_bool)
+        .unwrap_or(false)
+}
+
+/// Definition of `id` merged over its parents, without the `parent` and `abstract` keys.
+fn resolve(
+    id: &str,
+    definitions: &HashMap<&str, &Value>,
+    resolved: &mut HashMap<String, Value>,
+    stack: &mut Vec<String>,
+) -> AppResult<Value> {
+    if let Some(value) = resolved.get(id) {
+        return Ok(value.clone());
+    }
+    if stack.iter().any(|parent| parent == id) {
+        return Err(AppError::PrototypeCycle(id.to_string()));
+    }
+    let definition = definitions[id];
+    let mut value = match definition.get(PARENT_KEY) {
+        Some(Value::String(parent)) => {
+            if !definitions.contains_key(parent.as_str()) {
+                return Err(AppError::UnknownPrototypeParent {
+                    id: id.to_string(),
+                    parent: parent.clone(),
+                });
+            }
+            stack.push(id.to_string());
+            let parent_value = resolve(parent, definitions, resolved, stack)?;
+            stack.pop();
+            parent_value
+        }
+        Some(_) => {
+            return Err(AppError::InvalidPrototype {
+                id: id.to_string(),
+                message: "`parent` must be a prototype id".to_string(),
+            })
+        }
+        None => Value::Mapping(Mapping::new()),
+    };
+    merge(&mut value, definition);
+    if let Value::Mapping(mapping) = &mut value {
+        mapping.remove(PARENT_KEY);
+        mapping.remove(ABSTRACT_KEY);
+    }
+    resolved.insert(id.to_string(), value.clone());
+    Ok(value)
+}
+
+/// Overrides `base` with `overrides`, merging nested mappings key by key.
+fn merge(base: &mut Value, overrides: &Value) {
+    match (base, overrides) {
+        (Value::Mapping(base), Value::Mapping(overrides)) => {
+            for (key, value) in overrides {
+                match base.get_mut(key) {
+                    Some(base_value) => merge(base_value, value),
+                    None => {
+                        base.insert(key.clone(), value.clone());
+                    }
+                }
+            }
+        }
+        (base, overrides) => *base = overrides.clone(),
+    }
+}
+
+fn validate(id: &str, definition: &PrototypeDefinition) -> AppResult<()> {
+    let invalid = |message: &str| -> AppResult<()> {
+        Err(AppError::InvalidPrototype {
+            id: id.to_string(),
+            message: message.to_string(),
+        })
+    };
+    if definition.health.map_or(false, |health| health <= 0.0) {
+        return invalid("`health` must be positive");
+    }
+    if definition.armor.map_or(false, |armor| armor < 0.0) {
+        return invalid("`armor` can't be negative");
+    }
+    if definition
+        .resistances
+        .values()
+        .any(|resistance| *resistance < 0.0)
+    {
+        return invalid("`resistances` can't be negative");
+    }
+    if definition
+        .invulnerability
+        .map_or(false, |seconds| !(seconds >= 0.0 && seconds.is_finite()))
+    {
+        return invalid("`invulnerability` can't be negative");
+    }
+    if let Some(collider) = &definition.collider {
+        if collider.radius <= 0.0 {
+            return invalid("`collider.radius` must be positive");
+        }
+    }
+    if let Some([width, height]) = definition.footprint {
+        if width == 0 || height == 0 {
+            return invalid("`footprint` can't be empty");
+        }
+    }
+    if definition.footprint.is_some() && definition.kind != PrototypeKind::Tower {
+        return invalid("only towers have a `footprint`");
+    }
+    if let Some(weapon) = &definition.weapon {
+        if definition.kind != PrototypeKind::Tower {
+            return invalid("only towers have a `weapon`");
+        }
+        if weapon.range <= 0.0 {
+            return invalid("`weapon.range` must be positive");
+        }
+        if weapon.cooldown < 0.0 {
+            return invalid("`weapon.cooldown` can't be negative");
+        }
+    }
+    if definition.cost != CostDefinition::default() && definition.kind != PrototypeKind::Tower {
+        return invalid("only towers have a `cost`");
+    }
+    if let Some(projectile) = &definition.projectile {
+        if definition.kind != PrototypeKind::Projectile {
+            return invalid("only projectiles have a `projectile`");
+        }
+        if projectile.speed <= 0.0 {
+            return invalid("`projectile.speed` must be positive");
+        }
+    }
+    if let Some(sprite) = &definition.sprite {
+        validate_sprite(sprite).or_else(|message| invalid(message))?;
+    }
+    if !definition.upgrades.is_empty() && definition.kind != PrototypeKind::Tower {
+        return invalid("only towers have `upgrades`");
+    }
+    for (i, upgrade) in definition.upgrades.iter().enumerate() {
+        let upgrade_invalid =
+            |message: &str| invalid(&format!("upgrade `{}`: {}", upgrade.id, message));
+        if definition.upgrades[..i]
+            .iter()
+            .any(|other| other.id == upgrade.id)
+        {
+            return upgrade_invalid("defined more than once");
+        }
+        if upgrade.tier == 0 {
+            return upgrade_invalid("`tier` starts at 1");
+        }
+        for required in upgrade.requires.iter() {
+            match definition.upgrade(required) {
+                Some(other) if other.tier < upgrade.tier => {}
+                Some(_) => return upgrade_invalid("can only require upgrades of lower tiers"),
+                None => return upgrade_invalid(&format!("requires unknown `{}`", required)),
+            }
+        }
+        if upgrade.atlas_index.is_some()
+            && upgrade
+                .sprite
+                .as_ref()
+                .or(definition.sprite.as_ref())
+                .and_then(|sprite| sprite.atlas.as_ref())
+                .is_none()
+        {
+            return upgrade_invalid("`atlas_index` needs a sprite with an `atlas`");
+        }
+        if let Some(sprite) = upgrade.sprite(definition.sprite.as_ref()) {
+            validate_sprite(&sprite).or_else(|message| upgrade_invalid(message))?;
+        }
+    }
+    Ok(())
+}
+
+fn validate_sprite(sprite: &SpriteDefinition) -> Result<(), &'static str> {
+    if let Some(atlas) = &sprite.atlas {
+        if atlas.columns == 0 || atlas.rows == 0 {
+            return Err("`atlas` can't be empty");
+        }
+        if atlas.index >= atlas.columns * atlas.rows {
+            return Err("`atlas.index` is out of the atlas");
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    pub const PROTOTYPES: &str = r#"
+tower:
+  abstract: true
+  kind: tower
+  health: 100
+  footprint: [1, 1]
+  sprite:
+    texture: graphics/box-d.png
+    normal_texture: graphics/box-n.png
+arrow_tower:
+  parent: tower
+  light:
+    scale: 4
+    color: [1.0, 0.8, 0.5, 1.0]
+cannon_tower:
+  parent: arrow_tower
+  health: 250
+  footprint: [2, 2]
+  sprite:
+    color: [0.5, 0.5, 0.5, 1.0]
+slime:
+  kind: enemy
+  health: 20
+  speed: 1.5
+  armor: 2
+  resistances:
+    fire: 0.5
+  invulnerability: 0.2
+  bounty: 5
+  drops: [gem]
+  collider:
+    radius: 0.4
+gem:
+  kind: prop
+"#;
+
+    #[test]
+    fn inheritance() {
+        let set = PrototypeSet::parse(PROTOTYPES).unwrap();
+        let registry = PrototypeRegistry::from_sets([&set]).unwrap();
+        assert_eq!(registry.len(), 4);
+        assert!(registry.get("tower").is_none());
+
+        let arrow_tower = &registry.get("arrow_tower").unwrap().definition;
+        assert_eq!(arrow_tower.kind, PrototypeKind::Tower);
+        assert_eq!(arrow_tower.health, Some(100.0));
+        assert_eq!(arrow_tower.footprint_size(), Some(UVec2::ONE));
+
+        let cannon_tower = &registry.get("cannon_tower").unwrap().definition;
+        assert_eq!(cannon_tower.health, Some(250.0));
+        assert_eq!(cannon_tower.footprint_size(), Some(UVec2::splat(2)));
+        assert_eq!(cannon_tower.light, arrow_tower.light);
+        let sprite = cannon_tower.sprite.as_ref().unwrap();
+        assert_eq!(sprite.texture.as_deref(), Some("graphics/box-d.png"));
+        assert_eq!(sprite.color, [0.5, 0.5, 0.5, 1.0]);
+        assert_eq!(sprite.size, [1.0, 1.0]);
+
+        // Parents can come from other sets.
+        let other_set = PrototypeSet::parse("big_slime: {parent: slime, health: 80}").unwrap();
+        let registry = PrototypeRegistry::from_sets([&set, &other_set]).unwrap();
+        let big_slime = &registry.get("big_slime").unwrap().definition;
+        assert_eq!(big_slime.health, Some(80.0));
+        assert_eq!(big_slime.speed, Some(1.5));
+        assert_eq!(big_slime.resistances[&DamageType::Fire], 0.5);
+    }
+
+    #[test]
+    fn errors() {
+        let registry = |sources: &[&str]| {
+            let sets: Vec<PrototypeSet> = sources
+                .iter()
+                .map(|source| PrototypeSet::parse(source).unwrap())
+                .collect();
+            PrototypeRegistry::from_sets(sets.iter())
+        };
+        assert!(matches!(
+            PrototypeSet::parse("[1, 2]"),
+            Err(AppError::PrototypeSyntax(_))
+        ));
+        assert!(matches!(
+            registry(&["a: {kind: prop}", "a: {kind: prop}"]),
+            Err(AppError::DuplicatePrototype(id)) if id == "a"
+        ));
+        assert!(matches!(
+            registry(&["a: {parent: b}"]),
+            Err(AppError::UnknownPrototypeParent { id, parent }) if id == "a" && parent == "b"
+        ));
+        assert!(matches!(
+            registry(&["a: {parent: b}\nb: {parent: a}"]),
+            Err(AppError::PrototypeCycle(_))
+        ));
+        assert!(matches!(
+            registry(&["a: {health: 10}"]),
+            Err(AppError::InvalidPrototype { id, .. }) if id == "a"
+        ));
+        assert!(matches!(
+            registry(&["a: {kind: prop, helth: 10}"]),
+            Err(AppError::InvalidPrototype { .. })
+        ));
+        assert!(matches!(
+            registry(&["a: {kind: enemy, resistances: {frost: -1}}"]),
+            Err(AppError::InvalidPrototype { .. })
+        ));
+        assert!(matches!(
+            registry(&["a: {kind: enemy, invulnerability: -0.5}"]),
+            Err(AppError::InvalidPrototype { .. })
+        ));
+        assert!(matches!(
+            registry(&["a: {kind: enemy, invulnerability: .nan}"]),
+            Err(AppError::InvalidPrototype { .. })
+        ));
+        assert!(matches!(
+            registry(&["a: {kind: enemy, footprint: [1, 1]}"]),
+            Err(AppError::InvalidPrototype { .. })
+        ));
+        assert!(matches!(
+            registry(&["a: {kind: prop, weapon: {range: 1, cooldown: 1, damage: 1}}"]),
+            Err(AppError::InvalidPrototype { .. })
+        ));
+        assert!(matches!(
+            registry(&["a: {kind: projectile, projectile: {motion: homing, speed: 0}}"]),
+            Err(AppError::InvalidPrototype { .. })
+        ));
+    }
+}
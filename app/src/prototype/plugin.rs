@@ -0,0 +1,73 @@
+use crate::prototype::commands::ApplyPrototype;
+use crate::prototype::loader::PrototypeSetLoader;
+use crate::prototype::{
+    PrototypeComponent, PrototypeRegistry, PrototypeSet, PrototypeVisual, SpriteDefinition,
+};
+use crate::world_material::material::WorldMaterial;
+use bevy::prelude::*;
+
+pub struct PrototypePlugin;
+
+impl Plugin for PrototypePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<PrototypeSet>()
+            .init_asset_loader::<PrototypeSetLoader>()
+            .init_resource::<PrototypeRegistry>()
+            .add_system(prototype_registry_system.in_base_set(CoreSet::PreUpdate));
+    }
+}
+
+/// Rebuilds the registry when a prototype set is loaded, changed or removed, and reapplies the
+/// prototypes to the entities spawned from them.
+///
+/// Invalid sets are reported and the previous registry is kept.
+#[allow(clippy::too_many_arguments)]
+pub fn prototype_registry_system(
+    mut commands: Commands,
+    mut asset_events: EventReader<AssetEvent<PrototypeSet>>,
+    sets: Res<Assets<PrototypeSet>>,
+    mut registry: ResMut<PrototypeRegistry>,
+    asset_server: Option<Res<AssetServer>>,
+    meshes: Option<ResMut<Assets<Mesh>>>,
+    materials: Option<ResMut<Assets<WorldMaterial>>>,
+    prototype_query: Query<(Entity, &PrototypeComponent)>,
+) {
+    if asset_events.iter().count() == 0 {
+        return;
+    }
+    let mut next_registry = match PrototypeRegistry::from_sets(sets.iter().map(|(_, set)| set)) {
+        Ok(registry) => registry,
+        Err(err) => {
+            error!("{}", err);
+            return;
+        }
+    };
+
+    if let (Some(asset_server), Some(mut meshes), Some(mut materials)) =
+        (asset_server, meshes, materials)
+    {
+        let mut make_visual = |sprite: &SpriteDefinition| PrototypeVisual {
+            mesh: meshes.add(sprite.make_mesh()),
+            material: materials.add(sprite.make_material(&asset_server)),
+        };
+        for prototype in next_registry.iter_mut() {
+            let definition = &prototype.definition;
+            prototype.visual = definition.sprite.as_ref().map(&mut make_visual);
+            for upgrade in definition.upgrades.iter() {
+                if let Some(sprite) = upgrade.sprite(definition.sprite.as_ref()) {
+                    prototype
+                        .upgrade_visuals
+                        .insert(upgrade.id.clone(), make_visual(&sprite));
+                }
+            }
+        }
+    }
+
+    *registry = next_registry;
+    for (entity, prototype) in prototype_query.iter() {
+        commands.add(ApplyPrototype {
+            entity,
+            id: prototype.id.clone(),
+        });
+    }
+}
@@ -0,0 +1,354 @@
+use crate::tilemap::TILEMAP_CHUNK_SIZE;
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use std::marker::PhantomData;
+
+pub mod plugin;
+
+/// Systems updating the spatial indices, queries are up to date after this set.
+#[derive(SystemSet, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SpatialSet {
+    Index,
+}
+
+/// Positions of the entities with a `T` component, bucketed in square cells for proximity
+/// queries.
+///
+/// Kept up to date by [`spatial_index_system`], which only looks at changed transforms, and
+/// [`spatial_removal_system`]. Cells default to the size of a tilemap chunk, 1 world unit being
+/// 1 tile.
+#[derive(Resource, Debug, Clone)]
+pub struct SpatialIndex<T: Component> {
+    cell_size: f32,
+    cells: HashMap<IVec2, Vec<(Entity, Vec2)>>,
+    positions: HashMap<Entity, Vec2>,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T: Component> Default for SpatialIndex<T> {
+    fn default() -> Self {
+        SpatialIndex::with_cell_size(TILEMAP_CHUNK_SIZE as f32)
+    }
+}
+
+impl<T: Component> SpatialIndex<T> {
+    pub fn with_cell_size(cell_size: f32) -> Self {
+        assert!(cell_size > 0.0, "cell size must be positive");
+        SpatialIndex {
+            cell_size,
+            cells: HashMap::default(),
+            positions: HashMap::default(),
+            marker: PhantomData,
+        }
+    }
+
+    pub fn cell_size(&self) -> f32 {
+        self.cell_size
+    }
+
+    /// Cell containing a position.
+    pub fn position_to_cell(&self, position: Vec2) -> IVec2 {
+        (position / self.cell_size).floor().as_ivec2()
+    }
+
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    pub fn get(&self, entity: Entity) -> Option<Vec2> {
+        self.positions.get(&entity).copied()
+    }
+
+    pub fn clear(&mut self) {
+        self.cells.clear();
+        self.positions.clear();
+    }
+
+    /// Inserts an entity, or moves it when it's already indexed.
+    pub fn insert(&mut self, entity: Entity, position: Vec2) {
+        let cell = self.position_to_cell(position);
+        match self.positions.insert(entity, position) {
+            Some(previous) if self.position_to_cell(previous) == cell => {
+                let entry = self
+                    .cells
+                    .get_mut(&cell)
+                    .and_then(|entities| entities.iter_mut().find(|(other, _)| *other == entity));
+                if let Some(entry) = entry {
+                    entry.1 = position;
+                }
+                return;
+            }
+            Some(previous) => self.remove_from_cell(entity, previous),
+            None => {}
+        }
+        self.cells.entry(cell).or_default().push((entity, position));
+    }
+
+    pub fn remove(&mut self, entity: Entity) -> Option<Vec2> {
+        let position = self.positions.remove(&entity)?;
+        self.remove_from_cell(entity, position);
+        Some(position)
+    }
+
+    fn remove_from_cell(&mut self, entity: Entity, position: Vec2) {
+        let cell = self.position_to_cell(position);
+        if let Some(entities) = self.cells.get_mut(&cell) {
+            entities.retain(|(other, _)| *other != entity);
+            if entities.is_empty() {
+                self.cells.remove(&cell);
+            }
+        }
+    }
+
+    /// Entities inside the cells overlapping `min..=max`, not filtered by position.
+    fn cells_in(&self, min: Vec2, max: Vec2) -> impl Iterator<Item = (Entity, Vec2)> + '_ {
+        let min = self.position_to_cell(min);
+        let max = self.position_to_cell(max);
+        (min.x..=max.x)
+            .flat_map(move |x| (min.y..=max.y).map(move |y| IVec2::new(x, y)))
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+            .copied()
+    }
+
+    /// Entities within `radius` of `center`, in no particular order.
+    pub fn query_radius(
+        &self,
+        center: Vec2,
+        radius: f32,
+    ) -> impl Iterator<Item = (Entity, Vec2)> + '_ {
+        self.cells_in(center - Vec2::splat(radius), center + Vec2::splat(radius))
+            .filter(move |(_, position)| position.distance_squared(center) <= radius * radius)
+    }
+
+    /// Entities inside the rectangle `min..=max`, in no particular order.
+    pub fn query_aabb(&self, min: Vec2, max: Vec2) -> impl Iterator<Item = (Entity, Vec2)> + '_ {
+        self.cells_in(min, max)
+            .filter(move |(_, position)| position.cmpge(min).all() && position.cmple(max).all())
+    }
+
+    /// The `k` entities closest to `center`, closest first, equal distances sorted by entity.
+    pub fn nearest(&self, center: Vec2, k: usize) -> Vec<(Entity, Vec2)> {
+        let mut found: Vec<(Entity, Vec2)> = Vec::new();
+        if k == 0 {
+            return found;
+        }
+        let compare = |a: &(Entity, Vec2), b: &(Entity, Vec2)| {
+            a.1.distance_squared(center)
+                .total_cmp(&b.1.distance_squared(center))
+                .then_with(|| a.0.cmp(&b.0))
+        };
+        let origin = self.position_to_cell(center);
+        let mut visited = 0;
+        // Searches rings of cells around the center until the entities outside the searched
+        // square can't be closer than the k-th found.
+        for ring in 0.. {
+            if visited == self.len() {
+                break;
+            }
+            // Once the square has more cells than are occupied, most of them are empty and
+            // scanning every entity is cheaper.
+            if ((2 * ring + 1) as usize).pow(2) > self.cells.len() {
+                found = self
+                    .positions
+                    .iter()
+                    .map(|(entity, position)| (*entity, *position))
+                    .collect();
+                break;
+            }
+            for cell in ring_cells(origin, ring) {
+                if let Some(entities) = self.cells.get(&cell) {
+                    visited += entities.len();
+                    found.extend(entities.iter().copied());
+                }
+            }
+            if found.len() >= k {
+                found.sort_by(compare);
+                found.truncate(k);
+                let bound = ring as f32 * self.cell_size;
+                if found[k - 1].1.distance_squared(center) <= bound * bound {
+                    break;
+                }
+            }
+        }
+        found.sort_by(compare);
+        found.truncate(k);
+        found
+    }
+}
+
+/// Cells at a Chebyshev distance of exactly `ring` from `origin`.
+fn ring_cells(origin: IVec2, ring: i32) -> impl Iterator<Item = IVec2> {
+    (-ring..=ring).flat_map(move |x| {
+        let step = if x.abs() == ring {
+            1
+        } else {
+            (2 * ring).max(1) as usize
+        };
+        (-ring..=ring)
+            .step_by(step)
+            .map(move |y| origin + IVec2::new(x, y))
+    })
+}
+
+/// Indexes the entities whose `T` component or transform changed.
+#[allow(clippy::type_complexity)]
+pub fn spatial_index_system<T: Component>(
+    mut spatial_index: ResMut<SpatialIndex<T>>,
+    query: Query<(Entity, &Transform), (With<T>, Or<(Changed<Transform>, Added<T>)>)>,
+) {
+    for (entity, transform) in query.iter() {
+        spatial_index.insert(entity, transform.translation.truncate());
+    }
+}
+
+/// Forgets the entities that lost their `T` component or were despawned.
+///
+/// Runs every frame, removals are only kept for two frames and could be missed on the fixed
+/// timestep.
+pub fn spatial_removal_system<T: Component>(
+    mut spatial_index: ResMut<SpatialIndex<T>>,
+    mut removed: RemovedComponents<T>,
+) {
+    for entity in removed.iter() {
+        spatial_index.remove(entity);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spatial::plugin::SpatialIndexPlugin;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+    use std::time::Duration;
+
+    #[derive(Component)]
+    struct Marker;
+
+    fn brute_force(entities: &[(Entity, Vec2)], filter: impl Fn(Vec2) -> bool) -> Vec<Entity> {
+        entities
+            .iter()
+            .filter(|(_, position)| filter(*position))
+            .map(|(entity, _)| *entity)
+            .collect()
+    }
+
+    fn expected_nearest(
+        entities: &[(Entity, Vec2)],
+        center: Vec2,
+        k: usize,
+    ) -> Vec<(Entity, Vec2)> {
+        let mut expected = entities.to_vec();
+        expected.sort_by(|a, b| {
+            a.1.distance_squared(center)
+                .total_cmp(&b.1.distance_squared(center))
+                .then_with(|| a.0.cmp(&b.0))
+        });
+        expected.truncate(k);
+        expected
+    }
+
+    fn sorted(entities: impl Iterator<Item = (Entity, Vec2)>) -> Vec<Entity> {
+        let mut entities: Vec<Entity> = entities.map(|(entity, _)| entity).collect();
+        entities.sort();
+        entities
+    }
+
+    #[test]
+    fn queries() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut index = SpatialIndex::<Marker>::with_cell_size(4.0);
+        let entities: Vec<(Entity, Vec2)> = (0..500)
+            .map(|i| {
+                let position = Vec2::new(rng.gen_range(-50.0..50.0), rng.gen_range(-50.0..50.0));
+                (Entity::from_raw(i), position)
+            })
+            .collect();
+        for (entity, position) in entities.iter() {
+            index.insert(*entity, *position);
+        }
+        assert_eq!(index.len(), 500);
+
+        for _ in 0..20 {
+            let center = Vec2::new(rng.gen_range(-60.0..60.0), rng.gen_range(-60.0..60.0));
+            let radius = rng.gen_range(0.0..20.0);
+            assert_eq!(
+                sorted(index.query_radius(center, radius)),
+                brute_force(&entities, |position| position.distance(center) <= radius)
+            );
+
+            let max = center + Vec2::new(rng.gen_range(0.0..30.0), rng.gen_range(0.0..30.0));
+            assert_eq!(
+                sorted(index.query_aabb(center, max)),
+                brute_force(&entities, |position| {
+                    position.cmpge(center).all() && position.cmple(max).all()
+                })
+            );
+
+            assert_eq!(
+                index.nearest(center, 10),
+                expected_nearest(&entities, center, 10)
+            );
+        }
+        assert_eq!(index.nearest(Vec2::ZERO, 1000).len(), 500);
+
+        // Moving within a cell, to another cell, and removing.
+        let (entity, _) = entities[0];
+        index.insert(entity, Vec2::new(0.5, 0.5));
+        index.insert(entity, Vec2::new(1.0, 1.0));
+        assert_eq!(index.nearest(Vec2::new(1.0, 1.0), 1)[0].0, entity);
+        index.insert(entity, Vec2::new(100.0, 100.0));
+        assert_eq!(
+            sorted(index.query_radius(Vec2::splat(100.0), 0.1)),
+            vec![entity]
+        );
+        assert_eq!(index.remove(entity), Some(Vec2::splat(100.0)));
+        assert_eq!(index.query_radius(Vec2::splat(100.0), 0.1).count(), 0);
+        assert_eq!(index.len(), 499);
+
+        // Far from every entity, without searching every cell in between.
+        let far = Vec2::splat(1.0e6);
+        assert_eq!(
+            index.nearest(far, 2),
+            expected_nearest(&entities[1..], far, 2)
+        );
+    }
+
+    #[test]
+    fn incremental() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_plugin(SpatialIndexPlugin::<Marker>::default())
+            .insert_resource(FixedTime::new(Duration::from_secs(3600)));
+        let a = app
+            .world
+            .spawn((Marker, Transform::from_xyz(1.0, 2.0, 0.0)))
+            .id();
+        let b = app.world.spawn(Transform::from_xyz(3.0, 4.0, 0.0)).id();
+        app.world.run_schedule(CoreSchedule::FixedUpdate);
+        let index = app.world.resource::<SpatialIndex<Marker>>();
+        assert_eq!(index.get(a), Some(Vec2::new(1.0, 2.0)));
+        assert_eq!(index.get(b), None);
+
+        app.world.get_mut::<Transform>(a).unwrap().translation.x = 40.0;
+        app.world.entity_mut(b).insert(Marker);
+        app.world.run_schedule(CoreSchedule::FixedUpdate);
+        let index = app.world.resource::<SpatialIndex<Marker>>();
+        assert_eq!(index.get(a), Some(Vec2::new(40.0, 2.0)));
+        assert_eq!(index.get(b), Some(Vec2::new(3.0, 4.0)));
+
+        // Removals are kept for two frames, several frames can run between fixed steps.
+        app.world.despawn(a);
+        app.world.entity_mut(b).remove::<Marker>();
+        for _ in 0..4 {
+            app.update();
+        }
+        app.world.run_schedule(CoreSchedule::FixedUpdate);
+        assert!(app.world.resource::<SpatialIndex<Marker>>().is_empty());
+    }
+}
@@ -0,0 +1,29 @@
+use crate::spatial::{spatial_index_system, spatial_removal_system, SpatialIndex, SpatialSet};
+use bevy::prelude::*;
+use std::marker::PhantomData;
+
+/// Maintains a [`SpatialIndex`] of the entities with a `T` component on the fixed timestep,
+/// removals are applied every frame.
+pub struct SpatialIndexPlugin<T: Component> {
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T: Component> Default for SpatialIndexPlugin<T> {
+    fn default() -> Self {
+        SpatialIndexPlugin {
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T: Component> Plugin for SpatialIndexPlugin<T> {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SpatialIndex<T>>()
+            .add_system(
+                spatial_index_system::<T>
+                    .in_set(SpatialSet::Index)
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            )
+            .add_system(spatial_removal_system::<T>.in_base_set(CoreSet::PostUpdate));
+    }
+}
@@ -0,0 +1,299 @@
+use crate::actor::HealthComponent;
+use crate::combat::{DamageEvent, DamageType};
+use crate::prototype::commands::SpawnPrototypeExt;
+use crate::prototype::PrototypeRegistry;
+use crate::spatial::SpatialIndex;
+use crate::targeting::projectile::ProjectileComponent;
+use crate::wave::EnemyComponent;
+use bevy::prelude::*;
+use serde::Deserialize;
+use std::cmp::Ordering;
+use std::time::Duration;
+
+pub mod plugin;
+pub mod projectile;
+
+/// Which enemy in range a tower shoots at.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum TargetingPolicy {
+    /// The furthest along its path.
+    #[default]
+    First,
+    /// The least far along its path.
+    Last,
+    /// The one with the most health left.
+    Strongest,
+    Closest,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct WeaponDefinition {
+    /// Tiles.
+    pub range: f32,
+    /// Seconds between two shots.
+    pub cooldown: f32,
+    pub damage: f32,
+    #[serde(default)]
+    pub damage_type: DamageType,
+    #[serde(default)]
+    pub targeting: TargetingPolicy,
+    /// Prototype of the projectiles, targets are hit instantly when `None`.
+    #[serde(default)]
+    pub projectile: Option<String>,
+}
+
+#[derive(Component, Debug, Clone)]
+pub struct WeaponComponent {
+    pub definition: WeaponDefinition,
+    /// Enemy chosen during the last step.
+    pub target: Option<Entity>,
+    cooldown: Duration,
+}
+
+/// Distance travelled by an enemy along its path, used by the first and last policies.
+#[derive(Component, Debug, Clone, Copy, Default, PartialEq)]
+pub struct PathProgressComponent(pub f32);
+
+/// An enemy a tower could shoot at.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TargetCandidate {
+    pub entity: Entity,
+    pub position: Vec2,
+    pub progress: f32,
+    pub health: f32,
+}
+
+impl WeaponComponent {
+    pub fn new(definition: WeaponDefinition) -> Self {
+        WeaponComponent {
+            definition,
+            target: None,
+            cooldown: Duration::ZERO,
+        }
+    }
+
+    /// Time left before the next shot.
+    pub fn cooldown(&self) -> Duration {
+        self.cooldown
+    }
+}
+
+/// Best candidate for the policy, equal candidates resolve to the smallest entity.
+pub fn select_target(
+    policy: TargetingPolicy,
+    origin: Vec2,
+    candidates: impl IntoIterator<Item = TargetCandidate>,
+) -> Option<TargetCandidate> {
+    // Ordered so that the best candidate is the smallest.
+    let compare = |a: &TargetCandidate, b: &TargetCandidate| -> Ordering {
+        match policy {
+            TargetingPolicy::First => b.progress.total_cmp(&a.progress),
+            TargetingPolicy::Last => a.progress.total_cmp(&b.progress),
+            TargetingPolicy::Strongest => b.health.total_cmp(&a.health),
+            TargetingPolicy::Closest => a
+                .position
+                .distance_squared(origin)
+                .total_cmp(&b.position.distance_squared(origin)),
+        }
+        .then_with(|| a.entity.cmp(&b.entity))
+    };
+    candidates.into_iter().min_by(compare)
+}
+
+/// Picks the target of every weapon and fires the ready ones.
+#[allow(clippy::too_many_arguments)]
+pub fn targeting_system(
+    mut commands: Commands,
+    fixed_time: Res<FixedTime>,
+    spatial_index: Res<SpatialIndex<EnemyComponent>>,
+    registry: Res<PrototypeRegistry>,
+    mut weapon_query: Query<(Entity, &Transform, &mut WeaponComponent)>,
+    target_query: Query<(&HealthComponent, Option<&PathProgressComponent>), With<EnemyComponent>>,
+    mut damage_events: EventWriter<DamageEvent>,
+) {
+    for (entity, transform, mut weapon) in weapon_query.iter_mut() {
+        weapon.cooldown = weapon.cooldown.saturating_sub(fixed_time.period);
+        let origin = transform.translation.truncate();
+        let candidates = spatial_index
+            .query_radius(origin, weapon.definition.range)
+            .filter_map(|(target, position)| {
+                let (health, progress) = target_query.get(target).ok()?;
+                (health.current > 0.0).then_some(TargetCandidate {
+                    entity: target,
+                    position,
+                    progress: progress.map_or(0.0, |progress| progress.0),
+                    health: health.current,
+                })
+            });
+        let target = select_target(weapon.definition.targeting, origin, candidates);
+        weapon.target = target.map(|target| target.entity);
+
+        let target = match target {
+            Some(target) if weapon.cooldown.is_zero() => target,
+            _ => continue,
+        };
+        let definition = &weapon.definition;
+        match &definition.projectile {
+            Some(id) => {
+                let projectile = match registry
+                    .get(id)
+                    .and_then(|prototype| prototype.definition.projectile.clone())
+                {
+                    Some(projectile) => projectile,
+                    None => {
+                        error!("`{}` isn't a projectile prototype", id);
+                        continue;
+                    }
+                };
+                commands
+                    .spawn_prototype(id, *transform)
+                    .insert(ProjectileComponent::new(
+                        projectile,
+                        Some(entity),
+                        Some(target.entity),
+                        definition.damage,
+                        definition.damage_type,
+                        origin,
+                        target.position,
+                    ));
+            }
+            None => damage_events.send(DamageEvent {
+                source: Some(entity),
+                target: target.entity,
+                amount: definition.damage,
+                damage_type: definition.damage_type,
+            }),
+        }
+        weapon.cooldown = Duration::from_secs_f32(definition.cooldown);
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::combat::plugin::CombatPlugin;
+    use crate::combat::Died;
+    use crate::prototype::commands::InsertPrototypeExt;
+    use crate::prototype::PrototypeSet;
+    use crate::targeting::plugin::TargetingPlugin;
+    use bevy::ecs::event::ManualEventReader;
+    use bevy::ecs::system::CommandQueue;
+
+    pub const PROTOTYPES: &str = r#"
+gun_tower:
+  kind: tower
+  weapon: {range: 3, cooldown: 0.5, damage: 10}
+mortar_tower:
+  kind: tower
+  weapon: {range: 10, cooldown: 10, damage: 50, damage_type: fire, projectile: shell}
+homing_tower:
+  kind: tower
+  weapon: {range: 10, cooldown: 10, damage: 50, targeting: closest, projectile: missile}
+shell:
+  kind: projectile
+  projectile: {motion: ballistic, speed: 10, arc_height: 2, splash_radius: 1.5}
+missile:
+  kind: projectile
+  light: {scale: 2}
+  projectile: {motion: homing, speed: 5}
+dummy:
+  kind: enemy
+  health: 25
+"#;
+
+    pub fn make_app() -> App {
+        let mut app = App::new();
+        let set = PrototypeSet::parse(PROTOTYPES).unwrap();
+        app.add_plugins(MinimalPlugins)
+            .add_plugin(CombatPlugin)
+            .add_plugin(TargetingPlugin)
+            .insert_resource(FixedTime::new(Duration::from_millis(100)))
+            .insert_resource(PrototypeRegistry::from_sets([&set]).unwrap());
+        app
+    }
+
+    pub fn spawn(app: &mut App, id: &str, position: Vec2) -> Entity {
+        let mut queue = CommandQueue::default();
+        let mut commands = Commands::new(&mut queue, &app.world);
+        let transform = Transform::from_translation(position.extend(0.0));
+        let mut entity_commands = commands.spawn(SpatialBundle {
+            transform,
+            global_transform: GlobalTransform::from(transform),
+            ..Default::default()
+        });
+        entity_commands.insert_prototype(id);
+        if id == "dummy" {
+            entity_commands.insert(EnemyComponent {
+                enemy: id.to_string(),
+                modifiers: Default::default(),
+                spawner: Entity::PLACEHOLDER,
+                wave: 0,
+            });
+        }
+        let entity = entity_commands.id();
+        queue.apply(&mut app.world);
+        entity
+    }
+
+    pub fn died(app: &App, reader: &mut ManualEventReader<Died>) -> Vec<Entity> {
+        reader
+            .iter(app.world.resource::<Events<Died>>())
+            .map(|died| died.entity)
+            .collect()
+    }
+
+    #[test]
+    fn policies() {
+        let candidate = |index, x, progress, health| TargetCandidate {
+            entity: Entity::from_raw(index),
+            position: Vec2::new(x, 0.0),
+            progress,
+            health,
+        };
+        let candidates = [
+            candidate(0, 3.0, 5.0, 10.0),
+            candidate(1, -1.0, 8.0, 10.0),
+            candidate(2, 2.0, 1.0, 30.0),
+            candidate(3, 2.0, 1.0, 30.0),
+        ];
+        let select = |policy| {
+            select_target(policy, Vec2::ZERO, candidates).map(|target| target.entity.index())
+        };
+        assert_eq!(select(TargetingPolicy::First), Some(1));
+        assert_eq!(select(TargetingPolicy::Last), Some(2));
+        assert_eq!(select(TargetingPolicy::Strongest), Some(2));
+        assert_eq!(select(TargetingPolicy::Closest), Some(1));
+        assert_eq!(select_target(TargetingPolicy::First, Vec2::ZERO, []), None);
+    }
+
+    #[test]
+    fn hitscan() {
+        let mut app = make_app();
+        let tower = spawn(&mut app, "gun_tower", Vec2::ZERO);
+        let far = spawn(&mut app, "dummy", Vec2::new(3.5, 0.0));
+        let near = spawn(&mut app, "dummy", Vec2::new(0.0, -2.0));
+
+        let mut died_reader = ManualEventReader::<Died>::default();
+        // A shot every 0.5s, the third one kills.
+        for _ in 0..10 {
+            app.world.run_schedule(CoreSchedule::FixedUpdate);
+        }
+        assert_eq!(app.world.get::<HealthComponent>(near).unwrap().current, 5.0);
+        assert!(died(&app, &mut died_reader).is_empty());
+        app.world.run_schedule(CoreSchedule::FixedUpdate);
+        assert_eq!(died(&app, &mut died_reader), vec![near]);
+        assert_eq!(app.world.get::<HealthComponent>(far).unwrap().current, 25.0);
+        assert_eq!(
+            app.world.get::<WeaponComponent>(tower).unwrap().target,
+            Some(near)
+        );
+        // Out of range.
+        app.world.run_schedule(CoreSchedule::FixedUpdate);
+        assert_eq!(
+            app.world.get::<WeaponComponent>(tower).unwrap().target,
+            None
+        );
+    }
+}
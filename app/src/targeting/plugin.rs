@@ -0,0 +1,22 @@
+use crate::combat::CombatSet;
+use crate::spatial::plugin::SpatialIndexPlugin;
+use crate::spatial::SpatialSet;
+use crate::targeting::projectile::projectile_system;
+use crate::targeting::targeting_system;
+use crate::wave::EnemyComponent;
+use bevy::prelude::*;
+
+pub struct TargetingPlugin;
+
+impl Plugin for TargetingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugin(SpatialIndexPlugin::<EnemyComponent>::default())
+            .add_systems(
+                (targeting_system, projectile_system)
+                    .chain()
+                    .after(SpatialSet::Index)
+                    .before(CombatSet::Damage)
+                    .in_schedule(CoreSchedule::FixedUpdate),
+            );
+    }
+}
@@ -0,0 +1,237 @@
+use crate::combat::{DamageEvent, DamageType};
+use crate::spatial::SpatialIndex;
+use crate::wave::EnemyComponent;
+use bevy::prelude::*;
+use serde::Deserialize;
+
+/// Distance from the impact within which ballistic projectiles without splash hit an enemy.
+pub const IMPACT_RADIUS: f32 = 0.5;
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ProjectileMotion {
+    /// Flies to where the target was when fired, along an arc.
+    Ballistic,
+    /// Follows the target, and flies on to its last position when the target is gone.
+    Homing,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ProjectileDefinition {
+    pub motion: ProjectileMotion,
+    /// Tiles per second.
+    pub speed: f32,
+    /// Height of the top of ballistic arcs, in tiles.
+    #[serde(default)]
+    pub arc_height: f32,
+    /// Damages every enemy in this radius on impact instead of a single one.
+    #[serde(default)]
+    pub splash_radius: f32,
+}
+
+#[derive(Component, Debug, Clone)]
+pub struct ProjectileComponent {
+    pub definition: ProjectileDefinition,
+    pub source: Option<Entity>,
+    pub target: Option<Entity>,
+    pub damage: f32,
+    pub damage_type: DamageType,
+    origin: Vec2,
+    destination: Vec2,
+    /// Position on the ground, the transform adds the arc height.
+    position: Vec2,
+}
+
+impl ProjectileComponent {
+    pub fn new(
+        definition: ProjectileDefinition,
+        source: Option<Entity>,
+        target: Option<Entity>,
+        damage: f32,
+        damage_type: DamageType,
+        origin: Vec2,
+        destination: Vec2,
+    ) -> Self {
+        ProjectileComponent {
+            definition,
+            source,
+            target,
+            damage,
+            damage_type,
+            origin,
+            destination,
+            position: origin,
+        }
+    }
+
+    pub fn position(&self) -> Vec2 {
+        self.position
+    }
+
+    pub fn destination(&self) -> Vec2 {
+        self.destination
+    }
+
+    /// Height above the ground, a parabola peaking halfway for ballistic projectiles.
+    pub fn height(&self) -> f32 {
+        if self.definition.motion != ProjectileMotion::Ballistic {
+            return 0.0;
+        }
+        let distance = self.origin.distance(self.destination);
+        if distance == 0.0 {
+            return 0.0;
+        }
+        let t = (self.origin.distance(self.position) / distance).clamp(0.0, 1.0);
+        4.0 * self.definition.arc_height * t * (1.0 - t)
+    }
+}
+
+/// Moves the projectiles and damages what they hit on impact.
+pub fn projectile_system(
+    mut commands: Commands,
+    fixed_time: Res<FixedTime>,
+    spatial_index: Res<SpatialIndex<EnemyComponent>>,
+    mut projectile_query: Query<(Entity, &mut Transform, &mut ProjectileComponent)>,
+    target_query: Query<&Transform, (With<EnemyComponent>, Without<ProjectileComponent>)>,
+    mut damage_events: EventWriter<DamageEvent>,
+) {
+    let delta = fixed_time.period.as_secs_f32();
+    for (entity, mut transform, mut projectile) in projectile_query.iter_mut() {
+        if projectile.definition.motion == ProjectileMotion::Homing {
+            match projectile
+                .target
+                .and_then(|target| target_query.get(target).ok())
+            {
+                Some(target_transform) => {
+                    projectile.destination = target_transform.translation.truncate();
+                }
+                None => projectile.target = None,
+            }
+        }
+
+        let step = projectile.definition.speed * delta;
+        let offset = projectile.destination - projectile.position;
+        if offset.length() > step {
+            projectile.position += offset.normalize() * step;
+            let position = projectile.position + Vec2::Y * projectile.height();
+            transform.translation = position.extend(transform.translation.z);
+            continue;
+        }
+
+        let impact = projectile.destination;
+        let mut targets: Vec<Entity> = if projectile.definition.splash_radius > 0.0 {
+            spatial_index
+                .query_radius(impact, projectile.definition.splash_radius)
+                .map(|(target, _)| target)
+                .collect()
+        } else {
+            match projectile.definition.motion {
+                ProjectileMotion::Homing => projectile.target.into_iter().collect(),
+                ProjectileMotion::Ballistic => spatial_index
+                    .query_radius(impact, IMPACT_RADIUS)
+                    .min_by(|(a, a_position), (b, b_position)| {
+                        a_position
+                            .distance_squared(impact)
+                            .total_cmp(&b_position.distance_squared(impact))
+                            .then_with(|| a.cmp(b))
+                    })
+                    .map(|(target, _)| target)
+                    .into_iter()
+                    .collect(),
+            }
+        };
+        targets.sort();
+        for target in targets {
+            damage_events.send(DamageEvent {
+                source: projectile.source,
+                target,
+                amount: projectile.damage,
+                damage_type: projectile.damage_type,
+            });
+        }
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actor::HealthComponent;
+    use crate::combat::Died;
+    use crate::lighting::LightComponent;
+    use crate::targeting::tests::{died, make_app, spawn};
+    use bevy::ecs::event::ManualEventReader;
+
+    fn projectiles(app: &mut App) -> Vec<(Entity, Vec3)> {
+        app.world
+            .query_filtered::<(Entity, &Transform), With<ProjectileComponent>>()
+            .iter(&app.world)
+            .map(|(entity, transform)| (entity, transform.translation))
+            .collect()
+    }
+
+    #[test]
+    fn ballistic_splash() {
+        let mut app = make_app();
+        spawn(&mut app, "mortar_tower", Vec2::ZERO);
+        let targets = [
+            spawn(&mut app, "dummy", Vec2::new(5.0, 0.0)),
+            spawn(&mut app, "dummy", Vec2::new(6.0, 0.0)),
+            spawn(&mut app, "dummy", Vec2::new(5.0, 1.4)),
+        ];
+        let outside = spawn(&mut app, "dummy", Vec2::new(7.0, 0.0));
+
+        let mut died_reader = ManualEventReader::<Died>::default();
+        app.world.run_schedule(CoreSchedule::FixedUpdate);
+        // 10 tiles per second to the first enemy 5 tiles away.
+        for step in 1..5 {
+            app.world.run_schedule(CoreSchedule::FixedUpdate);
+            let projectiles = projectiles(&mut app);
+            assert_eq!(projectiles.len(), 1);
+            let position = projectiles[0].1;
+            assert_eq!(position.x, step as f32);
+            let t = step as f32 / 5.0;
+            assert!((position.y - 8.0 * t * (1.0 - t)).abs() < 1e-5);
+        }
+        app.world.run_schedule(CoreSchedule::FixedUpdate);
+        assert!(projectiles(&mut app).is_empty());
+        assert_eq!(died(&app, &mut died_reader), targets.to_vec());
+        assert_eq!(
+            app.world.get::<HealthComponent>(outside).unwrap().current,
+            25.0
+        );
+    }
+
+    #[test]
+    fn homing() {
+        let mut app = make_app();
+        spawn(&mut app, "homing_tower", Vec2::ZERO);
+        let target = spawn(&mut app, "dummy", Vec2::new(0.0, 4.0));
+        let other = spawn(&mut app, "dummy", Vec2::new(0.0, 8.0));
+
+        let mut died_reader = ManualEventReader::<Died>::default();
+        app.world.run_schedule(CoreSchedule::FixedUpdate);
+        app.world.run_schedule(CoreSchedule::FixedUpdate);
+        let (projectile, position) = projectiles(&mut app)[0];
+        assert_eq!(position, Vec3::new(0.0, 0.5, 0.0));
+        assert_eq!(
+            app.world.get::<LightComponent>(projectile).unwrap().scale,
+            2.0
+        );
+
+        // The target moves away to the side, the projectile follows it.
+        app.world.get_mut::<Transform>(target).unwrap().translation = Vec3::new(3.0, 0.5, 0.0);
+        app.world.run_schedule(CoreSchedule::FixedUpdate);
+        assert_eq!(projectiles(&mut app)[0].1, Vec3::new(0.5, 0.5, 0.0));
+        for _ in 0..5 {
+            app.world.run_schedule(CoreSchedule::FixedUpdate);
+        }
+        assert!(projectiles(&mut app).is_empty());
+        assert_eq!(died(&app, &mut died_reader), vec![target]);
+        assert_eq!(
+            app.world.get::<HealthComponent>(other).unwrap().current,
+            25.0
+        );
+    }
+}
@@ -0,0 +1,200 @@
+use crate::tilemap::bundle::TilemapComponent;
+use crate::tilemap::material::TilemapMaterial;
+use bevy::prelude::*;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+use bevy::utils::{Duration, HashMap};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TileAnimationFrame {
+    pub atlas_index: usize,
+    pub duration: Duration,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TileAnimation {
+    pub frames: Vec<TileAnimationFrame>,
+}
+
+/// Animations keyed by the atlas index of the tiles they animate.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TileAnimations(pub HashMap<usize, TileAnimation>);
+
+impl TileAnimation {
+    pub fn new(frames: impl IntoIterator<Item = TileAnimationFrame>) -> Self {
+        TileAnimation {
+            frames: frames.into_iter().collect(),
+        }
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.frames.iter().map(|frame| frame.duration).sum()
+    }
+
+    /// Frame shown after `elapsed` time, the animation loops forever.
+    pub fn frame_at(&self, elapsed: Duration) -> Option<&TileAnimationFrame> {
+        let duration = self.duration().as_nanos();
+        if duration == 0 {
+            return self.frames.first();
+        }
+        let mut time = elapsed.as_nanos() % duration;
+        for frame in self.frames.iter() {
+            if time < frame.duration.as_nanos() {
+                return Some(frame);
+            }
+            time -= frame.duration.as_nanos();
+        }
+        self.frames.last()
+    }
+}
+
+impl TileAnimations {
+    pub fn insert(&mut self, atlas_index: usize, animation: TileAnimation) {
+        self.0.insert(atlas_index, animation);
+    }
+
+    pub fn get(&self, atlas_index: usize) -> Option<&TileAnimation> {
+        self.0.get(&atlas_index)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+const FRAME_RECT_SIZE: usize = std::mem::size_of::<[f32; 4]>();
+
+/// Makes the frame lookup texture of an atlas, one texel holding the UV rect of each atlas index.
+///
+/// Tiles are meshed with their own atlas index, animating them only rewrites texels of this
+/// texture instead of remeshing chunks.
+pub fn make_frame_texture(texture_atlas: &TextureAtlas, texture_size: Vec2) -> Image {
+    let rects: Vec<[f32; 4]> = (0..texture_atlas.len())
+        .map(|atlas_index| get_frame_rect(texture_atlas, atlas_index, texture_size))
+        .collect();
+    Image::new(
+        Extent3d {
+            width: rects.len().max(1) as u32,
+            height: 1,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        if rects.is_empty() {
+            vec![0u8; FRAME_RECT_SIZE]
+        } else {
+            bytemuck::cast_slice(&rects).to_vec()
+        },
+        TextureFormat::Rgba32Float,
+    )
+}
+
+fn get_frame_rect(
+    texture_atlas: &TextureAtlas,
+    atlas_index: usize,
+    texture_size: Vec2,
+) -> [f32; 4] {
+    let rect = texture_atlas.textures[atlas_index];
+    let uv_min = rect.min / texture_size;
+    let uv_max = rect.max / texture_size;
+    [uv_min.x, uv_min.y, uv_max.x, uv_max.y]
+}
+
+/// Creates the frame texture of every tilemap layer and advances its animations.
+pub fn tile_animation_system(
+    time: Res<Time>,
+    mut tilemap_query: Query<&mut TilemapComponent>,
+    mut images: ResMut<Assets<Image>>,
+    mut materials: ResMut<Assets<TilemapMaterial>>,
+    texture_atlases: Res<Assets<TextureAtlas>>,
+) {
+    for mut tilemap in tilemap_query.iter_mut() {
+        for layer in tilemap.bypass_change_detection().layers_mut() {
+            let texture_atlas = match texture_atlases.get(&layer.texture_atlas) {
+                Some(texture_atlas) => texture_atlas,
+                None => continue,
+            };
+            let texture_size = match images.get(&texture_atlas.texture) {
+                Some(texture) => texture.size(),
+                None => continue,
+            };
+
+            let frame_texture = layer
+                .frame_texture
+                .get_or_insert_with(|| images.add(make_frame_texture(texture_atlas, texture_size)))
+                .clone();
+            let material_outdated = materials
+                .get(&layer.material)
+                .map_or(false, |material| material.frame_texture != frame_texture);
+            if material_outdated {
+                if let Some(material) = materials.get_mut(&layer.material) {
+                    material.frame_texture = frame_texture.clone();
+                }
+            }
+
+            if layer.animations.is_empty() {
+                continue;
+            }
+            let data = match images.get(&frame_texture) {
+                Some(image) => &image.data,
+                None => continue,
+            };
+            let mut changed_rects = Vec::new();
+            for (atlas_index, animation) in layer.animations.0.iter() {
+                let frame = match animation.frame_at(time.elapsed()) {
+                    Some(frame) => frame,
+                    None => continue,
+                };
+                if *atlas_index >= texture_atlas.len() || frame.atlas_index >= texture_atlas.len() {
+                    continue;
+                }
+                let rect = get_frame_rect(texture_atlas, frame.atlas_index, texture_size);
+                let offset = atlas_index * FRAME_RECT_SIZE;
+                if data[offset..offset + FRAME_RECT_SIZE] != *bytemuck::cast_slice::<f32, u8>(&rect)
+                {
+                    changed_rects.push((offset, rect));
+                }
+            }
+
+            if changed_rects.is_empty() {
+                continue;
+            }
+            if let Some(image) = images.get_mut(&frame_texture) {
+                for (offset, rect) in changed_rects {
+                    image.data[offset..offset + FRAME_RECT_SIZE]
+                        .copy_from_slice(bytemuck::cast_slice(&rect));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames() {
+        let animation = TileAnimation::new([
+            TileAnimationFrame {
+                atlas_index: 3,
+                duration: Duration::from_millis(100),
+            },
+            TileAnimationFrame {
+                atlas_index: 4,
+                duration: Duration::from_millis(300),
+            },
+        ]);
+        let atlas_index_at = |millis| {
+            animation
+                .frame_at(Duration::from_millis(millis))
+                .unwrap()
+                .atlas_index
+        };
+        assert_eq!(atlas_index_at(0), 3);
+        assert_eq!(atlas_index_at(99), 3);
+        assert_eq!(atlas_index_at(100), 4);
+        assert_eq!(atlas_index_at(399), 4);
+        assert_eq!(atlas_index_at(400), 3);
+        assert_eq!(atlas_index_at(1300), 4);
+        assert!(TileAnimation::default().frame_at(Duration::ZERO).is_none());
+    }
+}
@@ -0,0 +1,190 @@
+use crate::tilemap::autotile::{blob, AutotileError, AutotileKind, AutotileSet, TerrainType};
+use bevy::utils::HashMap;
+use serde::Deserialize;
+
+/// Contents of an `*.autotile.yaml` file.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AutotileSetDefinition {
+    Blob {
+        terrains: Vec<BlobTerrainDefinition>,
+    },
+    WangCorner {
+        terrains: Vec<String>,
+        tiles: Vec<WangTileDefinition>,
+    },
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct BlobTerrainDefinition {
+    pub name: String,
+    /// Atlas index of the first of 47 tiles laid out in [`blob::masks`] order.
+    #[serde(default)]
+    pub first_index: Option<usize>,
+    /// Atlas indices of single masks, overriding the ones from `first_index`.
+    #[serde(default)]
+    pub tiles: HashMap<u8, usize>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct WangTileDefinition {
+    /// Terrain names of the `[NW, NE, SW, SE]` corners.
+    pub corners: [String; 4],
+    pub index: usize,
+}
+
+impl AutotileSetDefinition {
+    pub fn parse(source: &str) -> Result<Self, AutotileError> {
+        Ok(serde_yaml::from_str(source)?)
+    }
+
+    pub fn build(&self) -> Result<AutotileSet, AutotileError> {
+        match self {
+            AutotileSetDefinition::Blob { terrains } => {
+                check_terrain_count(terrains.len())?;
+                let mut sets = Vec::with_capacity(terrains.len());
+                for terrain in terrains {
+                    let mut tiles = HashMap::default();
+                    if let Some(first_index) = terrain.first_index {
+                        for (i, mask) in blob::masks().enumerate() {
+                            tiles.insert(mask, first_index + i);
+                        }
+                    }
+                    for (mask, index) in terrain.tiles.iter() {
+                        if blob::reduce(*mask) != *mask {
+                            return Err(AutotileError::InvalidMask(*mask));
+                        }
+                        tiles.insert(*mask, *index);
+                    }
+                    sets.push(tiles);
+                }
+                Ok(AutotileSet::new(
+                    terrains
+                        .iter()
+                        .map(|terrain| terrain.name.clone())
+                        .collect(),
+                    AutotileKind::Blob(sets),
+                ))
+            }
+            AutotileSetDefinition::WangCorner { terrains, tiles } => {
+                check_terrain_count(terrains.len())?;
+                let get_terrain = |name: &String| {
+                    terrains
+                        .iter()
+                        .position(|terrain| terrain == name)
+                        .map(|i| TerrainType(i as u8))
+                        .ok_or_else(|| AutotileError::UnknownTerrain(name.clone()))
+                };
+                let mut corner_tiles = HashMap::default();
+                for tile in tiles {
+                    let corners = [
+                        get_terrain(&tile.corners[0])?,
+                        get_terrain(&tile.corners[1])?,
+                        get_terrain(&tile.corners[2])?,
+                        get_terrain(&tile.corners[3])?,
+                    ];
+                    corner_tiles.insert(corners, tile.index);
+                }
+                Ok(AutotileSet::new(
+                    terrains.clone(),
+                    AutotileKind::WangCorner(corner_tiles),
+                ))
+            }
+        }
+    }
+}
+
+fn check_terrain_count(count: usize) -> Result<(), AutotileError> {
+    if count > u8::MAX as usize + 1 {
+        return Err(AutotileError::TooManyTerrains);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blob() {
+        let set = AutotileSetDefinition::parse(
+            r#"
+kind: blob
+terrains:
+  - name: dirt
+    first_index: 0
+  - name: grass
+    first_index: 47
+    tiles:
+      255: 100
+"#,
+        )
+        .unwrap()
+        .build()
+        .unwrap();
+        assert_eq!(set.get_terrain("grass"), Some(TerrainType(1)));
+        match set.kind() {
+            AutotileKind::Blob(sets) => {
+                assert_eq!(sets.len(), 2);
+                assert_eq!(sets[0].len(), 47);
+                assert_eq!(sets[0][&0], 0);
+                assert_eq!(sets[0][&255], 46);
+                assert_eq!(sets[1][&0], 47);
+                assert_eq!(sets[1][&255], 100);
+            }
+            kind => panic!("unexpected kind {:?}", kind),
+        }
+
+        let error = AutotileSetDefinition::parse(
+            r#"
+kind: blob
+terrains:
+  - name: dirt
+    tiles:
+      2: 0
+"#,
+        )
+        .unwrap()
+        .build();
+        assert!(matches!(error, Err(AutotileError::InvalidMask(2))));
+    }
+
+    #[test]
+    fn wang_corner() {
+        let set = AutotileSetDefinition::parse(
+            r#"
+kind: wang_corner
+terrains: [water, sand]
+tiles:
+  - corners: [water, water, water, water]
+    index: 0
+  - corners: [sand, sand, water, water]
+    index: 3
+"#,
+        )
+        .unwrap()
+        .build()
+        .unwrap();
+        let (water, sand) = (TerrainType(0), TerrainType(1));
+        match set.kind() {
+            AutotileKind::WangCorner(tiles) => {
+                assert_eq!(tiles.len(), 2);
+                assert_eq!(tiles[&[sand, sand, water, water]], 3);
+            }
+            kind => panic!("unexpected kind {:?}", kind),
+        }
+
+        let error = AutotileSetDefinition::parse(
+            r#"
+kind: wang_corner
+terrains: [water]
+tiles:
+  - corners: [water, water, water, lava]
+    index: 0
+"#,
+        )
+        .unwrap()
+        .build();
+        assert!(matches!(error, Err(AutotileError::UnknownTerrain(name)) if name == "lava"));
+    }
+}
@@ -0,0 +1,23 @@
+use crate::tilemap::autotile::definition::AutotileSetDefinition;
+use bevy::asset::{AssetLoader, BoxedFuture, LoadContext, LoadedAsset};
+
+#[derive(Default)]
+pub struct AutotileSetLoader;
+
+impl AssetLoader for AutotileSetLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let set = AutotileSetDefinition::parse(std::str::from_utf8(bytes)?)?.build()?;
+            load_context.set_default_asset(LoadedAsset::new(set));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["autotile.yaml"]
+    }
+}
@@ -0,0 +1,341 @@
+use crate::tilemap::data::{TileData, TilemapData};
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use bevy::utils::HashMap;
+use thiserror::Error;
+
+pub mod definition;
+pub mod loader;
+
+/// Logical terrain of a cell, the index of the terrain in its [`AutotileSet`].
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TerrainType(pub u8);
+
+/// Terrain types painted per cell, resolved into tiles by an [`AutotileSet`].
+#[derive(Debug, Clone, Default)]
+pub struct TerrainMap {
+    cells: HashMap<IVec2, TerrainType>,
+}
+
+#[derive(TypeUuid, Debug, Clone)]
+#[uuid = "a3f1c7e2-64b8-4d3a-9e55-1c2b7d9f0e48"]
+pub struct AutotileSet {
+    terrain_names: Vec<String>,
+    kind: AutotileKind,
+}
+
+#[derive(Debug, Clone)]
+pub enum AutotileKind {
+    /// 47-tile blob sets, one per terrain, keyed by the reduced neighbour mask (see [`blob`]).
+    Blob(Vec<HashMap<u8, usize>>),
+    /// Wang corner sets keyed by the terrains of the `[NW, NE, SW, SE]` corners.
+    ///
+    /// Each corner takes the lowest terrain of the four cells touching it, so higher terrains
+    /// are drawn with transitions into the ones below them.
+    WangCorner(HashMap<[TerrainType; 4], usize>),
+}
+
+#[derive(Error, Debug)]
+pub enum AutotileError {
+    #[error("invalid definition: {0}")]
+    Yaml(#[from] serde_yaml::Error),
+    #[error("unknown terrain `{0}`")]
+    UnknownTerrain(String),
+    #[error("too many terrains")]
+    TooManyTerrains,
+    #[error("invalid blob mask {0}")]
+    InvalidMask(u8),
+}
+
+pub mod blob {
+    use bevy::prelude::*;
+
+    pub const N: u8 = 1 << 0;
+    pub const NE: u8 = 1 << 1;
+    pub const E: u8 = 1 << 2;
+    pub const SE: u8 = 1 << 3;
+    pub const S: u8 = 1 << 4;
+    pub const SW: u8 = 1 << 5;
+    pub const W: u8 = 1 << 6;
+    pub const NW: u8 = 1 << 7;
+
+    /// Neighbour offsets in mask bit order, rows grow upwards.
+    pub const NEIGHBOURS: [IVec2; 8] = [
+        IVec2::new(0, 1),
+        IVec2::new(1, 1),
+        IVec2::new(1, 0),
+        IVec2::new(1, -1),
+        IVec2::new(0, -1),
+        IVec2::new(-1, -1),
+        IVec2::new(-1, 0),
+        IVec2::new(-1, 1),
+    ];
+
+    /// Drops corner bits that aren't enclosed by both of their edges, which leaves 47 masks.
+    pub fn reduce(mask: u8) -> u8 {
+        let mut reduced = mask & (N | E | S | W);
+        for (corner, a, b) in [(NE, N, E), (SE, S, E), (SW, S, W), (NW, N, W)] {
+            if mask & (corner | a | b) == corner | a | b {
+                reduced |= corner;
+            }
+        }
+        reduced
+    }
+
+    /// All reduced masks in ascending order, the layout of a blob set starting at `first_index`.
+    pub fn masks() -> impl Iterator<Item = u8> {
+        (0..=u8::MAX).filter(|mask| reduce(*mask) == *mask)
+    }
+}
+
+impl TerrainMap {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn get(&self, location: IVec2) -> Option<TerrainType> {
+        self.cells.get(&location).copied()
+    }
+
+    pub fn set(&mut self, location: IVec2, terrain: TerrainType) {
+        self.cells.insert(location, terrain);
+    }
+
+    pub fn remove(&mut self, location: IVec2) -> Option<TerrainType> {
+        self.cells.remove(&location)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (IVec2, TerrainType)> + '_ {
+        self.cells
+            .iter()
+            .map(|(location, terrain)| (*location, *terrain))
+    }
+}
+
+impl AutotileSet {
+    pub fn new(terrain_names: Vec<String>, kind: AutotileKind) -> Self {
+        AutotileSet {
+            terrain_names,
+            kind,
+        }
+    }
+
+    pub fn kind(&self) -> &AutotileKind {
+        &self.kind
+    }
+
+    pub fn get_terrain(&self, name: &str) -> Option<TerrainType> {
+        self.terrain_names
+            .iter()
+            .position(|terrain_name| terrain_name == name)
+            .map(|i| TerrainType(i as u8))
+    }
+
+    pub fn get_terrain_name(&self, terrain: TerrainType) -> Option<&str> {
+        self.terrain_names
+            .get(terrain.0 as usize)
+            .map(String::as_str)
+    }
+
+    /// Picks the atlas index of the cell at `location` from the terrain around it.
+    pub fn resolve(&self, terrain: &TerrainMap, location: IVec2) -> Option<usize> {
+        let cell = terrain.get(location)?;
+        match &self.kind {
+            AutotileKind::Blob(sets) => {
+                let tiles = sets.get(cell.0 as usize)?;
+                let mut mask = 0u8;
+                for (bit, offset) in blob::NEIGHBOURS.iter().enumerate() {
+                    if terrain.get(location + *offset) == Some(cell) {
+                        mask |= 1 << bit;
+                    }
+                }
+                tiles
+                    .get(&blob::reduce(mask))
+                    .or_else(|| tiles.get(&u8::MAX))
+                    .copied()
+            }
+            AutotileKind::WangCorner(tiles) => {
+                let corner = |dx: i32, dy: i32| {
+                    [
+                        IVec2::ZERO,
+                        IVec2::new(dx, 0),
+                        IVec2::new(0, dy),
+                        IVec2::new(dx, dy),
+                    ]
+                    .into_iter()
+                    .filter_map(|offset| terrain.get(location + offset))
+                    .min()
+                    .unwrap_or(cell)
+                };
+                let corners = [corner(-1, 1), corner(1, 1), corner(-1, -1), corner(1, -1)];
+                tiles
+                    .get(&corners)
+                    .or_else(|| tiles.get(&[cell; 4]))
+                    .copied()
+            }
+        }
+    }
+
+    /// Resolves every painted cell into `tilemap`.
+    pub fn apply(&self, terrain: &TerrainMap, tilemap: &mut TilemapData) {
+        for (location, _) in terrain.iter() {
+            if let Some(atlas_index) = self.resolve(terrain, location) {
+                tilemap.set_tile(location, TileData::new(atlas_index));
+            }
+        }
+    }
+
+    /// Resolves a changed cell and its neighbours, whose transitions depend on it.
+    pub fn apply_around(&self, terrain: &TerrainMap, tilemap: &mut TilemapData, location: IVec2) {
+        for offset in std::iter::once(IVec2::ZERO).chain(blob::NEIGHBOURS) {
+            if let Some(atlas_index) = self.resolve(terrain, location + offset) {
+                tilemap.set_tile(location + offset, TileData::new(atlas_index));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_terrain(rows: &[&str]) -> TerrainMap {
+        let mut terrain = TerrainMap::new();
+        for (y, row) in rows.iter().rev().enumerate() {
+            for (x, c) in row.chars().enumerate() {
+                let location = IVec2::new(x as i32, y as i32);
+                match c {
+                    '.' => terrain.set(location, TerrainType(0)),
+                    '#' => terrain.set(location, TerrainType(1)),
+                    _ => {}
+                }
+            }
+        }
+        terrain
+    }
+
+    #[test]
+    fn blob_masks() {
+        assert_eq!(blob::masks().count(), 47);
+        assert_eq!(blob::reduce(blob::NE), 0);
+        assert_eq!(blob::reduce(blob::N | blob::NE), blob::N);
+        assert_eq!(
+            blob::reduce(blob::N | blob::NE | blob::E),
+            blob::N | blob::NE | blob::E
+        );
+        assert_eq!(blob::reduce(u8::MAX), u8::MAX);
+    }
+
+    #[test]
+    fn blob() {
+        let tiles: HashMap<u8, usize> = blob::masks().enumerate().map(|(i, m)| (m, i)).collect();
+        let set = AutotileSet::new(
+            vec!["dirt".into(), "grass".into()],
+            AutotileKind::Blob(vec![tiles.clone(), tiles.clone()]),
+        );
+        let index_of = |mask: u8| tiles[&mask];
+
+        let terrain = make_terrain(&[
+            "....", //
+            ".##.", //
+            ".##.", //
+            "....", //
+        ]);
+        // Top left grass cell has grass to the east, south and south east.
+        assert_eq!(
+            set.resolve(&terrain, IVec2::new(1, 2)),
+            Some(index_of(blob::E | blob::SE | blob::S))
+        );
+        // Dirt in the corner only matches dirt along the edges of the map.
+        assert_eq!(
+            set.resolve(&terrain, IVec2::new(0, 0)),
+            Some(index_of(blob::N | blob::E))
+        );
+
+        let terrain = make_terrain(&[
+            "#.#", //
+            ".#.", //
+            "#.#", //
+        ]);
+        // Diagonal neighbours alone don't connect.
+        assert_eq!(set.resolve(&terrain, IVec2::new(1, 1)), Some(index_of(0)));
+
+        let terrain = make_terrain(&[
+            "###", //
+            "###", //
+            "###", //
+        ]);
+        assert_eq!(
+            set.resolve(&terrain, IVec2::new(1, 1)),
+            Some(index_of(u8::MAX))
+        );
+        assert_eq!(set.resolve(&terrain, IVec2::new(5, 5)), None);
+    }
+
+    #[test]
+    fn wang_corners() {
+        let (d, g) = (TerrainType(0), TerrainType(1));
+        let mut tiles = HashMap::default();
+        tiles.insert([d, d, d, d], 0);
+        tiles.insert([g, g, g, g], 1);
+        tiles.insert([g, g, d, d], 2);
+        tiles.insert([d, g, d, d], 3);
+        tiles.insert([g, d, d, d], 4);
+        let set = AutotileSet::new(
+            vec!["dirt".into(), "grass".into()],
+            AutotileKind::WangCorner(tiles),
+        );
+
+        let terrain = make_terrain(&[
+            "####", //
+            "####", //
+            "####", //
+            "....", //
+        ]);
+        assert_eq!(set.resolve(&terrain, IVec2::new(1, 2)), Some(1));
+        assert_eq!(set.resolve(&terrain, IVec2::new(1, 1)), Some(2));
+        assert_eq!(set.resolve(&terrain, IVec2::new(1, 0)), Some(0));
+
+        let terrain = make_terrain(&[
+            "##.", //
+            "##.", //
+            "...", //
+        ]);
+        assert_eq!(set.resolve(&terrain, IVec2::new(0, 2)), Some(1));
+        // Only the north west corner is surrounded by grass.
+        assert_eq!(set.resolve(&terrain, IVec2::new(1, 1)), Some(4));
+        // Missing transitions fall back to the full tile of the cell.
+        assert_eq!(set.resolve(&terrain, IVec2::new(1, 2)), Some(1));
+    }
+
+    #[test]
+    fn apply_around() {
+        let tiles: HashMap<u8, usize> = blob::masks().enumerate().map(|(i, m)| (m, i)).collect();
+        let set = AutotileSet::new(
+            vec!["dirt".into(), "grass".into()],
+            AutotileKind::Blob(vec![tiles.clone(), tiles.clone()]),
+        );
+        let mut terrain = make_terrain(&[
+            "...", //
+            "...", //
+            "...", //
+        ]);
+        let mut tilemap = TilemapData::new();
+        set.apply(&terrain, &mut tilemap);
+        assert_eq!(
+            tilemap.get_tile(IVec2::new(1, 1)).unwrap().atlas_index,
+            tiles[&u8::MAX]
+        );
+
+        terrain.set(IVec2::new(1, 1), TerrainType(1));
+        set.apply_around(&terrain, &mut tilemap, IVec2::new(1, 1));
+        assert_eq!(
+            tilemap.get_tile(IVec2::new(1, 1)).unwrap().atlas_index,
+            tiles[&0]
+        );
+        assert_eq!(
+            tilemap.get_tile(IVec2::new(0, 1)).unwrap().atlas_index,
+            tiles[&(blob::N | blob::S)]
+        );
+    }
+}
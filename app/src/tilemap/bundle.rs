@@ -1,96 +1,108 @@
-use crate::asset::TilemapAssetGroup;
-use crate::tilemap::data::ChunkData;
-use crate::tilemap::generator::random::RandomTilemapGenerator;
+use crate::tilemap::animation::TileAnimations;
+use crate::tilemap::data::TilemapData;
 use crate::tilemap::material::TilemapMaterial;
-use crate::tilemap::TILEMAP_CHUNK_SIZE;
 use bevy::prelude::*;
-use bevy::render::mesh::{Indices, PrimitiveTopology};
-use bevy::sprite::MaterialMesh2dBundle;
+use bevy::utils::HashMap;
+
+#[derive(Component, Default)]
+pub struct TilemapComponent {
+    layers: Vec<TilemapLayer>,
+}
+
+/// A named grid of tiles drawn with its own material, stacked by `z_offset`.
+pub struct TilemapLayer {
+    pub name: String,
+    pub z_offset: f32,
+    pub opacity: f32,
+    pub data: TilemapData,
+    pub texture_atlas: Handle<TextureAtlas>,
+    pub material: Handle<TilemapMaterial>,
+    pub animations: TileAnimations,
+    pub(crate) frame_texture: Option<Handle<Image>>,
+    pub(crate) chunk_entities: HashMap<IVec2, Entity>,
+}
 
 #[derive(Bundle)]
 pub struct TilemapBundle {
+    tilemap: TilemapComponent,
     #[bundle]
-    obj: MaterialMesh2dBundle<TilemapMaterial>,
+    spatial: SpatialBundle,
 }
 
-impl TilemapBundle {
-    pub fn make(
-        tilemap_asset_group: &Res<TilemapAssetGroup>,
-        mut materials: ResMut<Assets<TilemapMaterial>>,
-        meshes: &mut Assets<Mesh>,
-        images: &Res<Assets<Image>>,
-        texture_atlases: &Res<Assets<TextureAtlas>>,
-    ) -> Self {
-        let tilemap = RandomTilemapGenerator::generate();
-        let chunk = tilemap.get_chunk(IVec2::ZERO).unwrap();
-        let texture_atlas = texture_atlases
-            .get(&tilemap_asset_group.texture_atlas)
-            .unwrap();
-        let texture = images.get(&texture_atlas.texture).unwrap();
+impl TilemapComponent {
+    pub fn new(layers: impl IntoIterator<Item = TilemapLayer>) -> Self {
+        TilemapComponent {
+            layers: layers.into_iter().collect(),
+        }
+    }
 
-        let mesh = make_chunk_mesh(chunk, texture_atlas, texture);
+    pub fn add_layer(&mut self, layer: TilemapLayer) -> usize {
+        self.layers.push(layer);
+        self.layers.len() - 1
+    }
 
-        TilemapBundle {
-            obj: MaterialMesh2dBundle {
-                mesh: meshes.add(mesh).into(),
-                transform: Transform::default(),
-                material: materials.add(TilemapMaterial {
-                    color_texture: texture_atlas.texture.clone(),
-                    lighting_texture: Default::default(),
-                }),
-                ..Default::default()
-            },
-        }
+    pub fn layers(&self) -> &[TilemapLayer] {
+        &self.layers
+    }
+
+    pub fn layers_mut(&mut self) -> &mut [TilemapLayer] {
+        &mut self.layers
+    }
+
+    pub fn get_layer(&self, name: &str) -> Option<&TilemapLayer> {
+        self.layers.iter().find(|layer| layer.name == name)
+    }
+
+    pub fn get_layer_mut(&mut self, name: &str) -> Option<&mut TilemapLayer> {
+        self.layers.iter_mut().find(|layer| layer.name == name)
     }
 }
 
-const QUAD_VERTEX_POSITIONS: [Vec2; 4] = [
-    Vec2::new(0.0, 0.0),
-    Vec2::new(1.0, 0.0),
-    Vec2::new(1.0, 1.0),
-    Vec2::new(0.0, 1.0),
-];
-const QUAD_INDICES: [u32; 6] = [0, 2, 3, 0, 1, 2];
-const QUAD_UVS: [Vec2; 4] = [
-    Vec2::new(0., 1.),
-    Vec2::new(1., 1.),
-    Vec2::new(1., 0.),
-    Vec2::new(0., 0.),
-];
+impl TilemapLayer {
+    pub fn new(
+        name: impl Into<String>,
+        data: TilemapData,
+        texture_atlas: Handle<TextureAtlas>,
+        material: Handle<TilemapMaterial>,
+    ) -> Self {
+        TilemapLayer {
+            name: name.into(),
+            z_offset: 0.0,
+            opacity: 1.0,
+            data,
+            texture_atlas,
+            material,
+            animations: Default::default(),
+            frame_texture: None,
+            chunk_entities: Default::default(),
+        }
+    }
 
-fn make_chunk_mesh(chunk: &ChunkData, texture_atlas: &TextureAtlas, texture: &Image) -> Mesh {
-    const DEFAULT_CAPACITY: usize = TILEMAP_CHUNK_SIZE as usize * 4;
-    let mut positions: Vec<[f32; 3]> = Vec::with_capacity(DEFAULT_CAPACITY);
-    // let mut normals: Vec<[f32; 3]> = Vec::with_capacity(DEFAULT_CAPACITY);
-    let mut uvs: Vec<[f32; 2]> = Vec::with_capacity(DEFAULT_CAPACITY);
-    let mut indices: Vec<u32> = Vec::with_capacity(DEFAULT_CAPACITY);
+    pub fn with_z_offset(mut self, z_offset: f32) -> Self {
+        self.z_offset = z_offset;
+        self
+    }
 
-    let mut stride = 0u32;
-    for y in 0..TILEMAP_CHUNK_SIZE {
-        for x in 0..TILEMAP_CHUNK_SIZE {
-            let tile = chunk.get_tile_at(x, y);
+    pub fn with_opacity(mut self, opacity: f32) -> Self {
+        self.opacity = opacity;
+        self
+    }
 
-            positions.extend(QUAD_VERTEX_POSITIONS.map(|p| [p.x + x as f32, p.y + y as f32, 0.0]));
+    pub fn with_animations(mut self, animations: TileAnimations) -> Self {
+        self.animations = animations;
+        self
+    }
 
-            let rect = texture_atlas.textures[tile.atlas_index];
-            let uv_min = rect.min / texture.size();
-            let uv_max = rect.max / texture.size();
-            uvs.extend([
-                [uv_min.x, uv_max.y],
-                [uv_max.x, uv_max.y],
-                [uv_max.x, uv_min.y],
-                [uv_min.x, uv_min.y],
-            ]);
+    pub fn get_chunk_entity(&self, location: IVec2) -> Option<Entity> {
+        self.chunk_entities.get(&location).copied()
+    }
+}
 
-            indices.extend(QUAD_INDICES.map(|i| i + stride));
-            stride += 4;
+impl TilemapBundle {
+    pub fn new(layers: impl IntoIterator<Item = TilemapLayer>) -> Self {
+        TilemapBundle {
+            tilemap: TilemapComponent::new(layers),
+            spatial: SpatialBundle::default(),
         }
     }
-
-    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
-    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
-    // mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
-    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
-    mesh.set_indices(Some(Indices::U32(indices)));
-    mesh
 }
@@ -0,0 +1,368 @@
+use crate::tilemap::bundle::{TilemapComponent, TilemapLayer};
+use crate::tilemap::data::ChunkData;
+use crate::tilemap::material::TilemapMaterial;
+use crate::tilemap::TILEMAP_CHUNK_SIZE;
+use bevy::prelude::*;
+use bevy::render::mesh::{Indices, MeshVertexAttribute, PrimitiveTopology};
+use bevy::render::primitives::Aabb;
+use bevy::render::render_resource::VertexFormat;
+use bevy::sprite::{MaterialMesh2dBundle, Mesh2dHandle};
+use bevy::utils::HashSet;
+
+pub const ATTRIBUTE_ATLAS_INDEX: MeshVertexAttribute =
+    MeshVertexAttribute::new("Vertex_AtlasIndex", 100_001, VertexFormat::Uint32);
+
+#[derive(Component, Debug)]
+pub struct TilemapChunkComponent {
+    pub layer: usize,
+    pub location: IVec2,
+}
+
+#[derive(Bundle)]
+pub struct TilemapChunkBundle {
+    chunk: TilemapChunkComponent,
+    #[bundle]
+    obj: MaterialMesh2dBundle<TilemapMaterial>,
+    aabb: Aabb,
+}
+
+impl TilemapChunkBundle {
+    pub fn new(
+        layer_index: usize,
+        layer: &TilemapLayer,
+        location: IVec2,
+        mesh: Handle<Mesh>,
+    ) -> Self {
+        TilemapChunkBundle {
+            chunk: TilemapChunkComponent {
+                layer: layer_index,
+                location,
+            },
+            obj: MaterialMesh2dBundle {
+                mesh: mesh.into(),
+                material: layer.material.clone(),
+                transform: Transform::from_translation(
+                    (location * TILEMAP_CHUNK_SIZE as i32)
+                        .as_vec2()
+                        .extend(layer.z_offset),
+                ),
+                ..Default::default()
+            },
+            // 2D meshes don't get their bounds computed, without one the chunk would never be culled.
+            aabb: Aabb::from_min_max(
+                Vec3::ZERO,
+                Vec3::new(TILEMAP_CHUNK_SIZE as f32, TILEMAP_CHUNK_SIZE as f32, 0.0),
+            ),
+        }
+    }
+}
+
+/// Keeps one chunk entity per layer chunk, remeshing only the chunks that were marked dirty.
+pub fn tilemap_chunk_system(
+    mut commands: Commands,
+    mut tilemap_query: Query<(Entity, &mut TilemapComponent)>,
+    chunk_query: Query<&Mesh2dHandle, With<TilemapChunkComponent>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    for (entity, mut tilemap) in tilemap_query.iter_mut() {
+        for (layer_index, layer) in tilemap
+            .bypass_change_detection()
+            .layers_mut()
+            .iter_mut()
+            .enumerate()
+        {
+            if layer.data.dirty_chunks().is_empty() {
+                continue;
+            }
+            for location in layer.data.take_dirty_chunks() {
+                let chunk = match layer.data.get_chunk(location) {
+                    Some(chunk) => chunk,
+                    None => {
+                        if let Some(chunk_entity) = layer.chunk_entities.remove(&location) {
+                            commands.entity(chunk_entity).despawn_recursive();
+                        }
+                        continue;
+                    }
+                };
+
+                let mesh = make_chunk_mesh(chunk);
+                if let Some(chunk_entity) = layer.chunk_entities.get(&location) {
+                    if let Ok(mesh_handle) = chunk_query.get(*chunk_entity) {
+                        if let Some(chunk_mesh) = meshes.get_mut(&mesh_handle.0) {
+                            *chunk_mesh = mesh;
+                            continue;
+                        }
+                    }
+                    commands
+                        .entity(*chunk_entity)
+                        .insert(Mesh2dHandle(meshes.add(mesh)));
+                } else {
+                    let chunk_entity = commands
+                        .spawn(TilemapChunkBundle::new(
+                            layer_index,
+                            layer,
+                            location,
+                            meshes.add(mesh),
+                        ))
+                        .id();
+                    commands.entity(entity).add_child(chunk_entity);
+                    layer.chunk_entities.insert(location, chunk_entity);
+                }
+            }
+        }
+    }
+}
+
+/// Applies layer z offsets and opacities to chunk entities and layer materials.
+///
+/// Layers sharing a material get a copy of it, so that their opacities don't overwrite each other.
+pub fn tilemap_layer_system(
+    mut tilemap_query: Query<&mut TilemapComponent>,
+    mut chunk_query: Query<
+        (&mut Transform, &mut Handle<TilemapMaterial>),
+        With<TilemapChunkComponent>,
+    >,
+    mut materials: ResMut<Assets<TilemapMaterial>>,
+) {
+    let mut used_materials = HashSet::new();
+    for mut tilemap in tilemap_query.iter_mut() {
+        let changed = tilemap.is_changed();
+        for layer in tilemap.bypass_change_detection().layers_mut() {
+            let mut unshared = false;
+            if !used_materials.insert(layer.material.id()) {
+                if let Some(material) = materials.get(&layer.material).cloned() {
+                    layer.material = materials.add(material);
+                    used_materials.insert(layer.material.id());
+                    unshared = true;
+                }
+            }
+            if !changed && !unshared {
+                continue;
+            }
+
+            for chunk_entity in layer.chunk_entities.values() {
+                if let Ok((mut transform, mut material)) = chunk_query.get_mut(*chunk_entity) {
+                    if transform.translation.z != layer.z_offset {
+                        transform.translation.z = layer.z_offset;
+                    }
+                    if *material != layer.material {
+                        *material = layer.material.clone();
+                    }
+                }
+            }
+
+            let opacity_changed = materials
+                .get(&layer.material)
+                .map_or(false, |material| material.color.a() != layer.opacity);
+            if opacity_changed {
+                if let Some(material) = materials.get_mut(&layer.material) {
+                    material.color.set_a(layer.opacity);
+                }
+            }
+        }
+    }
+}
+
+const QUAD_VERTEX_POSITIONS: [Vec2; 4] = [
+    Vec2::new(0.0, 0.0),
+    Vec2::new(1.0, 0.0),
+    Vec2::new(1.0, 1.0),
+    Vec2::new(0.0, 1.0),
+];
+const QUAD_INDICES: [u32; 6] = [0, 2, 3, 0, 1, 2];
+const QUAD_UVS: [Vec2; 4] = [
+    Vec2::new(0., 1.),
+    Vec2::new(1., 1.),
+    Vec2::new(1., 0.),
+    Vec2::new(0., 0.),
+];
+
+/// Makes the mesh of a chunk, the atlas rect of each tile is looked up in the shader.
+pub fn make_chunk_mesh(chunk: &ChunkData) -> Mesh {
+    const DEFAULT_CAPACITY: usize = TILEMAP_CHUNK_SIZE as usize * 4;
+    let mut positions: Vec<[f32; 3]> = Vec::with_capacity(DEFAULT_CAPACITY);
+    // let mut normals: Vec<[f32; 3]> = Vec::with_capacity(DEFAULT_CAPACITY);
+    let mut uvs: Vec<[f32; 2]> = Vec::with_capacity(DEFAULT_CAPACITY);
+    let mut atlas_indices: Vec<u32> = Vec::with_capacity(DEFAULT_CAPACITY);
+    let mut indices: Vec<u32> = Vec::with_capacity(DEFAULT_CAPACITY);
+
+    let mut stride = 0u32;
+    for y in 0..TILEMAP_CHUNK_SIZE {
+        for x in 0..TILEMAP_CHUNK_SIZE {
+            let tile = chunk.get_tile_at(x, y);
+            if tile.is_empty() {
+                continue;
+            }
+
+            positions.extend(QUAD_VERTEX_POSITIONS.map(|p| [p.x + x as f32, p.y + y as f32, 0.0]));
+            uvs.extend(QUAD_UVS.map(|uv| uv.to_array()));
+            atlas_indices.extend([tile.atlas_index as u32; 4]);
+
+            indices.extend(QUAD_INDICES.map(|i| i + stride));
+            stride += 4;
+        }
+    }
+
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    // mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    mesh.insert_attribute(ATTRIBUTE_ATLAS_INDEX, atlas_indices);
+    mesh.set_indices(Some(Indices::U32(indices)));
+    mesh
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tilemap::bundle::{TilemapBundle, TilemapLayer};
+    use crate::tilemap::data::{TileData, TilemapData};
+    use bevy::asset::AssetPlugin;
+    use bevy::ecs::event::ManualEventReader;
+    use bevy::render::mesh::VertexAttributeValues;
+
+    #[test]
+    fn empty_tiles() {
+        let mut chunk = ChunkData::empty();
+        chunk.set_tile_at(1, 2, TileData::new(3));
+        let mesh = make_chunk_mesh(&chunk);
+        assert_eq!(mesh.count_vertices(), 4);
+        assert!(matches!(
+            mesh.attribute(ATTRIBUTE_ATLAS_INDEX),
+            Some(VertexAttributeValues::Uint32(indices)) if indices == &[3; 4]
+        ));
+    }
+
+    #[test]
+    fn remesh_dirty_chunks() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_plugin(AssetPlugin::default())
+            .add_asset::<Mesh>()
+            .add_system(tilemap_chunk_system);
+
+        let mut data = TilemapData::new();
+        data.set_tile(IVec2::new(0, 0), TileData::new(0));
+        data.set_tile(IVec2::new(-1, -1), TileData::new(0));
+        data.set_tile(IVec2::new(40, 0), TileData::new(0));
+        let tilemap_entity = app
+            .world
+            .spawn(TilemapBundle::new([TilemapLayer::new(
+                "ground",
+                data,
+                Default::default(),
+                Default::default(),
+            )]))
+            .id();
+
+        app.update();
+        let mut mesh_events = ManualEventReader::<AssetEvent<Mesh>>::default();
+        let created = mesh_events
+            .iter(app.world.resource::<Events<AssetEvent<Mesh>>>())
+            .filter(|event| matches!(event, AssetEvent::Created { .. }))
+            .count();
+        assert_eq!(created, 3);
+        assert_eq!(
+            app.world
+                .query::<&TilemapChunkComponent>()
+                .iter(&app.world)
+                .count(),
+            3
+        );
+
+        let mut tilemap = app
+            .world
+            .get_mut::<TilemapComponent>(tilemap_entity)
+            .unwrap();
+        let layer = tilemap.get_layer_mut("ground").unwrap();
+        layer.data.set_tile(IVec2::new(-5, -7), TileData::new(0));
+        let edited_chunk = layer.get_chunk_entity(IVec2::new(-1, -1)).unwrap();
+        app.update();
+
+        let modified: Vec<_> = mesh_events
+            .iter(app.world.resource::<Events<AssetEvent<Mesh>>>())
+            .filter_map(|event| match event {
+                AssetEvent::Modified { handle } => Some(handle.clone()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            modified,
+            vec![app
+                .world
+                .get::<Mesh2dHandle>(edited_chunk)
+                .unwrap()
+                .0
+                .clone()]
+        );
+
+        let mut tilemap = app
+            .world
+            .get_mut::<TilemapComponent>(tilemap_entity)
+            .unwrap();
+        let layer = tilemap.get_layer_mut("ground").unwrap();
+        layer.data.remove_chunk(IVec2::new(1, 0));
+        app.update();
+        assert_eq!(
+            app.world
+                .query::<&TilemapChunkComponent>()
+                .iter(&app.world)
+                .count(),
+            2
+        );
+    }
+
+    #[test]
+    fn layer_opacities() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_plugin(AssetPlugin::default())
+            .add_asset::<Mesh>()
+            .add_asset::<TilemapMaterial>()
+            .add_systems((tilemap_layer_system, tilemap_chunk_system).chain());
+
+        let material = app
+            .world
+            .resource_mut::<Assets<TilemapMaterial>>()
+            .add(TilemapMaterial::new(Default::default()));
+        let mut data = TilemapData::new();
+        data.set_tile(IVec2::ZERO, TileData::new(0));
+        let tilemap_entity = app
+            .world
+            .spawn(TilemapBundle::new([
+                TilemapLayer::new("ground", data.clone(), Default::default(), material.clone())
+                    .with_opacity(0.5),
+                TilemapLayer::new("decals", data, Default::default(), material)
+                    .with_z_offset(1.0)
+                    .with_opacity(0.25),
+            ]))
+            .id();
+        app.update();
+
+        let opacities = |app: &mut App| {
+            let tilemap = app.world.get::<TilemapComponent>(tilemap_entity).unwrap();
+            let materials = app.world.resource::<Assets<TilemapMaterial>>();
+            tilemap
+                .layers()
+                .iter()
+                .map(|layer| {
+                    let chunk_entity = layer.get_chunk_entity(IVec2::ZERO).unwrap();
+                    assert_eq!(
+                        app.world.get::<Handle<TilemapMaterial>>(chunk_entity),
+                        Some(&layer.material)
+                    );
+                    materials.get(&layer.material).unwrap().color.a()
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(opacities(&mut app), [0.5, 0.25]);
+
+        let mut tilemap = app
+            .world
+            .get_mut::<TilemapComponent>(tilemap_entity)
+            .unwrap();
+        tilemap.get_layer_mut("decals").unwrap().opacity = 1.0;
+        app.update();
+        assert_eq!(opacities(&mut app), [0.5, 1.0]);
+    }
+}
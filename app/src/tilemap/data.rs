@@ -1,19 +1,29 @@
+use crate::tilemap::properties::{
+    ChunkBitset, ChunkFlagsCache, TileFlags, TileProperties, TilesetProperties,
+};
 use crate::tilemap::TILEMAP_CHUNK_SIZE;
 use array_init::array_init;
 use bevy::prelude::*;
+use bevy::utils::HashSet;
 use std::collections::BTreeMap;
+use std::sync::Arc;
 
 #[derive(Debug, Clone)]
 pub struct TilemapData {
+    /// Chunks edited directly through this map have to be marked with [`TilemapData::mark_chunk_dirty`].
     pub chunks: BTreeMap<i32, BTreeMap<i32, ChunkData>>,
+    dirty_chunks: HashSet<IVec2>,
+    /// Shared by every tile, looked up by atlas index.
+    properties: Arc<TilesetProperties>,
+    chunk_flags: ChunkFlagsCache,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct ChunkData {
     pub tiles: [TileData; TILEMAP_CHUNK_SIZE as usize * TILEMAP_CHUNK_SIZE as usize],
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq)]
 pub struct TileData {
     pub atlas_index: usize,
     pub color: Option<Color>,
@@ -23,13 +33,85 @@ impl TilemapData {
     pub fn new() -> Self {
         TilemapData {
             chunks: Default::default(),
+            dirty_chunks: Default::default(),
+            properties: Default::default(),
+            chunk_flags: Default::default(),
         }
     }
 
+    pub fn with_properties(mut self, properties: impl Into<Arc<TilesetProperties>>) -> Self {
+        self.set_properties(properties);
+        self
+    }
+
+    pub fn properties(&self) -> &Arc<TilesetProperties> {
+        &self.properties
+    }
+
+    pub fn set_properties(&mut self, properties: impl Into<Arc<TilesetProperties>>) {
+        self.properties = properties.into();
+        self.chunk_flags.clear();
+    }
+
     pub fn get_chunk(&self, location: IVec2) -> Option<&ChunkData> {
         self.chunks.get(&location.x)?.get(&location.y)
     }
 
+    pub fn get_chunk_mut(&mut self, location: IVec2) -> Option<&mut ChunkData> {
+        let chunk = self.chunks.get_mut(&location.x)?.get_mut(&location.y)?;
+        self.dirty_chunks.insert(location);
+        self.chunk_flags.invalidate(location);
+        Some(chunk)
+    }
+
+    pub fn insert_chunk(&mut self, location: IVec2, chunk: ChunkData) -> Option<ChunkData> {
+        self.mark_chunk_dirty(location);
+        self.chunks
+            .entry(location.x)
+            .or_default()
+            .insert(location.y, chunk)
+    }
+
+    pub fn remove_chunk(&mut self, location: IVec2) -> Option<ChunkData> {
+        let columns = self.chunks.get_mut(&location.x)?;
+        let chunk = columns.remove(&location.y)?;
+        if columns.is_empty() {
+            self.chunks.remove(&location.x);
+        }
+        self.mark_chunk_dirty(location);
+        Some(chunk)
+    }
+
+    pub fn iter_chunks(&self) -> impl Iterator<Item = (IVec2, &ChunkData)> {
+        self.chunks.iter().flat_map(|(x, columns)| {
+            columns
+                .iter()
+                .map(move |(y, chunk)| (IVec2::new(*x, *y), chunk))
+        })
+    }
+
+    /// Tile at a location, `None` outside of the chunks and on empty tiles.
+    pub fn get_tile(&self, location: IVec2) -> Option<&TileData> {
+        self.get_chunk(Self::tilemap_to_chunk(location))
+            .map(|chunk| chunk.get_tile(ChunkData::tilemap_to_chunk_tile(location)))
+            .filter(|tile| !tile.is_empty())
+    }
+
+    pub fn get_tile_properties(&self, location: IVec2) -> Option<&TileProperties> {
+        self.get_tile(location)
+            .map(|tile| self.properties.get(tile.atlas_index))
+    }
+
+    /// Bits of the chunk tiles having all of `flags`, see [`ChunkBitset`].
+    ///
+    /// The bits are cached until the chunk is marked dirty.
+    pub fn get_chunk_flags(&self, location: IVec2, flags: TileFlags) -> Option<ChunkBitset> {
+        let chunk = self.get_chunk(location)?;
+        Some(self.chunk_flags.get(location, flags, || {
+            self.properties.get_chunk_flags_all(chunk)
+        }))
+    }
+
     pub fn set_tile(&mut self, location: IVec2, tile: TileData) {
         let chunk_location = Self::tilemap_to_chunk(location);
         let chunk = self
@@ -39,10 +121,28 @@ impl TilemapData {
             .entry(chunk_location.y)
             .or_insert_with(|| ChunkData::new());
         chunk.set_tile(ChunkData::tilemap_to_chunk_tile(location), tile);
+        self.mark_chunk_dirty(chunk_location);
+    }
+
+    /// Chunks that were added, removed or edited since the last [`TilemapData::take_dirty_chunks`].
+    pub fn dirty_chunks(&self) -> &HashSet<IVec2> {
+        &self.dirty_chunks
+    }
+
+    pub fn mark_chunk_dirty(&mut self, location: IVec2) {
+        self.dirty_chunks.insert(location);
+        self.chunk_flags.invalidate(location);
+    }
+
+    pub fn take_dirty_chunks(&mut self) -> HashSet<IVec2> {
+        std::mem::take(&mut self.dirty_chunks)
     }
 
     pub fn tilemap_to_chunk(tile_location: IVec2) -> IVec2 {
-        tile_location / TILEMAP_CHUNK_SIZE as i32
+        IVec2::new(
+            tile_location.x.div_euclid(TILEMAP_CHUNK_SIZE as i32),
+            tile_location.y.div_euclid(TILEMAP_CHUNK_SIZE as i32),
+        )
     }
 
     pub fn get_chunk_rect(&self) -> Rect {
@@ -58,6 +158,13 @@ impl TilemapData {
     }
 }
 
+/// Maps are equal when their tiles are, whatever their dirty chunks and properties.
+impl PartialEq for TilemapData {
+    fn eq(&self, other: &Self) -> bool {
+        self.chunks == other.chunks
+    }
+}
+
 impl ChunkData {
     pub fn new() -> Self {
         ChunkData {
@@ -65,6 +172,13 @@ impl ChunkData {
         }
     }
 
+    /// Chunk without tiles, [`ChunkData::new`] fills it with the first tile of the atlas.
+    pub fn empty() -> Self {
+        ChunkData {
+            tiles: array_init(|_| TileData::EMPTY),
+        }
+    }
+
     pub fn get_tile(&self, location: UVec2) -> &TileData {
         self.get_tile_at(location.x, location.y)
     }
@@ -90,42 +204,45 @@ impl ChunkData {
     }
 
     pub fn tilemap_to_chunk_tile(location: IVec2) -> UVec2 {
-        let x = location.x % TILEMAP_CHUNK_SIZE as i32;
-        let y = location.y % TILEMAP_CHUNK_SIZE as i32;
         UVec2::new(
-            if x < 0 {
-                (TILEMAP_CHUNK_SIZE as i32 + x) as u32
-            } else {
-                x as u32
-            },
-            if y < 0 {
-                (TILEMAP_CHUNK_SIZE as i32 + y) as u32
-            } else {
-                y as u32
-            },
+            location.x.rem_euclid(TILEMAP_CHUNK_SIZE as i32) as u32,
+            location.y.rem_euclid(TILEMAP_CHUNK_SIZE as i32) as u32,
         )
     }
 }
 
 impl TileData {
+    /// Cell without a tile, drawn as nothing and without properties.
+    pub const EMPTY: TileData = TileData {
+        atlas_index: usize::MAX,
+        color: None,
+    };
+
     pub fn new(atlas_index: usize) -> Self {
         TileData {
             atlas_index,
             color: None,
         }
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.atlas_index == TileData::EMPTY.atlas_index
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::tilemap::generator::random::RandomTilemapGenerator;
+    use crate::tilemap::generator::noise::NoiseTilemapGenerator;
+    use crate::tilemap::generator::{TilemapGenerator, TilemapGeneratorConfig};
 
     #[test]
     fn basic() {
         println!(
             "size: {}",
-            std::mem::size_of_val(&RandomTilemapGenerator::generate())
+            std::mem::size_of_val(
+                &NoiseTilemapGenerator.generate(&TilemapGeneratorConfig::default())
+            )
         );
     }
 
@@ -139,5 +256,13 @@ mod tests {
             ChunkData::tilemap_to_chunk_tile(IVec2::new(-1, -5)),
             UVec2::new(31, 27)
         );
+        assert_eq!(
+            TilemapData::tilemap_to_chunk(IVec2::new(40, 45)),
+            IVec2::new(1, 1)
+        );
+        assert_eq!(
+            TilemapData::tilemap_to_chunk(IVec2::new(-1, -33)),
+            IVec2::new(-1, -2)
+        );
     }
 }
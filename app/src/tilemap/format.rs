@@ -0,0 +1,432 @@
+//! Binary format of [`TilemapData`].
+//!
+//! A file starts with the magic `DTMP`, a `u16` version and a `u32` chunk count. Each chunk follows
+//! as its `i32` location, the `u32` length of its payload and the payload itself:
+//!
+//! * a flags byte, bit 0 set when the tiles have colors
+//! * runs of equal atlas indices over the tiles in [`ChunkData::tiles`] order, each a varint
+//!   length and a varint atlas index
+//! * with colors, runs of equal colors, each a varint length, a presence byte and the sRGBA
+//!   components as `f32` when present
+//!
+//! Colors are normalized to sRGBA when written, so a tile colored with e.g. [`Color::Hsla`] reads
+//! back as the same color in [`Color::Rgba`].
+//!
+//! Integers are little endian, varints are LEB128. Chunk locations are unique. Readers skip payload
+//! bytes they don't know, so chunks can grow new trailing data without a new version. Other
+//! changes bump the version and register a [`TilemapMigration`] from the previous one.
+
+use crate::tilemap::data::{ChunkData, TilemapData};
+use crate::tilemap::TILEMAP_CHUNK_SIZE;
+use bevy::prelude::*;
+use thiserror::Error;
+
+pub const TILEMAP_FORMAT_MAGIC: [u8; 4] = *b"DTMP";
+pub const TILEMAP_FORMAT_VERSION: u16 = 1;
+
+const CHUNK_TILE_COUNT: usize = TILEMAP_CHUNK_SIZE as usize * TILEMAP_CHUNK_SIZE as usize;
+const CHUNK_FLAG_COLORS: u8 = 1 << 0;
+
+/// Upgrades a whole file from the version it is registered for to a later one.
+pub type TilemapMigration = fn(&[u8]) -> Result<Vec<u8>, TilemapFormatError>;
+
+/// Migrations of older versions, keyed by the version they upgrade from.
+pub const TILEMAP_MIGRATIONS: &[(u16, TilemapMigration)] = &[];
+
+#[derive(Error, Debug)]
+pub enum TilemapFormatError {
+    #[error("not a tilemap file")]
+    InvalidMagic,
+    #[error("unsupported version {0}")]
+    UnsupportedVersion(u16),
+    #[error("unexpected end of data")]
+    UnexpectedEnd,
+    #[error("invalid {0}")]
+    InvalidData(&'static str),
+}
+
+pub fn encode_tilemap(tilemap: &TilemapData) -> Vec<u8> {
+    let chunks: Vec<_> = tilemap.iter_chunks().collect();
+    let mut bytes = Vec::new();
+    bytes.extend(TILEMAP_FORMAT_MAGIC);
+    bytes.extend(TILEMAP_FORMAT_VERSION.to_le_bytes());
+    bytes.extend((chunks.len() as u32).to_le_bytes());
+    for (location, chunk) in chunks {
+        let payload = encode_chunk(chunk);
+        bytes.extend(location.x.to_le_bytes());
+        bytes.extend(location.y.to_le_bytes());
+        bytes.extend((payload.len() as u32).to_le_bytes());
+        bytes.extend(payload);
+    }
+    bytes
+}
+
+pub fn decode_tilemap(bytes: &[u8]) -> Result<TilemapData, TilemapFormatError> {
+    decode_tilemap_with_migrations(bytes, TILEMAP_MIGRATIONS)
+}
+
+/// Decodes a file of any version that `migrations` can upgrade to the current one.
+pub fn decode_tilemap_with_migrations(
+    bytes: &[u8],
+    migrations: &[(u16, TilemapMigration)],
+) -> Result<TilemapData, TilemapFormatError> {
+    let mut migrated;
+    let mut bytes = bytes;
+    loop {
+        let version = read_header(&mut Reader::new(bytes))?;
+        if version == TILEMAP_FORMAT_VERSION {
+            break;
+        }
+        let migration = migrations
+            .iter()
+            .find(|(from_version, _)| *from_version == version)
+            .ok_or(TilemapFormatError::UnsupportedVersion(version))?;
+        migrated = (migration.1)(bytes)?;
+        bytes = &migrated;
+        // A migration not moving the version forward would loop forever.
+        if read_header(&mut Reader::new(bytes))? <= version {
+            return Err(TilemapFormatError::UnsupportedVersion(version));
+        }
+    }
+
+    let mut reader = Reader::new(bytes);
+    read_header(&mut reader)?;
+    let chunk_count = reader.read_u32()?;
+    let mut tilemap = TilemapData::new();
+    for _ in 0..chunk_count {
+        let location = IVec2::new(reader.read_i32()?, reader.read_i32()?);
+        let length = reader.read_u32()? as usize;
+        let chunk = decode_chunk(reader.read_bytes(length)?)?;
+        if tilemap.insert_chunk(location, chunk).is_some() {
+            return Err(TilemapFormatError::InvalidData("duplicate chunk location"));
+        }
+    }
+    Ok(tilemap)
+}
+
+/// Encodes the payload of a single chunk, see the [module docs](self).
+pub fn encode_chunk(chunk: &ChunkData) -> Vec<u8> {
+    let has_colors = chunk.tiles.iter().any(|tile| tile.color.is_some());
+    let mut bytes = vec![if has_colors { CHUNK_FLAG_COLORS } else { 0 }];
+
+    for (length, atlas_index) in runs(chunk.tiles.iter().map(|tile| tile.atlas_index)) {
+        write_varint(&mut bytes, length as u64);
+        write_varint(&mut bytes, atlas_index as u64);
+    }
+    if has_colors {
+        let colors = chunk
+            .tiles
+            .iter()
+            .map(|tile| tile.color.map(|color| color.as_rgba().as_rgba_f32()));
+        for (length, color) in runs(colors) {
+            write_varint(&mut bytes, length as u64);
+            match color {
+                Some(color) => {
+                    bytes.push(1);
+                    for component in color {
+                        bytes.extend(component.to_le_bytes());
+                    }
+                }
+                None => bytes.push(0),
+            }
+        }
+    }
+    bytes
+}
+
+pub fn decode_chunk(bytes: &[u8]) -> Result<ChunkData, TilemapFormatError> {
+    let mut reader = Reader::new(bytes);
+    let flags = reader.read_u8()?;
+    let mut chunk = ChunkData::new();
+
+    let mut i = 0;
+    while i < CHUNK_TILE_COUNT {
+        let length = read_run_length(&mut reader, CHUNK_TILE_COUNT - i)?;
+        let atlas_index = usize::try_from(reader.read_varint()?)
+            .map_err(|_| TilemapFormatError::InvalidData("atlas index"))?;
+        for tile in chunk.tiles[i..i + length].iter_mut() {
+            tile.atlas_index = atlas_index;
+        }
+        i += length;
+    }
+
+    if flags & CHUNK_FLAG_COLORS != 0 {
+        let mut i = 0;
+        while i < CHUNK_TILE_COUNT {
+            let length = read_run_length(&mut reader, CHUNK_TILE_COUNT - i)?;
+            let color = match reader.read_u8()? {
+                0 => None,
+                1 => Some(Color::rgba(
+                    reader.read_f32()?,
+                    reader.read_f32()?,
+                    reader.read_f32()?,
+                    reader.read_f32()?,
+                )),
+                _ => return Err(TilemapFormatError::InvalidData("color")),
+            };
+            for tile in chunk.tiles[i..i + length].iter_mut() {
+                tile.color = color;
+            }
+            i += length;
+        }
+    }
+    Ok(chunk)
+}
+
+impl TilemapData {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        encode_tilemap(self)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<TilemapData, TilemapFormatError> {
+        decode_tilemap(bytes)
+    }
+}
+
+/// Lengths of the runs of equal consecutive values.
+fn runs<T: PartialEq>(values: impl Iterator<Item = T>) -> Vec<(usize, T)> {
+    let mut runs: Vec<(usize, T)> = Vec::new();
+    for value in values {
+        match runs.last_mut() {
+            Some((length, last)) if *last == value => *length += 1,
+            _ => runs.push((1, value)),
+        }
+    }
+    runs
+}
+
+fn read_header(reader: &mut Reader) -> Result<u16, TilemapFormatError> {
+    if reader.read_bytes(TILEMAP_FORMAT_MAGIC.len())? != TILEMAP_FORMAT_MAGIC {
+        return Err(TilemapFormatError::InvalidMagic);
+    }
+    reader.read_u16()
+}
+
+fn read_run_length(reader: &mut Reader, remaining: usize) -> Result<usize, TilemapFormatError> {
+    let length = reader.read_varint()?;
+    if length == 0 || length > remaining as u64 {
+        return Err(TilemapFormatError::InvalidData("run length"));
+    }
+    Ok(length as usize)
+}
+
+fn write_varint(bytes: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+        if value == 0 {
+            bytes.push(byte);
+            return;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Reader { bytes }
+    }
+
+    fn read_bytes(&mut self, length: usize) -> Result<&'a [u8], TilemapFormatError> {
+        if self.bytes.len() < length {
+            return Err(TilemapFormatError::UnexpectedEnd);
+        }
+        let (bytes, rest) = self.bytes.split_at(length);
+        self.bytes = rest;
+        Ok(bytes)
+    }
+
+    fn read_array<const N: usize>(&mut self) -> Result<[u8; N], TilemapFormatError> {
+        Ok(self.read_bytes(N)?.try_into().unwrap())
+    }
+
+    fn read_u8(&mut self) -> Result<u8, TilemapFormatError> {
+        Ok(self.read_array::<1>()?[0])
+    }
+
+    fn read_u16(&mut self) -> Result<u16, TilemapFormatError> {
+        Ok(u16::from_le_bytes(self.read_array()?))
+    }
+
+    fn read_u32(&mut self) -> Result<u32, TilemapFormatError> {
+        Ok(u32::from_le_bytes(self.read_array()?))
+    }
+
+    fn read_i32(&mut self) -> Result<i32, TilemapFormatError> {
+        Ok(i32::from_le_bytes(self.read_array()?))
+    }
+
+    fn read_f32(&mut self) -> Result<f32, TilemapFormatError> {
+        Ok(f32::from_le_bytes(self.read_array()?))
+    }
+
+    fn read_varint(&mut self) -> Result<u64, TilemapFormatError> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.read_u8()?;
+            value |= ((byte & 0x7F) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(TilemapFormatError::InvalidData("varint"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tilemap::data::TileData;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    fn random_tilemap(rng: &mut StdRng) -> TilemapData {
+        let mut tilemap = TilemapData::new();
+        for _ in 0..rng.gen_range(0..6) {
+            let location = IVec2::new(rng.gen_range(-100..100), rng.gen_range(-100..100));
+            let mut chunk = ChunkData::new();
+            let with_colors = rng.gen_bool(0.5);
+            let mut i = 0;
+            while i < CHUNK_TILE_COUNT {
+                let length = rng.gen_range(1..=200).min(CHUNK_TILE_COUNT - i);
+                let tile = TileData {
+                    atlas_index: if rng.gen_bool(0.1) {
+                        rng.gen()
+                    } else {
+                        rng.gen_range(0..8)
+                    },
+                    color: (with_colors && rng.gen_bool(0.3))
+                        .then(|| Color::rgba(rng.gen(), rng.gen(), rng.gen(), rng.gen())),
+                };
+                chunk.tiles[i..i + length].fill(tile);
+                i += length;
+            }
+            tilemap.insert_chunk(location, chunk);
+        }
+        tilemap
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut rng = StdRng::seed_from_u64(11);
+        for _ in 0..200 {
+            let tilemap = random_tilemap(&mut rng);
+            let bytes = encode_tilemap(&tilemap);
+            let decoded = decode_tilemap(&bytes).unwrap();
+            assert_eq!(decoded.chunks, tilemap.chunks);
+
+            // Every truncation is an error rather than a panic.
+            let cut = rng.gen_range(0..bytes.len());
+            assert!(decode_tilemap(&bytes[..cut]).is_err());
+        }
+    }
+
+    #[test]
+    fn compression() {
+        let mut tilemap = TilemapData::new();
+        tilemap.set_tile(IVec2::new(3, 4), TileData::new(2));
+        let chunk = tilemap.get_chunk(IVec2::ZERO).unwrap();
+        // Flags and three runs, the length of the last one taking two bytes.
+        assert_eq!(encode_chunk(chunk).len(), 1 + 3 * 2 + 1);
+    }
+
+    #[test]
+    fn versions() {
+        let tilemap = random_tilemap(&mut StdRng::seed_from_u64(3));
+        let mut bytes = encode_tilemap(&tilemap);
+        assert!(matches!(
+            decode_tilemap(b"PNG\0\0\0"),
+            Err(TilemapFormatError::InvalidMagic)
+        ));
+
+        // Unknown trailing chunk data is skipped.
+        let mut extended = TilemapData::new();
+        extended.set_tile(IVec2::new(-1, 5), TileData::new(7));
+        let mut extended_bytes = encode_tilemap(&extended);
+        let length_offset = 4 + 2 + 4 + 8;
+        let length = u32::from_le_bytes(
+            extended_bytes[length_offset..length_offset + 4]
+                .try_into()
+                .unwrap(),
+        );
+        extended_bytes[length_offset..length_offset + 4]
+            .copy_from_slice(&(length + 3).to_le_bytes());
+        extended_bytes.extend([1, 2, 3]);
+        assert_eq!(
+            decode_tilemap(&extended_bytes).unwrap().chunks,
+            extended.chunks
+        );
+
+        // Pretend the current format replaced a version 0 without the chunk count.
+        bytes[4..6].copy_from_slice(&0u16.to_le_bytes());
+        bytes.drain(6..10);
+        assert!(matches!(
+            decode_tilemap(&bytes),
+            Err(TilemapFormatError::UnsupportedVersion(0))
+        ));
+        fn migrate_from_0(bytes: &[u8]) -> Result<Vec<u8>, TilemapFormatError> {
+            let mut reader = Reader::new(&bytes[6..]);
+            let mut chunk_count = 0u32;
+            while !reader.bytes.is_empty() {
+                reader.read_bytes(8)?;
+                let length = reader.read_u32()? as usize;
+                reader.read_bytes(length)?;
+                chunk_count += 1;
+            }
+            let mut migrated = bytes[..4].to_vec();
+            migrated.extend(1u16.to_le_bytes());
+            migrated.extend(chunk_count.to_le_bytes());
+            migrated.extend(&bytes[6..]);
+            Ok(migrated)
+        }
+        let decoded = decode_tilemap_with_migrations(&bytes, &[(0, migrate_from_0)]).unwrap();
+        assert_eq!(decoded.chunks, tilemap.chunks);
+
+        fn keep_version(bytes: &[u8]) -> Result<Vec<u8>, TilemapFormatError> {
+            Ok(bytes.to_vec())
+        }
+        assert!(matches!(
+            decode_tilemap_with_migrations(&bytes, &[(0, keep_version)]),
+            Err(TilemapFormatError::UnsupportedVersion(0))
+        ));
+
+        bytes[4..6].copy_from_slice(&(TILEMAP_FORMAT_VERSION + 1).to_le_bytes());
+        assert!(matches!(
+            decode_tilemap(&bytes),
+            Err(TilemapFormatError::UnsupportedVersion(2))
+        ));
+    }
+
+    #[test]
+    fn invalid_data() {
+        let mut tilemap = TilemapData::new();
+        let color = Color::hsla(120.0, 0.5, 0.5, 1.0);
+        tilemap.set_tile(
+            IVec2::new(1, 2),
+            TileData {
+                atlas_index: 1,
+                color: Some(color),
+            },
+        );
+        let bytes = encode_tilemap(&tilemap);
+        let decoded = decode_tilemap(&bytes).unwrap();
+        assert_eq!(
+            decoded.get_tile(IVec2::new(1, 2)).unwrap().color,
+            Some(color.as_rgba())
+        );
+
+        // The same chunk twice.
+        let mut duplicated = bytes[..6].to_vec();
+        duplicated.extend(2u32.to_le_bytes());
+        duplicated.extend(&bytes[10..]);
+        duplicated.extend(&bytes[10..]);
+        assert!(matches!(
+            decode_tilemap(&duplicated),
+            Err(TilemapFormatError::InvalidData(_))
+        ));
+    }
+}
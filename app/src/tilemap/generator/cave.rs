@@ -0,0 +1,104 @@
+use crate::tilemap::autotile::{TerrainMap, TerrainType};
+use crate::tilemap::generator::{TilemapGenerator, TilemapGeneratorConfig};
+use bevy::prelude::*;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+/// Caves grown by a cellular automaton from random noise, floors are terrain 0 and walls 1.
+#[derive(Debug, Clone)]
+pub struct CaveTilemapGenerator {
+    /// Chance of a cell starting out as a wall.
+    pub fill_probability: f64,
+    pub iterations: u32,
+}
+
+impl Default for CaveTilemapGenerator {
+    fn default() -> Self {
+        CaveTilemapGenerator {
+            fill_probability: 0.45,
+            iterations: 5,
+        }
+    }
+}
+
+impl TilemapGenerator for CaveTilemapGenerator {
+    fn generate_terrain(&self, config: &TilemapGeneratorConfig) -> TerrainMap {
+        let size = config.size();
+        let index = |location: IVec2| {
+            let local = location - config.min;
+            (local.x + local.y * size.x) as usize
+        };
+
+        let mut rng = ChaCha8Rng::seed_from_u64(config.seed);
+        let mut walls: Vec<bool> = config
+            .locations()
+            .map(|_| rng.gen_bool(self.fill_probability))
+            .collect();
+        for _ in 0..self.iterations {
+            // Outside of the bounds counts as wall, which closes the caves off.
+            let is_wall = |location: IVec2| !config.contains(location) || walls[index(location)];
+            walls = config
+                .locations()
+                .map(|location| {
+                    let neighbours = (-1..=1)
+                        .flat_map(|y| (-1..=1).map(move |x| IVec2::new(x, y)))
+                        .filter(|offset| *offset != IVec2::ZERO && is_wall(location + *offset))
+                        .count();
+                    neighbours >= 5 || (neighbours == 4 && is_wall(location))
+                })
+                .collect();
+        }
+
+        let mut terrain = TerrainMap::new();
+        for location in config.locations() {
+            terrain.set(location, TerrainType(walls[index(location)] as u8));
+        }
+        terrain
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tilemap::generator::tests::render;
+
+    #[test]
+    fn seeded() {
+        let generator = CaveTilemapGenerator::default();
+        let config = TilemapGeneratorConfig::default().with_seed(7);
+        assert_eq!(generator.generate(&config), generator.generate(&config));
+        assert_ne!(
+            generator.generate(&config),
+            generator.generate(&config.clone().with_seed(8))
+        );
+    }
+
+    #[test]
+    fn snapshot() {
+        let config = TilemapGeneratorConfig::default()
+            .with_seed(1)
+            .with_bounds(IVec2::ZERO, IVec2::new(23, 11));
+        assert_eq!(
+            render(
+                &CaveTilemapGenerator::default().generate_terrain(&config),
+                &config
+            ),
+            [
+                "111111111111111111111111",
+                "111110000111111000111111",
+                "111100000011100000011111",
+                "111100000000000000011111",
+                "111110000000000000011111",
+                "111111000000000000001111",
+                "111111100000000000000001",
+                "111111100000000000000001",
+                "111111000000000000011001",
+                "111111000000000000111111",
+                "111111100000000001111111",
+                "111111111001111111111111",
+            ]
+            .map(|row| row.to_owned() + "\n")
+            .concat()
+        );
+    }
+}
@@ -1 +1,138 @@
-pub mod random;
+use crate::tilemap::autotile::{TerrainMap, TerrainType};
+use crate::tilemap::data::{TileData, TilemapData};
+use bevy::prelude::*;
+use bracket_noise::prelude::{FastNoise, FractalType, Interp, NoiseType};
+
+pub mod cave;
+pub mod noise;
+pub mod rooms;
+
+/// Generates terrain from a [`TilemapGeneratorConfig`], the same config always yields the same map.
+pub trait TilemapGenerator {
+    /// Terrain type of every cell within the config bounds.
+    fn generate_terrain(&self, config: &TilemapGeneratorConfig) -> TerrainMap;
+
+    /// Tiles using each terrain type as atlas index, use [`crate::tilemap::autotile::AutotileSet::apply`]
+    /// on the terrain instead to get transitions.
+    fn generate(&self, config: &TilemapGeneratorConfig) -> TilemapData {
+        let mut tilemap = TilemapData::new();
+        for (location, terrain) in self.generate_terrain(config).iter() {
+            tilemap.set_tile(location, TileData::new(terrain.0 as usize));
+        }
+        tilemap
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TilemapGeneratorConfig {
+    pub seed: u64,
+    /// Inclusive tile bounds of the generated map.
+    pub min: IVec2,
+    pub max: IVec2,
+    pub noise_type: NoiseType,
+    pub fractal_type: FractalType,
+    pub octaves: i32,
+    pub gain: f32,
+    pub lacunarity: f32,
+    pub frequency: f32,
+    /// Ascending noise values where the next terrain type starts.
+    pub thresholds: Vec<f32>,
+}
+
+impl Default for TilemapGeneratorConfig {
+    fn default() -> Self {
+        TilemapGeneratorConfig {
+            seed: 0,
+            min: IVec2::splat(-10),
+            max: IVec2::splat(10),
+            noise_type: NoiseType::SimplexFractal,
+            fractal_type: FractalType::Billow,
+            octaves: 5,
+            gain: 0.6,
+            lacunarity: 2.0,
+            frequency: 0.02,
+            thresholds: vec![0.2, 0.4, 0.6, 0.8],
+        }
+    }
+}
+
+impl TilemapGeneratorConfig {
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn with_bounds(mut self, min: IVec2, max: IVec2) -> Self {
+        self.min = min;
+        self.max = max;
+        self
+    }
+
+    pub fn size(&self) -> IVec2 {
+        (self.max - self.min + IVec2::ONE).max(IVec2::ZERO)
+    }
+
+    pub fn contains(&self, location: IVec2) -> bool {
+        location.cmpge(self.min).all() && location.cmple(self.max).all()
+    }
+
+    /// Every location within the bounds, row by row.
+    pub fn locations(&self) -> impl Iterator<Item = IVec2> {
+        let (min, max) = (self.min, self.max);
+        (min.y..=max.y).flat_map(move |y| (min.x..=max.x).map(move |x| IVec2::new(x, y)))
+    }
+
+    pub fn make_noise(&self) -> FastNoise {
+        let mut noise = FastNoise::seeded(self.seed);
+        noise.set_noise_type(self.noise_type);
+        noise.set_fractal_type(self.fractal_type);
+        noise.set_interp(Interp::Quintic);
+        noise.set_fractal_octaves(self.octaves);
+        noise.set_fractal_gain(self.gain);
+        noise.set_fractal_lacunarity(self.lacunarity);
+        noise.set_frequency(self.frequency);
+        noise
+    }
+
+    /// Terrain type of a noise value, the number of thresholds it reaches.
+    pub fn get_terrain(&self, value: f32) -> TerrainType {
+        TerrainType(
+            self.thresholds
+                .iter()
+                .take_while(|threshold| value >= **threshold)
+                .count() as u8,
+        )
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Draws terrain types as digits, top row first.
+    pub fn render(terrain: &TerrainMap, config: &TilemapGeneratorConfig) -> String {
+        let mut output = String::new();
+        for y in (config.min.y..=config.max.y).rev() {
+            for x in config.min.x..=config.max.x {
+                output.push(match terrain.get(IVec2::new(x, y)) {
+                    Some(terrain) => char::from_digit(terrain.0 as u32, 36).unwrap_or('?'),
+                    None => ' ',
+                });
+            }
+            output.push('\n');
+        }
+        output
+    }
+
+    #[test]
+    fn config() {
+        let config = TilemapGeneratorConfig::default();
+        assert_eq!(config.size(), IVec2::new(21, 21));
+        assert_eq!(config.locations().count(), 21 * 21);
+        assert!(config.contains(IVec2::new(10, -10)));
+        assert!(!config.contains(IVec2::new(11, 0)));
+        assert_eq!(config.get_terrain(0.0), TerrainType(0));
+        assert_eq!(config.get_terrain(0.2), TerrainType(1));
+        assert_eq!(config.get_terrain(0.95), TerrainType(4));
+    }
+}
@@ -0,0 +1,64 @@
+use crate::tilemap::autotile::TerrainMap;
+use crate::tilemap::generator::{TilemapGenerator, TilemapGeneratorConfig};
+
+/// Terrain from the magnitude of fractal noise split by the config thresholds.
+#[derive(Debug, Clone, Default)]
+pub struct NoiseTilemapGenerator;
+
+impl TilemapGenerator for NoiseTilemapGenerator {
+    fn generate_terrain(&self, config: &TilemapGeneratorConfig) -> TerrainMap {
+        let noise = config.make_noise();
+        let mut terrain = TerrainMap::new();
+        for location in config.locations() {
+            let n = noise.get_noise(location.x as f32, location.y as f32);
+            terrain.set(location, config.get_terrain(n.abs()));
+        }
+        terrain
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tilemap::generator::tests::render;
+    use bevy::prelude::*;
+
+    #[test]
+    fn seeded() {
+        let config = TilemapGeneratorConfig::default().with_seed(42);
+        assert_eq!(
+            NoiseTilemapGenerator.generate(&config),
+            NoiseTilemapGenerator.generate(&config)
+        );
+        assert_ne!(
+            NoiseTilemapGenerator.generate(&config),
+            NoiseTilemapGenerator.generate(&config.clone().with_seed(43))
+        );
+    }
+
+    #[test]
+    fn snapshot() {
+        let config = TilemapGeneratorConfig::default()
+            .with_seed(1)
+            .with_bounds(IVec2::ZERO, IVec2::new(23, 11));
+        assert_eq!(
+            render(&NoiseTilemapGenerator.generate_terrain(&config), &config),
+            [
+                "000000110123222111111122",
+                "000000001112221011111123",
+                "000000011111111100011123",
+                "000000010121110000011223",
+                "000000011111100000112113",
+                "000000011111100000111112",
+                "000000011121110011111111",
+                "000000011123221111000111",
+                "011000111223322111000011",
+                "222101011222332211000011",
+                "332211211122232111000011",
+                "432211122223222221001011",
+            ]
+            .map(|row| row.to_owned() + "\n")
+            .concat()
+        );
+    }
+}
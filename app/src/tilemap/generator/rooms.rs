@@ -0,0 +1,182 @@
+use crate::tilemap::autotile::{TerrainMap, TerrainType};
+use crate::tilemap::generator::{TilemapGenerator, TilemapGeneratorConfig};
+use bevy::prelude::*;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+const FLOOR: TerrainType = TerrainType(0);
+const WALL: TerrainType = TerrainType(1);
+
+/// Rooms in a binary space partition of the bounds joined by corridors, floors are terrain 0 and
+/// walls 1.
+#[derive(Debug, Clone)]
+pub struct RoomsTilemapGenerator {
+    /// Areas are only split while both halves are at least this large, at least 1.
+    pub min_area_size: i32,
+    pub min_room_size: i32,
+}
+
+impl Default for RoomsTilemapGenerator {
+    fn default() -> Self {
+        RoomsTilemapGenerator {
+            min_area_size: 8,
+            min_room_size: 3,
+        }
+    }
+}
+
+impl TilemapGenerator for RoomsTilemapGenerator {
+    fn generate_terrain(&self, config: &TilemapGeneratorConfig) -> TerrainMap {
+        let mut terrain = TerrainMap::new();
+        for location in config.locations() {
+            terrain.set(location, WALL);
+        }
+        if config.size().cmpgt(IVec2::ZERO).all() {
+            let mut rng = ChaCha8Rng::seed_from_u64(config.seed);
+            self.build(&mut rng, config.min, config.max, &mut terrain);
+        }
+        terrain
+    }
+}
+
+impl RoomsTilemapGenerator {
+    /// Carves the rooms of the inclusive area and returns a location inside one of them.
+    fn build(
+        &self,
+        rng: &mut ChaCha8Rng,
+        min: IVec2,
+        max: IVec2,
+        terrain: &mut TerrainMap,
+    ) -> IVec2 {
+        let min_area_size = self.min_area_size.max(1);
+        let size = max - min + IVec2::ONE;
+        let can_split = size.cmpge(IVec2::splat(min_area_size * 2));
+        let split_x = match (can_split.x, can_split.y) {
+            (false, false) => return self.carve_room(rng, min, max, terrain),
+            (true, false) => true,
+            (false, true) => false,
+            (true, true) => rng.gen_bool(0.5),
+        };
+
+        let (first, second) = if split_x {
+            let x = rng.gen_range(min.x + min_area_size..=max.x + 1 - min_area_size);
+            (
+                self.build(rng, min, IVec2::new(x - 1, max.y), terrain),
+                self.build(rng, IVec2::new(x, min.y), max, terrain),
+            )
+        } else {
+            let y = rng.gen_range(min.y + min_area_size..=max.y + 1 - min_area_size);
+            (
+                self.build(rng, min, IVec2::new(max.x, y - 1), terrain),
+                self.build(rng, IVec2::new(min.x, y), max, terrain),
+            )
+        };
+
+        let corner = if rng.gen_bool(0.5) {
+            IVec2::new(second.x, first.y)
+        } else {
+            IVec2::new(first.x, second.y)
+        };
+        carve_line(first, corner, terrain);
+        carve_line(corner, second, terrain);
+        if rng.gen_bool(0.5) {
+            first
+        } else {
+            second
+        }
+    }
+
+    fn carve_room(
+        &self,
+        rng: &mut ChaCha8Rng,
+        min: IVec2,
+        max: IVec2,
+        terrain: &mut TerrainMap,
+    ) -> IVec2 {
+        // Keep a wall between rooms of neighbouring areas when there is space for it.
+        let inner_min = (min + IVec2::ONE).min(max);
+        let inner_max = (max - IVec2::ONE).max(inner_min);
+        let inner_size = inner_max - inner_min + IVec2::ONE;
+        let room_size = IVec2::new(
+            rng.gen_range(self.min_room_size.clamp(1, inner_size.x)..=inner_size.x),
+            rng.gen_range(self.min_room_size.clamp(1, inner_size.y)..=inner_size.y),
+        );
+        let room_min = IVec2::new(
+            rng.gen_range(inner_min.x..=inner_max.x + 1 - room_size.x),
+            rng.gen_range(inner_min.y..=inner_max.y + 1 - room_size.y),
+        );
+        for y in 0..room_size.y {
+            for x in 0..room_size.x {
+                terrain.set(room_min + IVec2::new(x, y), FLOOR);
+            }
+        }
+        room_min + room_size / 2
+    }
+}
+
+/// Carves a horizontal or vertical corridor including both ends.
+fn carve_line(from: IVec2, to: IVec2, terrain: &mut TerrainMap) {
+    let step = (to - from).signum();
+    let mut location = from;
+    terrain.set(location, FLOOR);
+    while location != to {
+        location += step;
+        terrain.set(location, FLOOR);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tilemap::generator::tests::render;
+
+    #[test]
+    fn seeded() {
+        let generator = RoomsTilemapGenerator::default();
+        let config = TilemapGeneratorConfig::default().with_seed(3);
+        assert_eq!(generator.generate(&config), generator.generate(&config));
+        assert_ne!(
+            generator.generate(&config),
+            generator.generate(&config.clone().with_seed(4))
+        );
+
+        // Areas too small to split don't recurse forever.
+        let generator = RoomsTilemapGenerator {
+            min_area_size: 0,
+            min_room_size: 0,
+        };
+        let terrain = generator.generate_terrain(&config);
+        assert!(config
+            .locations()
+            .any(|location| terrain.get(location) == Some(FLOOR)));
+    }
+
+    #[test]
+    fn snapshot() {
+        let config = TilemapGeneratorConfig::default()
+            .with_seed(1)
+            .with_bounds(IVec2::ZERO, IVec2::new(23, 11));
+        assert_eq!(
+            render(
+                &RoomsTilemapGenerator::default().generate_terrain(&config),
+                &config
+            ),
+            [
+                "111111111111111111111111",
+                "111111111111110000001111",
+                "111111000000000000001111",
+                "111111000000110000001111",
+                "111111000000110000001111",
+                "111111000000111111111111",
+                "111111000000111111111111",
+                "111111111111111111111111",
+                "111111111111111111111111",
+                "111111111111111111111111",
+                "111111111111111111111111",
+                "111111111111111111111111",
+            ]
+            .map(|row| row.to_owned() + "\n")
+            .concat()
+        );
+    }
+}
@@ -1,7 +1,6 @@
+use crate::tilemap::chunk::ATTRIBUTE_ATLAS_INDEX;
 use bevy::render::mesh::MeshVertexBufferLayout;
-use bevy::render::render_resource::{
-    BindGroupLayout, RenderPipelineDescriptor, SpecializedMeshPipelineError, VertexBufferLayout,
-};
+use bevy::render::render_resource::{RenderPipelineDescriptor, SpecializedMeshPipelineError};
 use bevy::sprite::{Material2d, Material2dKey};
 use bevy::{
     prelude::*,
@@ -18,10 +17,44 @@ pub struct TilemapMaterial {
     #[texture(2)]
     #[sampler(3)]
     pub lighting_texture: Option<Handle<Image>>,
+    /// Tint multiplied with the tile colors, its alpha is the layer opacity.
+    #[uniform(4)]
+    pub color: Color,
+    /// Lookup of the atlas rect drawn for each atlas index, see [`crate::tilemap::animation`].
+    #[texture(5, sample_type = "float", filterable = false)]
+    pub frame_texture: Handle<Image>,
+}
+
+impl TilemapMaterial {
+    pub fn new(color_texture: Handle<Image>) -> Self {
+        TilemapMaterial {
+            color_texture,
+            lighting_texture: None,
+            color: Color::WHITE,
+            frame_texture: Default::default(),
+        }
+    }
 }
 
 impl Material2d for TilemapMaterial {
+    fn vertex_shader() -> ShaderRef {
+        "shaders/tilemap.wgsl".into()
+    }
+
     fn fragment_shader() -> ShaderRef {
         "shaders/tilemap.wgsl".into()
     }
+
+    fn specialize(
+        descriptor: &mut RenderPipelineDescriptor,
+        layout: &MeshVertexBufferLayout,
+        _key: Material2dKey<Self>,
+    ) -> Result<(), SpecializedMeshPipelineError> {
+        descriptor.vertex.buffers = vec![layout.get_layout(&[
+            Mesh::ATTRIBUTE_POSITION.at_shader_location(0),
+            Mesh::ATTRIBUTE_UV_0.at_shader_location(1),
+            ATTRIBUTE_ATLAS_INDEX.at_shader_location(2),
+        ])?];
+        Ok(())
+    }
 }
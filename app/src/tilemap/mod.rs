@@ -1,7 +1,15 @@
+pub mod animation;
+pub mod autotile;
 pub mod bundle;
+pub mod chunk;
 pub mod data;
+pub mod format;
 pub mod generator;
 pub mod material;
+pub mod picking;
 pub mod plugin;
+pub mod properties;
+pub mod streaming;
+pub mod tiled;
 
 pub const TILEMAP_CHUNK_SIZE: u32 = 32;
@@ -0,0 +1,182 @@
+use crate::camera::MainCameraComponent;
+use crate::input_manager::action::InputAction;
+use crate::input_manager::action_state::InputActionState;
+use crate::input_manager::mouse::MousePosition;
+use crate::tilemap::bundle::TilemapComponent;
+use crate::tilemap::data::{ChunkData, TilemapData};
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+
+/// A tile of a tilemap along with its chunk and its location in the chunk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TileLocation {
+    pub tile: IVec2,
+    pub chunk: IVec2,
+    pub chunk_tile: UVec2,
+}
+
+/// The tile under the cursor, if any.
+#[derive(Resource, Debug, Default, Clone)]
+pub struct HoveredTile(pub Option<TilePick>);
+
+/// Sent when the `Select` action is pressed over a tile.
+#[derive(Debug, Clone)]
+pub struct TileClicked(pub TilePick);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TilePick {
+    pub tilemap: Entity,
+    pub world_position: Vec2,
+    pub location: TileLocation,
+}
+
+impl TileLocation {
+    pub fn new(tile: IVec2) -> Self {
+        TileLocation {
+            tile,
+            chunk: TilemapData::tilemap_to_chunk(tile),
+            chunk_tile: ChunkData::tilemap_to_chunk_tile(tile),
+        }
+    }
+}
+
+/// World position of a point of the viewport, with the origin at the bottom left like
+/// [`MousePosition`].
+pub fn viewport_to_world(
+    projection: &OrthographicProjection,
+    camera_transform: &GlobalTransform,
+    viewport_size: Vec2,
+    viewport_position: Vec2,
+) -> Vec2 {
+    let area = projection.area;
+    let local = area.min + viewport_position / viewport_size * area.size();
+    camera_transform
+        .transform_point(local.extend(0.0))
+        .truncate()
+}
+
+/// Tile of a tilemap containing a world position, 1 unit being 1 tile in tilemap space.
+pub fn world_to_tile(tilemap_transform: &GlobalTransform, world_position: Vec2) -> IVec2 {
+    tilemap_transform
+        .affine()
+        .inverse()
+        .transform_point3(world_position.extend(0.0))
+        .truncate()
+        .floor()
+        .as_ivec2()
+}
+
+/// World position of the center of a tile.
+pub fn tile_to_world(tilemap_transform: &GlobalTransform, tile: IVec2) -> Vec2 {
+    tilemap_transform
+        .transform_point((tile.as_vec2() + Vec2::splat(0.5)).extend(0.0))
+        .truncate()
+}
+
+/// Picks the top tilemap having a tile under the cursor.
+pub fn tile_picking_system(
+    mouse_position: Res<MousePosition>,
+    input_action_state: Res<InputActionState>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    camera_query: Query<(&OrthographicProjection, &GlobalTransform), With<MainCameraComponent>>,
+    tilemap_query: Query<(Entity, &GlobalTransform, &TilemapComponent)>,
+    mut hovered_tile: ResMut<HoveredTile>,
+    mut tile_clicked_events: EventWriter<TileClicked>,
+) {
+    let (window, (projection, camera_transform)) =
+        match (window_query.get_single(), camera_query.get_single()) {
+            (Ok(window), Ok(camera)) => (window, camera),
+            _ => {
+                hovered_tile.0 = None;
+                return;
+            }
+        };
+    let world_position = viewport_to_world(
+        projection,
+        camera_transform,
+        Vec2::new(window.width(), window.height()),
+        mouse_position.0,
+    );
+
+    let pick = tilemap_query
+        .iter()
+        .filter_map(|(entity, transform, tilemap)| {
+            let location = TileLocation::new(world_to_tile(transform, world_position));
+            tilemap
+                .layers()
+                .iter()
+                .any(|layer| layer.data.get_chunk(location.chunk).is_some())
+                .then_some((transform.translation().z, entity, location))
+        })
+        .max_by(|a, b| a.0.total_cmp(&b.0))
+        .map(|(_, tilemap, location)| TilePick {
+            tilemap,
+            world_position,
+            location,
+        });
+
+    if hovered_tile.0 != pick {
+        hovered_tile.0 = pick;
+    }
+    if let Some(pick) = pick {
+        if input_action_state.just_pressed(InputAction::Select) {
+            tile_clicked_events.send(TileClicked(pick));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::MainCameraBundle;
+    use bevy::render::camera::CameraProjection;
+
+    #[test]
+    fn conversions() {
+        let location = TileLocation::new(IVec2::new(-1, -33));
+        assert_eq!(location.chunk, IVec2::new(-1, -2));
+        assert_eq!(location.chunk_tile, UVec2::new(31, 31));
+        let location = TileLocation::new(IVec2::new(-32, 32));
+        assert_eq!(location.chunk, IVec2::new(-1, 1));
+        assert_eq!(location.chunk_tile, UVec2::new(0, 0));
+
+        let transform = GlobalTransform::from_xyz(-10.0, 4.0, 0.0);
+        assert_eq!(
+            world_to_tile(&transform, Vec2::new(-10.5, 3.9)),
+            IVec2::new(-1, -1)
+        );
+        assert_eq!(
+            world_to_tile(&transform, Vec2::new(-10.0, 4.0)),
+            IVec2::new(0, 0)
+        );
+        let tile = IVec2::new(-7, 3);
+        assert_eq!(
+            world_to_tile(&transform, tile_to_world(&transform, tile)),
+            tile
+        );
+    }
+
+    #[test]
+    fn viewport() {
+        let mut camera = MainCameraBundle::new().camera2d;
+        camera.projection.update(800.0, 600.0);
+        let transform = GlobalTransform::from_xyz(5.0, -5.0, 999.9);
+        let size = Vec2::new(800.0, 600.0);
+        assert_eq!(
+            viewport_to_world(&camera.projection, &transform, size, size / 2.0),
+            Vec2::new(5.0, -5.0)
+        );
+        // 32 pixels per unit, the origin at the bottom left.
+        assert_eq!(
+            viewport_to_world(&camera.projection, &transform, size, Vec2::ZERO),
+            Vec2::new(5.0 - 12.5, -5.0 - 9.375)
+        );
+        assert!(viewport_to_world(
+            &camera.projection,
+            &transform,
+            size,
+            Vec2::new(432.0, 268.0)
+        )
+        .abs_diff_eq(Vec2::new(6.0, -6.0), 1e-4));
+    }
+}
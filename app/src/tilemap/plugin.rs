@@ -1,4 +1,12 @@
+use crate::tilemap::animation::tile_animation_system;
+use crate::tilemap::autotile::loader::AutotileSetLoader;
+use crate::tilemap::autotile::AutotileSet;
+use crate::tilemap::chunk::{tilemap_chunk_system, tilemap_layer_system};
 use crate::tilemap::material::TilemapMaterial;
+use crate::tilemap::picking::{tile_picking_system, HoveredTile, TileClicked};
+use crate::tilemap::streaming::tilemap_streaming_system;
+use crate::tilemap::tiled::loader::{TiledMapLoader, TiledTilesetLoader};
+use crate::tilemap::tiled::{TiledMap, TiledTileset};
 use bevy::prelude::*;
 use bevy::sprite::Material2dPlugin;
 
@@ -6,6 +14,25 @@ pub struct TilemapPlugin;
 
 impl Plugin for TilemapPlugin {
     fn build(&self, app: &mut App) {
-        app.add_plugin(Material2dPlugin::<TilemapMaterial>::default());
+        app.add_plugin(Material2dPlugin::<TilemapMaterial>::default())
+            .add_asset::<TiledMap>()
+            .add_asset::<TiledTileset>()
+            .init_asset_loader::<TiledMapLoader>()
+            .init_asset_loader::<TiledTilesetLoader>()
+            .add_asset::<AutotileSet>()
+            .init_asset_loader::<AutotileSetLoader>()
+            .init_resource::<HoveredTile>()
+            .add_event::<TileClicked>()
+            .add_system(tilemap_streaming_system)
+            .add_system(tile_picking_system)
+            .add_systems(
+                (
+                    tilemap_layer_system,
+                    tilemap_chunk_system,
+                    tile_animation_system,
+                )
+                    .chain()
+                    .in_base_set(CoreSet::PostUpdate),
+            );
     }
 }
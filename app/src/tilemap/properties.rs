@@ -0,0 +1,310 @@
+use crate::tilemap::data::ChunkData;
+use crate::tilemap::TILEMAP_CHUNK_SIZE;
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use std::sync::{Mutex, PoisonError};
+
+bitflags::bitflags! {
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct TileFlags: u8 {
+        const WALKABLE           = (1 << 0);
+        const BUILDABLE          = (1 << 1);
+        const BLOCKS_LIGHT       = (1 << 2);
+        const BLOCKS_PROJECTILES = (1 << 3);
+    }
+}
+
+/// Gameplay properties shared by every tile with the same atlas index.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TileProperties {
+    pub flags: TileFlags,
+    /// Cost of moving onto the tile, relative to an open floor.
+    pub movement_cost: f32,
+}
+
+/// Properties of the tiles of a tileset, indexed by atlas index.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TilesetProperties {
+    /// Properties of tiles without their own.
+    pub default: TileProperties,
+    tiles: Vec<TileProperties>,
+}
+
+const CHUNK_BITSET_WORDS: usize =
+    (TILEMAP_CHUNK_SIZE as usize * TILEMAP_CHUNK_SIZE as usize + 63) / 64;
+
+/// One bit per tile of a chunk, in the order of [`ChunkData::tiles`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ChunkBitset([u64; CHUNK_BITSET_WORDS]);
+
+/// A [`ChunkBitset`] for each of the [`TileFlags`] bits of a chunk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkFlags([ChunkBitset; u8::BITS as usize]);
+
+/// Flags of the chunks of a tilemap, computed when queried and dropped when the chunks get dirty.
+#[derive(Debug, Default)]
+pub(crate) struct ChunkFlagsCache(Mutex<HashMap<IVec2, ChunkFlags>>);
+
+impl Default for TileProperties {
+    fn default() -> Self {
+        TileProperties {
+            flags: TileFlags::WALKABLE | TileFlags::BUILDABLE,
+            movement_cost: 1.0,
+        }
+    }
+}
+
+impl TileProperties {
+    pub fn is_walkable(&self) -> bool {
+        self.flags.contains(TileFlags::WALKABLE)
+    }
+
+    pub fn is_buildable(&self) -> bool {
+        self.flags.contains(TileFlags::BUILDABLE)
+    }
+
+    pub fn blocks_light(&self) -> bool {
+        self.flags.contains(TileFlags::BLOCKS_LIGHT)
+    }
+
+    pub fn blocks_projectiles(&self) -> bool {
+        self.flags.contains(TileFlags::BLOCKS_PROJECTILES)
+    }
+}
+
+impl TilesetProperties {
+    pub fn new(default: TileProperties) -> Self {
+        TilesetProperties {
+            default,
+            tiles: Vec::new(),
+        }
+    }
+
+    pub fn get(&self, atlas_index: usize) -> &TileProperties {
+        self.tiles.get(atlas_index).unwrap_or(&self.default)
+    }
+
+    pub fn set(&mut self, atlas_index: usize, properties: TileProperties) {
+        if atlas_index >= self.tiles.len() {
+            self.tiles.resize(atlas_index + 1, self.default);
+        }
+        self.tiles[atlas_index] = properties;
+    }
+
+    /// Bits of the chunk tiles having all of `flags`.
+    pub fn get_chunk_flags(&self, chunk: &ChunkData, flags: TileFlags) -> ChunkBitset {
+        let mut bitset = ChunkBitset::default();
+        for (i, tile) in chunk.tiles.iter().enumerate() {
+            if !tile.is_empty() && self.get(tile.atlas_index).flags.contains(flags) {
+                bitset.0[i / 64] |= 1 << (i % 64);
+            }
+        }
+        bitset
+    }
+
+    /// Bits of every flag of the chunk tiles, in a single pass.
+    pub fn get_chunk_flags_all(&self, chunk: &ChunkData) -> ChunkFlags {
+        let mut flags = ChunkFlags([ChunkBitset::default(); u8::BITS as usize]);
+        for (i, tile) in chunk.tiles.iter().enumerate() {
+            if tile.is_empty() {
+                continue;
+            }
+            let tile_flags = self.get(tile.atlas_index).flags.bits();
+            for (bit, bitset) in flags.0.iter_mut().enumerate() {
+                if tile_flags & (1 << bit) != 0 {
+                    bitset.0[i / 64] |= 1 << (i % 64);
+                }
+            }
+        }
+        flags
+    }
+}
+
+impl ChunkFlags {
+    /// Bits of the tiles having all of `flags`.
+    pub fn get(&self, flags: TileFlags) -> ChunkBitset {
+        self.0
+            .iter()
+            .enumerate()
+            .filter(|(bit, _)| flags.bits() & (1 << bit) != 0)
+            .fold(
+                ChunkBitset([u64::MAX; CHUNK_BITSET_WORDS]),
+                |bitset, (_, flag_bitset)| bitset & *flag_bitset,
+            )
+    }
+}
+
+impl ChunkFlagsCache {
+    pub fn get(
+        &self,
+        location: IVec2,
+        flags: TileFlags,
+        make: impl FnOnce() -> ChunkFlags,
+    ) -> ChunkBitset {
+        let mut cache = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        cache.entry(location).or_insert_with(make).get(flags)
+    }
+
+    pub fn invalidate(&mut self, location: IVec2) {
+        self.map_mut().remove(&location);
+    }
+
+    pub fn clear(&mut self) {
+        self.map_mut().clear();
+    }
+
+    fn map_mut(&mut self) -> &mut HashMap<IVec2, ChunkFlags> {
+        self.0.get_mut().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Clone for ChunkFlagsCache {
+    fn clone(&self) -> Self {
+        let cache = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        ChunkFlagsCache(Mutex::new(cache.clone()))
+    }
+}
+
+impl ChunkBitset {
+    /// Bits of the tiles of a chunk that aren't empty.
+    pub fn tiles(chunk: &ChunkData) -> Self {
+        let mut bitset = ChunkBitset::default();
+        for (i, tile) in chunk.tiles.iter().enumerate() {
+            if !tile.is_empty() {
+                bitset.0[i / 64] |= 1 << (i % 64);
+            }
+        }
+        bitset
+    }
+
+    pub fn get(&self, location: UVec2) -> bool {
+        let i = ChunkData::tile_index(location);
+        self.0[i / 64] & (1 << (i % 64)) != 0
+    }
+
+    pub fn set(&mut self, location: UVec2, value: bool) {
+        let i = ChunkData::tile_index(location);
+        if value {
+            self.0[i / 64] |= 1 << (i % 64);
+        } else {
+            self.0[i / 64] &= !(1 << (i % 64));
+        }
+    }
+
+    pub fn count(&self) -> u32 {
+        self.0.iter().map(|word| word.count_ones()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.iter().all(|word| *word == 0)
+    }
+}
+
+impl std::ops::BitAnd for ChunkBitset {
+    type Output = ChunkBitset;
+
+    fn bitand(mut self, rhs: ChunkBitset) -> ChunkBitset {
+        for (word, rhs_word) in self.0.iter_mut().zip(rhs.0) {
+            *word &= rhs_word;
+        }
+        self
+    }
+}
+
+impl std::ops::BitOr for ChunkBitset {
+    type Output = ChunkBitset;
+
+    fn bitor(mut self, rhs: ChunkBitset) -> ChunkBitset {
+        for (word, rhs_word) in self.0.iter_mut().zip(rhs.0) {
+            *word |= rhs_word;
+        }
+        self
+    }
+}
+
+impl std::ops::Not for ChunkBitset {
+    type Output = ChunkBitset;
+
+    fn not(mut self) -> ChunkBitset {
+        for word in self.0.iter_mut() {
+            *word = !*word;
+        }
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tilemap::data::{TileData, TilemapData};
+
+    #[test]
+    fn chunk_flags() {
+        let mut properties = TilesetProperties::default();
+        properties.set(
+            2,
+            TileProperties {
+                flags: TileFlags::BLOCKS_LIGHT | TileFlags::BLOCKS_PROJECTILES,
+                movement_cost: 1.0,
+            },
+        );
+        assert!(properties.get(0).is_walkable());
+        assert!(!properties.get(2).is_walkable());
+        assert!(properties.get(5).is_buildable());
+
+        let mut tilemap = TilemapData::new().with_properties(properties);
+        tilemap.set_tile(IVec2::new(0, 0), TileData::new(2));
+        tilemap.set_tile(IVec2::new(-1, 3), TileData::new(2));
+        assert!(tilemap
+            .get_tile_properties(IVec2::new(-1, 3))
+            .unwrap()
+            .blocks_light());
+        assert!(tilemap.get_tile_properties(IVec2::new(100, 0)).is_none());
+
+        let walkable = tilemap
+            .get_chunk_flags(IVec2::ZERO, TileFlags::WALKABLE)
+            .unwrap();
+        let size = TILEMAP_CHUNK_SIZE * TILEMAP_CHUNK_SIZE;
+        assert_eq!(walkable.count(), size - 1);
+        assert!(!walkable.get(UVec2::new(0, 0)));
+        assert!(walkable.get(UVec2::new(1, 0)));
+        let blocking = tilemap
+            .get_chunk_flags(IVec2::new(-1, 0), TileFlags::BLOCKS_LIGHT)
+            .unwrap();
+        assert_eq!(blocking.count(), 1);
+        assert!(blocking.get(UVec2::new(31, 3)));
+        assert!(tilemap
+            .get_chunk_flags(IVec2::new(5, 5), TileFlags::WALKABLE)
+            .is_none());
+
+        // Cached bitsets follow the edits of the chunks.
+        tilemap.set_tile(IVec2::new(0, 0), TileData::new(0));
+        tilemap.take_dirty_chunks();
+        let walkable = tilemap
+            .get_chunk_flags(IVec2::ZERO, TileFlags::WALKABLE)
+            .unwrap();
+        assert_eq!(walkable.count(), size);
+        tilemap
+            .get_chunk_mut(IVec2::ZERO)
+            .unwrap()
+            .set_tile(UVec2::new(4, 4), TileData::new(2));
+        let walkable = tilemap
+            .get_chunk_flags(IVec2::ZERO, TileFlags::WALKABLE)
+            .unwrap();
+        assert!(!walkable.get(UVec2::new(4, 4)));
+        assert_eq!(
+            tilemap
+                .get_chunk_flags(IVec2::ZERO, TileFlags::WALKABLE | TileFlags::BUILDABLE)
+                .unwrap(),
+            walkable
+        );
+        tilemap.set_properties(TilesetProperties::default());
+        assert_eq!(
+            tilemap
+                .get_chunk_flags(IVec2::ZERO, TileFlags::WALKABLE)
+                .unwrap()
+                .count(),
+            size
+        );
+    }
+}
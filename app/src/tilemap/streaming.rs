@@ -0,0 +1,313 @@
+use crate::camera::MainCameraComponent;
+use crate::tilemap::bundle::TilemapComponent;
+use crate::tilemap::data::{ChunkData, TilemapData};
+use crate::tilemap::format::{decode_chunk, encode_chunk};
+use crate::tilemap::generator::{TilemapGenerator, TilemapGeneratorConfig};
+use crate::tilemap::TILEMAP_CHUNK_SIZE;
+use bevy::prelude::*;
+use bevy::tasks::{AsyncComputeTaskPool, Task};
+use bevy::utils::HashMap;
+use futures_lite::future;
+use std::sync::Arc;
+
+/// Generates the chunks of a tilemap layer around the main camera and unloads the far ones.
+///
+/// Chunks are generated one at a time with the config bounds set to the chunk, so the generator
+/// should tile seamlessly like [`crate::tilemap::generator::noise::NoiseTilemapGenerator`].
+#[derive(Component)]
+pub struct TilemapStreamingComponent {
+    pub layer: usize,
+    pub generator: Arc<dyn TilemapGenerator + Send + Sync>,
+    pub config: TilemapGeneratorConfig,
+    /// Chunks up to this many chunks away from the camera chunk are loaded.
+    pub load_radius: i32,
+    /// Chunks further away than this are unloaded, larger than `load_radius` so chunks on the
+    /// border don't get reloaded over and over.
+    pub unload_radius: i32,
+    /// Keeps unloaded chunks in memory, encoded, so they come back with their edits instead of
+    /// regenerated.
+    pub persist: bool,
+    persisted_chunks: HashMap<IVec2, Vec<u8>>,
+    tasks: HashMap<IVec2, Task<ChunkData>>,
+}
+
+impl TilemapStreamingComponent {
+    pub fn new(
+        layer: usize,
+        generator: impl TilemapGenerator + Send + Sync + 'static,
+        config: TilemapGeneratorConfig,
+    ) -> Self {
+        TilemapStreamingComponent {
+            layer,
+            generator: Arc::new(generator),
+            config,
+            load_radius: 2,
+            unload_radius: 4,
+            persist: true,
+            persisted_chunks: Default::default(),
+            tasks: Default::default(),
+        }
+    }
+
+    pub fn with_radius(mut self, load_radius: i32, unload_radius: i32) -> Self {
+        self.load_radius = load_radius;
+        self.unload_radius = unload_radius.max(load_radius);
+        self
+    }
+
+    pub fn with_persist(mut self, persist: bool) -> Self {
+        self.persist = persist;
+        self
+    }
+
+    pub fn persisted_chunks(&self) -> &HashMap<IVec2, Vec<u8>> {
+        &self.persisted_chunks
+    }
+
+    pub fn is_generating(&self, location: IVec2) -> bool {
+        self.tasks.contains_key(&location)
+    }
+
+    fn spawn_task(&mut self, location: IVec2) {
+        let min = location * TILEMAP_CHUNK_SIZE as i32;
+        let max = min + IVec2::splat(TILEMAP_CHUNK_SIZE as i32 - 1);
+        let generator = self.generator.clone();
+        let config = self.config.clone().with_bounds(min, max);
+        let task = AsyncComputeTaskPool::get().spawn(async move {
+            generator
+                .generate(&config)
+                .remove_chunk(location)
+                .unwrap_or_else(ChunkData::new)
+        });
+        self.tasks.insert(location, task);
+    }
+}
+
+/// Chessboard distance between two chunk locations.
+fn chunk_distance(a: IVec2, b: IVec2) -> i32 {
+    (a - b).abs().max_element()
+}
+
+pub fn tilemap_streaming_system(
+    camera_query: Query<&GlobalTransform, With<MainCameraComponent>>,
+    mut tilemap_query: Query<(
+        &GlobalTransform,
+        &mut TilemapComponent,
+        &mut TilemapStreamingComponent,
+    )>,
+) {
+    let camera_position = match camera_query.get_single() {
+        Ok(transform) => transform.translation(),
+        Err(_) => return,
+    };
+
+    for (transform, mut tilemap, mut streaming) in tilemap_query.iter_mut() {
+        let streaming = streaming.as_mut();
+        let layer = match tilemap
+            .bypass_change_detection()
+            .layers_mut()
+            .get_mut(streaming.layer)
+        {
+            Some(layer) => layer,
+            None => continue,
+        };
+        let camera_tile = transform
+            .affine()
+            .inverse()
+            .transform_point3(camera_position)
+            .truncate()
+            .floor()
+            .as_ivec2();
+        let center = TilemapData::tilemap_to_chunk(camera_tile);
+
+        let finished: Vec<IVec2> = streaming
+            .tasks
+            .iter()
+            .filter(|(_, task)| task.is_finished())
+            .map(|(location, _)| *location)
+            .collect();
+        for location in finished {
+            let task = streaming.tasks.remove(&location).unwrap();
+            let chunk = future::block_on(task);
+            if layer.data.get_chunk(location).is_none() {
+                layer.data.insert_chunk(location, chunk);
+            }
+        }
+
+        // Dropping a task cancels it.
+        let unload_radius = streaming.unload_radius;
+        streaming
+            .tasks
+            .retain(|location, _| chunk_distance(*location, center) <= unload_radius);
+        let unloaded: Vec<IVec2> = layer
+            .data
+            .iter_chunks()
+            .map(|(location, _)| location)
+            .filter(|location| chunk_distance(*location, center) > unload_radius)
+            .collect();
+        for location in unloaded {
+            if let Some(chunk) = layer.data.remove_chunk(location) {
+                if streaming.persist {
+                    streaming
+                        .persisted_chunks
+                        .insert(location, encode_chunk(&chunk));
+                }
+            }
+        }
+
+        let load_radius = streaming.load_radius;
+        for y in -load_radius..=load_radius {
+            for x in -load_radius..=load_radius {
+                let location = center + IVec2::new(x, y);
+                if layer.data.get_chunk(location).is_some() || streaming.is_generating(location) {
+                    continue;
+                }
+                let persisted_chunk = streaming
+                    .persisted_chunks
+                    .remove(&location)
+                    .and_then(|bytes| decode_chunk(&bytes).ok());
+                match persisted_chunk {
+                    Some(chunk) => {
+                        layer.data.insert_chunk(location, chunk);
+                    }
+                    None => streaming.spawn_task(location),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::MainCameraBundle;
+    use crate::tilemap::bundle::{TilemapBundle, TilemapLayer};
+    use crate::tilemap::data::TileData;
+    use crate::tilemap::generator::noise::NoiseTilemapGenerator;
+    use std::time::Duration;
+
+    fn update_until_generated(app: &mut App, tilemap_entity: Entity) {
+        for _ in 0..1000 {
+            app.update();
+            let streaming = app
+                .world
+                .get::<TilemapStreamingComponent>(tilemap_entity)
+                .unwrap();
+            if streaming.tasks.is_empty() {
+                return;
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        panic!("chunks weren't generated");
+    }
+
+    fn loaded_chunks(app: &App, tilemap_entity: Entity) -> Vec<IVec2> {
+        let tilemap = app.world.get::<TilemapComponent>(tilemap_entity).unwrap();
+        tilemap.layers()[0]
+            .data
+            .iter_chunks()
+            .map(|(location, _)| location)
+            .collect()
+    }
+
+    #[test]
+    fn streaming() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_system(tilemap_streaming_system);
+
+        let chunk_size = TILEMAP_CHUNK_SIZE as f32;
+        let camera_entity = app
+            .world
+            .spawn(MainCameraBundle::new())
+            .insert(GlobalTransform::from_xyz(
+                0.5 * chunk_size,
+                0.5 * chunk_size,
+                0.0,
+            ))
+            .id();
+        let config = TilemapGeneratorConfig::default().with_seed(5);
+        let tilemap_entity = app
+            .world
+            .spawn(TilemapBundle::new([TilemapLayer::new(
+                "ground",
+                TilemapData::new(),
+                Default::default(),
+                Default::default(),
+            )]))
+            .insert(
+                TilemapStreamingComponent::new(0, NoiseTilemapGenerator, config.clone())
+                    .with_radius(1, 2),
+            )
+            .id();
+
+        update_until_generated(&mut app, tilemap_entity);
+        let mut chunks = loaded_chunks(&app, tilemap_entity);
+        chunks.sort_by_key(|location| (location.x, location.y));
+        assert_eq!(
+            chunks,
+            (-1..=1)
+                .flat_map(|x| (-1..=1).map(move |y| IVec2::new(x, y)))
+                .collect::<Vec<_>>()
+        );
+        // Streamed chunks match the map generated in one go.
+        let expected = NoiseTilemapGenerator.generate(&config.with_bounds(
+            IVec2::splat(-(TILEMAP_CHUNK_SIZE as i32)),
+            IVec2::splat(2 * TILEMAP_CHUNK_SIZE as i32 - 1),
+        ));
+        let tilemap = app.world.get::<TilemapComponent>(tilemap_entity).unwrap();
+        for (location, chunk) in tilemap.layers()[0].data.iter_chunks() {
+            assert_eq!(Some(chunk), expected.get_chunk(location));
+        }
+
+        let mut tilemap = app
+            .world
+            .get_mut::<TilemapComponent>(tilemap_entity)
+            .unwrap();
+        tilemap.layers_mut()[0]
+            .data
+            .set_tile(IVec2::new(-1, -1), TileData::new(100));
+
+        // Moving by two chunks keeps the chunks within the unload radius.
+        app.world
+            .entity_mut(camera_entity)
+            .insert(GlobalTransform::from_xyz(
+                2.5 * chunk_size,
+                0.5 * chunk_size,
+                0.0,
+            ));
+        update_until_generated(&mut app, tilemap_entity);
+        assert_eq!(loaded_chunks(&app, tilemap_entity).len(), 12);
+
+        app.world
+            .entity_mut(camera_entity)
+            .insert(GlobalTransform::from_xyz(
+                10.5 * chunk_size,
+                0.5 * chunk_size,
+                0.0,
+            ));
+        update_until_generated(&mut app, tilemap_entity);
+        assert_eq!(loaded_chunks(&app, tilemap_entity).len(), 9);
+        let streaming = app
+            .world
+            .get::<TilemapStreamingComponent>(tilemap_entity)
+            .unwrap();
+        assert!(streaming
+            .persisted_chunks()
+            .contains_key(&IVec2::new(-1, -1)));
+
+        app.world
+            .entity_mut(camera_entity)
+            .insert(GlobalTransform::from_xyz(0.0, 0.0, 0.0));
+        update_until_generated(&mut app, tilemap_entity);
+        let tilemap = app.world.get::<TilemapComponent>(tilemap_entity).unwrap();
+        assert_eq!(
+            tilemap.layers()[0]
+                .data
+                .get_tile(IVec2::new(-1, -1))
+                .unwrap()
+                .atlas_index,
+            100
+        );
+    }
+}
@@ -0,0 +1,172 @@
+use crate::tilemap::tiled::parse::{
+    parse_map, parse_tileset, TiledTilesetDefinition, TiledTilesetSource,
+};
+use crate::tilemap::tiled::{TiledMap, TiledMapTileset, TiledTileset};
+use bevy::asset::{AssetLoader, AssetPath, BoxedFuture, LoadContext, LoadedAsset};
+use bevy::prelude::*;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+
+#[derive(Default)]
+pub struct TiledMapLoader;
+
+#[derive(Default)]
+pub struct TiledTilesetLoader;
+
+impl AssetLoader for TiledMapLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let definition = parse_map(std::str::from_utf8(bytes)?)?;
+            let base_path = load_context
+                .path()
+                .parent()
+                .map(Path::to_path_buf)
+                .unwrap_or_default();
+
+            let mut dependencies = Vec::new();
+            let mut tilesets = Vec::with_capacity(definition.tilesets.len());
+            for (i, tileset) in definition.tilesets.into_iter().enumerate() {
+                let handle = match tileset.source {
+                    TiledTilesetSource::External(source) => {
+                        let path = resolve_path(&base_path, &source);
+                        dependencies.push(AssetPath::new(path.clone(), None));
+                        load_context.get_handle(AssetPath::new(path, None))
+                    }
+                    TiledTilesetSource::Embedded(tileset) => {
+                        let (tileset, image_path) = make_tileset(
+                            &tileset,
+                            &base_path,
+                            &format!("Tileset{}/Atlas", i),
+                            load_context,
+                        );
+                        load_context.set_labeled_asset(
+                            &format!("Tileset{}", i),
+                            LoadedAsset::new(tileset).with_dependency(image_path),
+                        )
+                    }
+                };
+                tilesets.push(TiledMapTileset {
+                    first_gid: tileset.first_gid,
+                    tileset: handle,
+                });
+            }
+
+            load_context.set_default_asset(
+                LoadedAsset::new(TiledMap {
+                    tile_size: definition.tile_size,
+                    tilesets,
+                    layers: definition.layers,
+                })
+                .with_dependencies(dependencies),
+            );
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["tmx"]
+    }
+}
+
+impl AssetLoader for TiledTilesetLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let definition = parse_tileset(std::str::from_utf8(bytes)?)?;
+            let base_path = load_context
+                .path()
+                .parent()
+                .map(Path::to_path_buf)
+                .unwrap_or_default();
+
+            let (tileset, image_path) =
+                make_tileset(&definition, &base_path, "Atlas", load_context);
+            load_context.set_default_asset(LoadedAsset::new(tileset).with_dependency(image_path));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["tsx"]
+    }
+}
+
+/// Registers the texture atlas of a tileset as a labeled asset.
+fn make_tileset(
+    definition: &TiledTilesetDefinition,
+    base_path: &Path,
+    atlas_label: &str,
+    load_context: &mut LoadContext,
+) -> (TiledTileset, AssetPath<'static>) {
+    let image_path = AssetPath::new(resolve_path(base_path, &definition.image_source), None);
+    let image: Handle<Image> = load_context.get_handle(image_path.clone());
+
+    let columns = definition.columns.max(1);
+    let rows = (definition.tile_count + columns - 1) / columns;
+    let texture_atlas = load_context.set_labeled_asset(
+        atlas_label,
+        LoadedAsset::new(TextureAtlas::from_grid(
+            image.clone(),
+            definition.tile_size.as_vec2(),
+            columns as usize,
+            rows as usize,
+            Some(Vec2::splat(definition.spacing as f32)),
+            Some(Vec2::splat(definition.margin as f32)),
+        ))
+        .with_dependency(image_path.clone()),
+    );
+
+    (
+        TiledTileset {
+            name: definition.name.clone(),
+            tile_size: definition.tile_size,
+            tile_count: definition.tile_count,
+            columns,
+            image,
+            texture_atlas,
+            animations: definition.animations.clone(),
+            properties: Arc::new(definition.properties.clone()),
+        },
+        image_path,
+    )
+}
+
+/// Joins a path relative to a Tiled file, collapsing `..` so that the same file always
+/// resolves to the same asset path.
+fn resolve_path(base_path: &Path, relative: &str) -> PathBuf {
+    let mut path = PathBuf::new();
+    for component in base_path.join(relative).components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                path.pop();
+            }
+            component => path.push(component),
+        }
+    }
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paths() {
+        assert_eq!(
+            resolve_path(Path::new("maps"), "../tilesets/tiles.tsx"),
+            PathBuf::from("tilesets/tiles.tsx")
+        );
+        assert_eq!(
+            resolve_path(Path::new("tilesets"), "./tiles.png"),
+            PathBuf::from("tilesets/tiles.png")
+        );
+    }
+}
@@ -0,0 +1,179 @@
+use crate::tilemap::animation::TileAnimations;
+use crate::tilemap::bundle::TilemapLayer;
+use crate::tilemap::data::TilemapData;
+use crate::tilemap::material::TilemapMaterial;
+use crate::tilemap::properties::TilesetProperties;
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use std::sync::Arc;
+use thiserror::Error;
+
+pub mod loader;
+pub mod parse;
+
+/// Tiled stores flip and rotation flags in the upper bits of every global tile id.
+pub const TILED_GID_FLAGS_MASK: u32 = 0xF000_0000;
+
+/// A map authored in Tiled (`.tmx`).
+#[derive(TypeUuid, Debug)]
+#[uuid = "0b4d4c1e-7a3e-4a6f-9a3c-2b8f61c4d2a7"]
+pub struct TiledMap {
+    pub tile_size: UVec2,
+    pub tilesets: Vec<TiledMapTileset>,
+    pub layers: Vec<TiledTileLayer>,
+}
+
+/// A tileset referenced by a [`TiledMap`].
+#[derive(Debug, Clone)]
+pub struct TiledMapTileset {
+    pub first_gid: u32,
+    pub tileset: Handle<TiledTileset>,
+}
+
+/// A single tile layer of a [`TiledMap`].
+///
+/// Atlas indices in `data` are local to the tileset at `tileset_index`, layers authored with
+/// several tilesets are split into one layer per tileset.
+#[derive(Debug, Clone)]
+pub struct TiledTileLayer {
+    pub name: String,
+    pub opacity: f32,
+    pub tileset_index: Option<usize>,
+    pub data: TilemapData,
+}
+
+/// A tileset authored in Tiled (`.tsx`), or embedded in a map.
+#[derive(TypeUuid, Debug, Clone)]
+#[uuid = "5f0f8a3b-2d6c-4b8e-8f21-6e7c3a9d1b54"]
+pub struct TiledTileset {
+    pub name: String,
+    pub tile_size: UVec2,
+    pub tile_count: u32,
+    pub columns: u32,
+    pub image: Handle<Image>,
+    pub texture_atlas: Handle<TextureAtlas>,
+    pub animations: TileAnimations,
+    pub properties: Arc<TilesetProperties>,
+}
+
+impl TiledMap {
+    /// Makes a tilemap layer for each tile layer, stacked in the order they were authored.
+    ///
+    /// Returns `None` while any of the used tilesets isn't loaded yet.
+    pub fn make_layers(
+        &self,
+        tilesets: &Assets<TiledTileset>,
+        materials: &mut Assets<TilemapMaterial>,
+    ) -> Option<Vec<TilemapLayer>> {
+        let mut layers = Vec::with_capacity(self.layers.len());
+        for (i, layer) in self.layers.iter().enumerate() {
+            let tileset_index = match layer.tileset_index {
+                Some(tileset_index) => tileset_index,
+                None => continue,
+            };
+            let tileset = tilesets.get(&self.tilesets[tileset_index].tileset)?;
+            let material = materials.add(TilemapMaterial::new(tileset.image.clone()));
+            layers.push(
+                TilemapLayer::new(
+                    layer.name.clone(),
+                    layer
+                        .data
+                        .clone()
+                        .with_properties(tileset.properties.clone()),
+                    tileset.texture_atlas.clone(),
+                    material,
+                )
+                .with_z_offset(i as f32)
+                .with_opacity(layer.opacity)
+                .with_animations(tileset.animations.clone()),
+            );
+        }
+        Some(layers)
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum TiledError {
+    #[error("invalid xml: {0}")]
+    Xml(#[from] roxmltree::Error),
+    #[error("invalid base64 data: {0}")]
+    Base64(#[from] base64::DecodeError),
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("missing element `{0}`")]
+    MissingElement(&'static str),
+    #[error("missing attribute `{0}`")]
+    MissingAttribute(&'static str),
+    #[error("invalid value `{value}` for `{name}`")]
+    InvalidValue { name: &'static str, value: String },
+    #[error("unsupported {0}")]
+    Unsupported(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::asset::{AssetPlugin, HandleId};
+
+    #[test]
+    fn make_layers() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_plugin(AssetPlugin::default())
+            .add_asset::<TiledTileset>()
+            .add_asset::<TilemapMaterial>();
+        let layer = |name: &str, opacity, tileset_index| TiledTileLayer {
+            name: name.to_string(),
+            opacity,
+            tileset_index,
+            data: TilemapData::new(),
+        };
+        let map = TiledMap {
+            tile_size: UVec2::splat(8),
+            tilesets: vec![TiledMapTileset {
+                first_gid: 1,
+                tileset: Handle::weak(HandleId::random::<TiledTileset>()),
+            }],
+            layers: vec![
+                layer("ground", 1.0, Some(0)),
+                layer("empty", 1.0, None),
+                layer("shadows", 0.5, Some(0)),
+            ],
+        };
+
+        // Nothing is made until the tilesets are loaded.
+        app.world
+            .resource_scope(|world, mut materials: Mut<Assets<TilemapMaterial>>| {
+                let tilesets = world.resource::<Assets<TiledTileset>>();
+                assert!(map.make_layers(tilesets, &mut materials).is_none());
+            });
+        let tileset = TiledTileset {
+            name: "tiles".to_string(),
+            tile_size: UVec2::splat(8),
+            tile_count: 4,
+            columns: 2,
+            image: Default::default(),
+            texture_atlas: Default::default(),
+            animations: Default::default(),
+            properties: Default::default(),
+        };
+        let handle = app
+            .world
+            .resource_mut::<Assets<TiledTileset>>()
+            .set(&map.tilesets[0].tileset, tileset);
+        assert_eq!(handle, map.tilesets[0].tileset);
+
+        let layers =
+            app.world
+                .resource_scope(|world, mut materials: Mut<Assets<TilemapMaterial>>| {
+                    let tilesets = world.resource::<Assets<TiledTileset>>();
+                    map.make_layers(tilesets, &mut materials).unwrap()
+                });
+        let summary: Vec<_> = layers
+            .iter()
+            .map(|layer| (layer.name.as_str(), layer.z_offset, layer.opacity))
+            .collect();
+        assert_eq!(summary, [("ground", 0.0, 1.0), ("shadows", 2.0, 0.5)]);
+        assert_ne!(layers[0].material, layers[1].material);
+    }
+}
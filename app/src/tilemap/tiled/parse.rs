@@ -0,0 +1,523 @@
+use crate::tilemap::animation::{TileAnimation, TileAnimationFrame, TileAnimations};
+use crate::tilemap::data::{ChunkData, TileData, TilemapData};
+use crate::tilemap::properties::{TileFlags, TileProperties, TilesetProperties};
+use crate::tilemap::tiled::{TiledError, TiledTileLayer, TILED_GID_FLAGS_MASK};
+use base64::Engine;
+use bevy::prelude::*;
+use bevy::utils::Duration;
+use flate2::read::{GzDecoder, ZlibDecoder};
+use roxmltree::{Document, Node};
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::str::FromStr;
+
+#[derive(Debug, Clone)]
+pub struct TiledMapDefinition {
+    pub tile_size: UVec2,
+    pub tilesets: Vec<TiledTilesetReference>,
+    pub layers: Vec<TiledTileLayer>,
+}
+
+#[derive(Debug, Clone)]
+pub struct TiledTilesetReference {
+    pub first_gid: u32,
+    pub source: TiledTilesetSource,
+}
+
+#[derive(Debug, Clone)]
+pub enum TiledTilesetSource {
+    /// Path of a `.tsx` file, relative to the map.
+    External(String),
+    Embedded(TiledTilesetDefinition),
+}
+
+#[derive(Debug, Clone)]
+pub struct TiledTilesetDefinition {
+    pub name: String,
+    pub tile_size: UVec2,
+    pub tile_count: u32,
+    pub columns: u32,
+    pub spacing: u32,
+    pub margin: u32,
+    /// Path of the tileset image, relative to the file that defines the tileset.
+    pub image_source: String,
+    pub image_size: UVec2,
+    pub animations: TileAnimations,
+    pub properties: TilesetProperties,
+}
+
+pub fn parse_map(text: &str) -> Result<TiledMapDefinition, TiledError> {
+    let document = Document::parse(text)?;
+    let map = document.root_element();
+    if !map.has_tag_name("map") {
+        return Err(TiledError::MissingElement("map"));
+    }
+    let orientation = map.attribute("orientation").unwrap_or("orthogonal");
+    if orientation != "orthogonal" {
+        return Err(TiledError::Unsupported(format!(
+            "orientation `{}`",
+            orientation
+        )));
+    }
+
+    let tile_size = UVec2::new(
+        parse_attribute(&map, "tilewidth")?,
+        parse_attribute(&map, "tileheight")?,
+    );
+    let infinite = parse_attribute_or(&map, "infinite", 0u32)? != 0;
+
+    let mut tilesets = Vec::new();
+    for tileset in map.children().filter(|node| node.has_tag_name("tileset")) {
+        let first_gid = parse_attribute(&tileset, "firstgid")?;
+        let source = match tileset.attribute("source") {
+            Some(source) => TiledTilesetSource::External(source.to_string()),
+            None => TiledTilesetSource::Embedded(parse_tileset_node(&tileset)?),
+        };
+        tilesets.push(TiledTilesetReference { first_gid, source });
+    }
+    tilesets.sort_by_key(|tileset| tileset.first_gid);
+    let first_gids: Vec<u32> = tilesets.iter().map(|tileset| tileset.first_gid).collect();
+
+    // Layers may be nested inside groups, document order is the draw order.
+    let mut layers = Vec::new();
+    for layer in map.descendants().filter(|node| node.has_tag_name("layer")) {
+        layers.extend(parse_layer(&layer, infinite, &first_gids)?);
+    }
+
+    Ok(TiledMapDefinition {
+        tile_size,
+        tilesets,
+        layers,
+    })
+}
+
+pub fn parse_tileset(text: &str) -> Result<TiledTilesetDefinition, TiledError> {
+    let document = Document::parse(text)?;
+    let tileset = document.root_element();
+    if !tileset.has_tag_name("tileset") {
+        return Err(TiledError::MissingElement("tileset"));
+    }
+    parse_tileset_node(&tileset)
+}
+
+fn parse_tileset_node(tileset: &Node) -> Result<TiledTilesetDefinition, TiledError> {
+    let image = tileset
+        .children()
+        .find(|node| node.has_tag_name("image"))
+        .ok_or_else(|| TiledError::Unsupported("tileset without a single image".into()))?;
+
+    // Tileset properties apply to every tile, tile properties override them.
+    let mut properties =
+        TilesetProperties::new(parse_tile_properties(tileset, TileProperties::default())?);
+    let mut animations = TileAnimations::default();
+    for tile in tileset.children().filter(|node| node.has_tag_name("tile")) {
+        let id = parse_attribute(&tile, "id")?;
+        if tile.children().any(|node| node.has_tag_name("properties")) {
+            properties.set(id, parse_tile_properties(&tile, properties.default)?);
+        }
+
+        let animation = match tile.children().find(|node| node.has_tag_name("animation")) {
+            Some(animation) => animation,
+            None => continue,
+        };
+        let frames = animation
+            .children()
+            .filter(|node| node.has_tag_name("frame"))
+            .map(|frame| {
+                Ok(TileAnimationFrame {
+                    atlas_index: parse_attribute(&frame, "tileid")?,
+                    duration: Duration::from_millis(parse_attribute(&frame, "duration")?),
+                })
+            })
+            .collect::<Result<Vec<_>, TiledError>>()?;
+        animations.insert(id, TileAnimation::new(frames));
+    }
+
+    Ok(TiledTilesetDefinition {
+        name: tileset.attribute("name").unwrap_or_default().to_string(),
+        tile_size: UVec2::new(
+            parse_attribute(tileset, "tilewidth")?,
+            parse_attribute(tileset, "tileheight")?,
+        ),
+        tile_count: parse_attribute(tileset, "tilecount")?,
+        columns: parse_attribute(tileset, "columns")?,
+        spacing: parse_attribute_or(tileset, "spacing", 0)?,
+        margin: parse_attribute_or(tileset, "margin", 0)?,
+        image_source: image
+            .attribute("source")
+            .ok_or(TiledError::MissingAttribute("source"))?
+            .to_string(),
+        image_size: UVec2::new(
+            parse_attribute(&image, "width")?,
+            parse_attribute(&image, "height")?,
+        ),
+        animations,
+        properties,
+    })
+}
+
+/// Applies the `walkable`, `buildable`, `blocks_light`, `blocks_projectiles` and `movement_cost`
+/// custom properties of a node, other properties are ignored.
+fn parse_tile_properties(
+    node: &Node,
+    mut properties: TileProperties,
+) -> Result<TileProperties, TiledError> {
+    let nodes = node
+        .children()
+        .filter(|child| child.has_tag_name("properties"))
+        .flat_map(|child| child.children())
+        .filter(|child| child.has_tag_name("property"));
+    for property in nodes {
+        let flag = match property.attribute("name") {
+            Some("walkable") => TileFlags::WALKABLE,
+            Some("buildable") => TileFlags::BUILDABLE,
+            Some("blocks_light") => TileFlags::BLOCKS_LIGHT,
+            Some("blocks_projectiles") => TileFlags::BLOCKS_PROJECTILES,
+            Some("movement_cost") => {
+                properties.movement_cost = parse_attribute(&property, "value")?;
+                continue;
+            }
+            _ => continue,
+        };
+        properties
+            .flags
+            .set(flag, parse_attribute(&property, "value")?);
+    }
+    Ok(properties)
+}
+
+/// Splits a layer using several tilesets into a layer per tileset, sharing its name.
+fn parse_layer(
+    layer: &Node,
+    infinite: bool,
+    first_gids: &[u32],
+) -> Result<Vec<TiledTileLayer>, TiledError> {
+    let name = layer.attribute("name").unwrap_or_default().to_string();
+    let opacity = parse_attribute_or(layer, "opacity", 1.0f32)?;
+    let data = layer
+        .children()
+        .find(|node| node.has_tag_name("data"))
+        .ok_or(TiledError::MissingElement("data"))?;
+    let encoding = data.attribute("encoding");
+    let compression = data.attribute("compression");
+
+    let mut tilemaps: BTreeMap<usize, TilemapData> = BTreeMap::new();
+    let mut place_tiles = |origin: IVec2, width: u32, gids: Vec<u32>| -> Result<(), TiledError> {
+        for (i, gid) in gids.into_iter().enumerate() {
+            // Flipped and rotated tiles are placed unflipped.
+            let gid = gid & !TILED_GID_FLAGS_MASK;
+            if gid == 0 {
+                continue;
+            }
+            let index = first_gids
+                .iter()
+                .rposition(|first_gid| *first_gid <= gid)
+                .ok_or(TiledError::InvalidValue {
+                    name: "gid",
+                    value: gid.to_string(),
+                })?;
+
+            // Tiled rows grow downwards, tilemap rows grow upwards.
+            let x = origin.x + (i as u32 % width) as i32;
+            let y = origin.y + (i as u32 / width) as i32;
+            let location = IVec2::new(x, -y - 1);
+            let tilemap = tilemaps.entry(index).or_insert_with(TilemapData::new);
+            // Cells without a tile stay empty so that the layers below show through.
+            let chunk_location = TilemapData::tilemap_to_chunk(location);
+            if tilemap.get_chunk(chunk_location).is_none() {
+                tilemap.insert_chunk(chunk_location, ChunkData::empty());
+            }
+            tilemap.set_tile(location, TileData::new((gid - first_gids[index]) as usize));
+        }
+        Ok(())
+    };
+
+    if infinite {
+        for chunk in data.children().filter(|node| node.has_tag_name("chunk")) {
+            let origin = IVec2::new(parse_attribute(&chunk, "x")?, parse_attribute(&chunk, "y")?);
+            let width: u32 = parse_attribute(&chunk, "width")?;
+            let height: u32 = parse_attribute(&chunk, "height")?;
+            let gids = decode_tiles(&chunk, encoding, compression, (width * height) as usize)?;
+            place_tiles(origin, width, gids)?;
+        }
+    } else {
+        let width: u32 = parse_attribute(layer, "width")?;
+        let height: u32 = parse_attribute(layer, "height")?;
+        let gids = decode_tiles(&data, encoding, compression, (width * height) as usize)?;
+        place_tiles(IVec2::ZERO, width, gids)?;
+    }
+
+    if tilemaps.is_empty() {
+        return Ok(vec![TiledTileLayer {
+            name,
+            opacity,
+            tileset_index: None,
+            data: TilemapData::new(),
+        }]);
+    }
+    Ok(tilemaps
+        .into_iter()
+        .map(|(tileset_index, data)| TiledTileLayer {
+            name: name.clone(),
+            opacity,
+            tileset_index: Some(tileset_index),
+            data,
+        })
+        .collect())
+}
+
+fn decode_tiles(
+    node: &Node,
+    encoding: Option<&str>,
+    compression: Option<&str>,
+    count: usize,
+) -> Result<Vec<u32>, TiledError> {
+    let gids = match encoding {
+        None => node
+            .children()
+            .filter(|child| child.has_tag_name("tile"))
+            .map(|tile| parse_attribute_or(&tile, "gid", 0u32))
+            .collect::<Result<Vec<_>, _>>()?,
+        Some("csv") => node
+            .text()
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+            .map(|value| {
+                value.parse().map_err(|_| TiledError::InvalidValue {
+                    name: "csv",
+                    value: value.to_string(),
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?,
+        Some("base64") => {
+            let text: String = node
+                .text()
+                .unwrap_or_default()
+                .chars()
+                .filter(|c| !c.is_whitespace())
+                .collect();
+            let bytes = base64::engine::general_purpose::STANDARD.decode(text)?;
+            let bytes = match compression {
+                None => bytes,
+                Some("zlib") => {
+                    let mut decoded = Vec::with_capacity(count * 4);
+                    ZlibDecoder::new(bytes.as_slice()).read_to_end(&mut decoded)?;
+                    decoded
+                }
+                Some("gzip") => {
+                    let mut decoded = Vec::with_capacity(count * 4);
+                    GzDecoder::new(bytes.as_slice()).read_to_end(&mut decoded)?;
+                    decoded
+                }
+                Some(compression) => {
+                    return Err(TiledError::Unsupported(format!(
+                        "compression `{}`",
+                        compression
+                    )))
+                }
+            };
+            bytes
+                .chunks_exact(4)
+                .map(|gid| u32::from_le_bytes([gid[0], gid[1], gid[2], gid[3]]))
+                .collect()
+        }
+        Some(encoding) => return Err(TiledError::Unsupported(format!("encoding `{}`", encoding))),
+    };
+
+    if gids.len() != count {
+        return Err(TiledError::InvalidValue {
+            name: "data",
+            value: format!("{} tiles, expected {}", gids.len(), count),
+        });
+    }
+    Ok(gids)
+}
+
+fn parse_attribute<T: FromStr>(node: &Node, name: &'static str) -> Result<T, TiledError> {
+    let value = node
+        .attribute(name)
+        .ok_or(TiledError::MissingAttribute(name))?;
+    value.parse().map_err(|_| TiledError::InvalidValue {
+        name,
+        value: value.to_string(),
+    })
+}
+
+fn parse_attribute_or<T: FromStr>(
+    node: &Node,
+    name: &'static str,
+    default: T,
+) -> Result<T, TiledError> {
+    if node.has_attribute(name) {
+        parse_attribute(node, name)
+    } else {
+        Ok(default)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_map() {
+        let map = parse_map(include_str!("../../../assets/maps/test.tmx")).unwrap();
+        assert_eq!(map.tile_size, UVec2::new(8, 8));
+        assert_eq!(map.tilesets.len(), 1);
+        assert!(matches!(
+            &map.tilesets[0].source,
+            TiledTilesetSource::External(source) if source == "../tilesets/tiles.tsx"
+        ));
+        assert_eq!(map.layers.len(), 1);
+        assert_eq!(map.layers[0].tileset_index, Some(0));
+        assert!(!map.layers[0].data.chunks.is_empty());
+    }
+
+    #[test]
+    fn test_tileset() {
+        let tileset = parse_tileset(include_str!("../../../assets/tilesets/tiles.tsx")).unwrap();
+        assert_eq!(tileset.tile_size, UVec2::new(8, 8));
+        assert_eq!(tileset.tile_count, 72);
+        assert_eq!(tileset.columns, 8);
+        assert_eq!(tileset.image_source, "../graphics/tiles.png");
+    }
+
+    #[test]
+    fn animations() {
+        let tileset = parse_tileset(
+            r#"<tileset name="water" tilewidth="16" tileheight="16" tilecount="4" columns="4">
+             <image source="water.png" width="64" height="16"/>
+             <tile id="1">
+              <animation>
+               <frame tileid="1" duration="100"/>
+               <frame tileid="2" duration="250"/>
+              </animation>
+             </tile>
+            </tileset>"#,
+        )
+        .unwrap();
+        assert_eq!(
+            tileset.animations.get(1),
+            Some(&TileAnimation::new([
+                TileAnimationFrame {
+                    atlas_index: 1,
+                    duration: Duration::from_millis(100),
+                },
+                TileAnimationFrame {
+                    atlas_index: 2,
+                    duration: Duration::from_millis(250),
+                },
+            ]))
+        );
+        assert!(tileset.animations.get(0).is_none());
+    }
+
+    #[test]
+    fn properties() {
+        let tileset = parse_tileset(
+            r#"<tileset name="ground" tilewidth="16" tileheight="16" tilecount="4" columns="4">
+             <properties>
+              <property name="buildable" type="bool" value="false"/>
+             </properties>
+             <image source="ground.png" width="64" height="16"/>
+             <tile id="2">
+              <properties>
+               <property name="walkable" type="bool" value="false"/>
+               <property name="blocks_projectiles" type="bool" value="true"/>
+               <property name="footstep" value="stone"/>
+              </properties>
+             </tile>
+             <tile id="3">
+              <properties>
+               <property name="movement_cost" type="float" value="2.5"/>
+              </properties>
+             </tile>
+            </tileset>"#,
+        )
+        .unwrap();
+        let properties = &tileset.properties;
+        assert_eq!(properties.get(0).flags, TileFlags::WALKABLE);
+        assert_eq!(properties.get(2).flags, TileFlags::BLOCKS_PROJECTILES);
+        assert_eq!(properties.get(3).flags, TileFlags::WALKABLE);
+        assert_eq!(properties.get(3).movement_cost, 2.5);
+        assert_eq!(properties.get(10).movement_cost, 1.0);
+    }
+
+    #[test]
+    fn encodings() {
+        let gids: [u32; 4] = [1, 0, 12, 3 | 0x8000_0000];
+        let bytes: Vec<u8> = gids.iter().flat_map(|gid| gid.to_le_bytes()).collect();
+        let mut encoder =
+            flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(&bytes).unwrap();
+        let zlib = base64::engine::general_purpose::STANDARD.encode(encoder.finish().unwrap());
+
+        for data in [
+            r#"<data encoding="csv">1,0,12,2147483651</data>"#.to_string(),
+            format!(
+                r#"<data encoding="base64">{}</data>"#,
+                base64::engine::general_purpose::STANDARD.encode(&bytes)
+            ),
+            format!(
+                r#"<data encoding="base64" compression="zlib">{}</data>"#,
+                zlib
+            ),
+        ] {
+            let map = parse_map(&format!(
+                r#"<map orientation="orthogonal" width="2" height="2" tilewidth="8" tileheight="8">
+                 <tileset firstgid="1" source="a.tsx"/>
+                 <layer name="a" width="2" height="2">{}</layer>
+                </map>"#,
+                data
+            ))
+            .unwrap();
+            let tilemap = &map.layers[0].data;
+            let atlas_index = |x, y| {
+                tilemap
+                    .get_tile(IVec2::new(x, y))
+                    .map(|tile| tile.atlas_index)
+            };
+            assert_eq!(atlas_index(0, -1), Some(0));
+            assert_eq!(atlas_index(1, -1), None);
+            assert_eq!(atlas_index(0, -2), Some(11));
+            assert_eq!(atlas_index(1, -2), Some(2));
+        }
+    }
+
+    #[test]
+    fn first_gids() {
+        let map = parse_map(
+            r#"<map orientation="orthogonal" width="2" height="1" tilewidth="8" tileheight="8">
+             <tileset firstgid="10" source="b.tsx"/>
+             <tileset firstgid="1" source="a.tsx"/>
+             <layer name="a" width="2" height="1"><data encoding="csv">12,0</data></layer>
+             <layer name="b" width="2" height="1"><data encoding="csv">0,3</data></layer>
+            </map>"#,
+        )
+        .unwrap();
+        let (a, b) = (&map.layers[0], &map.layers[1]);
+        assert_eq!(a.tileset_index, Some(1));
+        assert_eq!(b.tileset_index, Some(0));
+        assert_eq!(a.data.get_tile(IVec2::new(0, -1)).unwrap().atlas_index, 2);
+        assert_eq!(b.data.get_tile(IVec2::new(1, -1)).unwrap().atlas_index, 2);
+
+        let map = parse_map(
+            r#"<map orientation="orthogonal" width="2" height="1" tilewidth="8" tileheight="8">
+             <tileset firstgid="1" source="a.tsx"/>
+             <tileset firstgid="10" source="b.tsx"/>
+             <layer name="a" width="2" height="1"><data encoding="csv">1,12</data></layer>
+            </map>"#,
+        )
+        .unwrap();
+        // Tiles of different tilesets are split into layers of the same name.
+        assert_eq!(map.layers.len(), 2);
+        let (a, b) = (&map.layers[0], &map.layers[1]);
+        assert_eq!((a.name.as_str(), a.tileset_index), ("a", Some(0)));
+        assert_eq!((b.name.as_str(), b.tileset_index), ("a", Some(1)));
+        assert_eq!(a.data.get_tile(IVec2::new(0, -1)).unwrap().atlas_index, 0);
+        assert_eq!(b.data.get_tile(IVec2::new(1, -1)).unwrap().atlas_index, 2);
+    }
+}
@@ -0,0 +1,348 @@
+use crate::economy::{Economy, Funds};
+use crate::error::{AppError, AppResult};
+use crate::prototype::commands::ApplyPrototype;
+use crate::prototype::{
+    PrototypeComponent, PrototypeDefinition, PrototypeRegistry, SpriteDefinition,
+};
+use bevy::ecs::system::Command;
+use bevy::prelude::*;
+use serde::Deserialize;
+
+/// Node of the upgrade tree of a tower prototype.
+///
+/// A tower gets at most one upgrade per tier, so upgrades sharing a tier are branches.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct UpgradeDefinition {
+    pub id: String,
+    /// Starts at 1.
+    pub tier: u32,
+    /// Upgrades of lower tiers the tower needs first.
+    #[serde(default)]
+    pub requires: Vec<String>,
+    #[serde(default)]
+    pub cost: Funds,
+    #[serde(default)]
+    pub modifiers: Vec<StatModifier>,
+    /// Replaces the sprite of the tower.
+    #[serde(default)]
+    pub sprite: Option<SpriteDefinition>,
+    /// Shows another cell of the sprite atlas.
+    #[serde(default)]
+    pub atlas_index: Option<u32>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Stat {
+    Health,
+    Range,
+    Cooldown,
+    Damage,
+}
+
+/// Changes a stat to `(base + add) * multiply`, adds of every upgrade being summed before the
+/// multipliers apply.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct StatModifier {
+    pub stat: Stat,
+    #[serde(default)]
+    pub add: f32,
+    #[serde(default = "default_multiply")]
+    pub multiply: f32,
+}
+
+/// Upgrades applied to a tower, in the order they were bought.
+#[derive(Component, Debug, Clone, Default, PartialEq, Eq)]
+pub struct UpgradesComponent {
+    pub applied: Vec<String>,
+}
+
+fn default_multiply() -> f32 {
+    1.0
+}
+
+impl UpgradeDefinition {
+    /// Sprite of a tower of `base` having this upgrade, `None` when it doesn't change visuals.
+    pub fn sprite(&self, base: Option<&SpriteDefinition>) -> Option<SpriteDefinition> {
+        if self.sprite.is_none() && self.atlas_index.is_none() {
+            return None;
+        }
+        let mut sprite = self.sprite.as_ref().or(base)?.clone();
+        if let (Some(atlas), Some(index)) = (&mut sprite.atlas, self.atlas_index) {
+            atlas.index = index;
+        }
+        Some(sprite)
+    }
+}
+
+impl UpgradesComponent {
+    pub fn contains(&self, id: &str) -> bool {
+        self.applied.iter().any(|applied| applied == id)
+    }
+}
+
+impl PrototypeDefinition {
+    pub fn upgrade(&self, id: &str) -> Option<&UpgradeDefinition> {
+        self.upgrades.iter().find(|upgrade| upgrade.id == id)
+    }
+
+    /// Definitions of the applied upgrades, unknown ids are skipped.
+    pub fn applied_upgrades<'a, 'b>(
+        &'a self,
+        upgrades: &'b UpgradesComponent,
+    ) -> impl Iterator<Item = &'a UpgradeDefinition> + 'b
+    where
+        'a: 'b,
+    {
+        upgrades.applied.iter().filter_map(|id| self.upgrade(id))
+    }
+
+    /// Upgrades a tower with `upgrades` can get next, affordable or not.
+    pub fn available_upgrades<'a, 'b>(
+        &'a self,
+        upgrades: &'b UpgradesComponent,
+    ) -> impl Iterator<Item = &'a UpgradeDefinition> + 'b
+    where
+        'a: 'b,
+    {
+        self.upgrades.iter().filter(|upgrade| {
+            !self
+                .applied_upgrades(upgrades)
+                .any(|applied| applied.tier == upgrade.tier)
+                && upgrade.requires.iter().all(|id| upgrades.contains(id))
+        })
+    }
+}
+
+/// Value of `stat` after the modifiers for it.
+pub fn modify_stat<'a>(
+    stat: Stat,
+    base: f32,
+    modifiers: impl IntoIterator<Item = &'a StatModifier>,
+) -> f32 {
+    let (add, multiply) = modifiers
+        .into_iter()
+        .filter(|modifier| modifier.stat == stat)
+        .fold((0.0, 1.0), |(add, multiply), modifier| {
+            (add + modifier.add, multiply * modifier.multiply)
+        });
+    (base + add) * multiply
+}
+
+/// Upgrades a tower can get next, empty for entities not made from a prototype.
+pub fn available_upgrades(world: &World, entity: Entity) -> Vec<&UpgradeDefinition> {
+    let prototype = match world
+        .get::<PrototypeComponent>(entity)
+        .and_then(|prototype| world.resource::<PrototypeRegistry>().get(&prototype.id))
+    {
+        Some(prototype) => prototype,
+        None => return Vec::new(),
+    };
+    let upgrades = world
+        .get::<UpgradesComponent>(entity)
+        .cloned()
+        .unwrap_or_default();
+    prototype.definition.available_upgrades(&upgrades).collect()
+}
+
+/// Charges and applies an upgrade to a tower, then reapplies its prototype so the modifiers and
+/// visuals take effect.
+///
+/// Nothing changes when the upgrade isn't available or can't be paid for.
+pub fn apply_upgrade(world: &mut World, entity: Entity, id: &str) -> AppResult<()> {
+    let prototype_id = world
+        .get::<PrototypeComponent>(entity)
+        .map(|prototype| prototype.id.clone())
+        .ok_or_else(|| AppError::UnavailableUpgrade {
+            entity,
+            upgrade: id.to_string(),
+        })?;
+    let upgrades = world
+        .get::<UpgradesComponent>(entity)
+        .cloned()
+        .unwrap_or_default();
+    world.resource_scope(|world, registry: Mut<PrototypeRegistry>| {
+        let definition = &registry
+            .get(&prototype_id)
+            .ok_or_else(|| AppError::UnknownPrototype(prototype_id.clone()))?
+            .definition;
+        if !definition
+            .available_upgrades(&upgrades)
+            .any(|upgrade| upgrade.id == id)
+        {
+            return Err(AppError::UnavailableUpgrade {
+                entity,
+                upgrade: id.to_string(),
+            });
+        }
+        world
+            .resource_mut::<Economy>()
+            .upgrade(&registry, &prototype_id, id)
+    })?;
+
+    let mut upgrades = upgrades;
+    upgrades.applied.push(id.to_string());
+    world.entity_mut(entity).insert(upgrades);
+    ApplyPrototype {
+        entity,
+        id: prototype_id,
+    }
+    .write(world);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actor::HealthComponent;
+    use crate::economy::Currency;
+    use crate::prototype::commands::SpawnPrototypeExt;
+    use crate::prototype::PrototypeSet;
+    use crate::targeting::WeaponComponent;
+    use bevy::ecs::system::CommandQueue;
+
+    const PROTOTYPES: &str = r#"
+archer_tower:
+  kind: tower
+  health: 100
+  sprite:
+    texture: graphics/towers.png
+    atlas: {columns: 4, rows: 2}
+  weapon: {range: 3, cooldown: 1, damage: 10}
+  upgrades:
+    - id: longbow
+      tier: 1
+      cost: {gold: 20}
+      modifiers: [{stat: range, add: 1}]
+      atlas_index: 1
+    - id: crossbow
+      tier: 1
+      cost: {gold: 30}
+      modifiers: [{stat: damage, multiply: 2}]
+    - id: fire_arrows
+      tier: 2
+      requires: [longbow]
+      cost: {gold: 40, crystal: 1}
+      modifiers:
+        - {stat: damage, add: 5}
+        - {stat: damage, multiply: 1.5}
+        - {stat: cooldown, multiply: 0.5}
+        - {stat: health, add: 50}
+      sprite: {texture: graphics/fire-tower.png}
+"#;
+
+    fn available(world: &World, tower: Entity) -> Vec<String> {
+        available_upgrades(world, tower)
+            .into_iter()
+            .map(|upgrade| upgrade.id.clone())
+            .collect()
+    }
+
+    #[test]
+    fn upgrades() {
+        let mut world = World::new();
+        let set = PrototypeSet::parse(PROTOTYPES).unwrap();
+        world.insert_resource(PrototypeRegistry::from_sets([&set]).unwrap());
+        world.insert_resource(Economy::default());
+        let mut queue = CommandQueue::default();
+        let tower = Commands::new(&mut queue, &world)
+            .spawn_prototype("archer_tower", Transform::IDENTITY)
+            .id();
+        queue.apply(&mut world);
+        let weapon = |world: &World| {
+            world
+                .get::<WeaponComponent>(tower)
+                .unwrap()
+                .definition
+                .clone()
+        };
+
+        assert_eq!(available(&world, tower), ["longbow", "crossbow"]);
+        assert!(matches!(
+            apply_upgrade(&mut world, tower, "fire_arrows"),
+            Err(AppError::UnavailableUpgrade { .. })
+        ));
+        assert!(matches!(
+            apply_upgrade(&mut world, tower, "longbow"),
+            Err(AppError::InsufficientFunds { .. })
+        ));
+        assert_eq!(world.get::<UpgradesComponent>(tower), None);
+        assert_eq!(weapon(&world).range, 3.0);
+
+        world
+            .resource_mut::<Economy>()
+            .grant(Funds::new([(Currency::Gold, 60), (Currency::Crystal, 1)]))
+            .unwrap();
+        apply_upgrade(&mut world, tower, "longbow").unwrap();
+        assert_eq!(weapon(&world).range, 4.0);
+        // Taking a branch closes the other ones of its tier.
+        assert_eq!(available(&world, tower), ["fire_arrows"]);
+
+        world.get_mut::<HealthComponent>(tower).unwrap().current = 80.0;
+        apply_upgrade(&mut world, tower, "fire_arrows").unwrap();
+        assert_eq!(weapon(&world).damage, 22.5);
+        assert_eq!(weapon(&world).cooldown, 0.5);
+        assert_eq!(
+            world.get::<HealthComponent>(tower),
+            Some(&HealthComponent {
+                current: 80.0,
+                max: 150.0
+            })
+        );
+        assert!(available(&world, tower).is_empty());
+        assert_eq!(
+            world.resource::<Economy>().balances(),
+            &Funds::new([(Currency::Gold, 0), (Currency::Crystal, 0)])
+        );
+    }
+
+    #[test]
+    fn visuals() {
+        let set = PrototypeSet::parse(PROTOTYPES).unwrap();
+        let registry = PrototypeRegistry::from_sets([&set]).unwrap();
+        let definition = &registry.get("archer_tower").unwrap().definition;
+        let base = definition.sprite.as_ref();
+
+        let longbow = definition.upgrade("longbow").unwrap().sprite(base).unwrap();
+        assert_eq!(longbow.texture.as_deref(), Some("graphics/towers.png"));
+        assert_eq!(longbow.atlas.as_ref().unwrap().index, 1);
+        let uvs = match longbow.make_mesh().attribute(Mesh::ATTRIBUTE_UV_0) {
+            Some(bevy::render::mesh::VertexAttributeValues::Float32x2(uvs)) => uvs.clone(),
+            _ => panic!("quads have uvs"),
+        };
+        assert!(uvs
+            .iter()
+            .all(|[u, v]| (0.25..=0.5).contains(u) && (0.0..=0.5).contains(v)));
+
+        assert_eq!(definition.upgrade("crossbow").unwrap().sprite(base), None);
+        let fire_arrows = definition
+            .upgrade("fire_arrows")
+            .unwrap()
+            .sprite(base)
+            .unwrap();
+        assert_eq!(
+            fire_arrows.texture.as_deref(),
+            Some("graphics/fire-tower.png")
+        );
+        assert_eq!(fire_arrows.atlas, None);
+
+        let invalid = |upgrades: &str| {
+            let set = PrototypeSet::parse(&format!("a: {{kind: tower, upgrades: {}}}", upgrades))
+                .unwrap();
+            matches!(
+                PrototypeRegistry::from_sets([&set]),
+                Err(AppError::InvalidPrototype { .. })
+            )
+        };
+        assert!(invalid("[{id: a, tier: 1, atlas_index: 1}]"));
+        assert!(invalid(
+            "[{id: a, tier: 1}, {id: b, tier: 1, requires: [a]}]"
+        ));
+        assert!(invalid("[{id: a, tier: 1}, {id: a, tier: 2}]"));
+        assert!(!invalid(
+            "[{id: a, tier: 1}, {id: b, tier: 2, requires: [a]}]"
+        ));
+    }
+}
@@ -0,0 +1,501 @@
+use crate::economy::Funds;
+use crate::error::{AppError, AppResult};
+use crate::prototype::commands::InsertPrototypeExt;
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use serde::Deserialize;
+use std::time::Duration;
+
+pub mod loader;
+pub mod plugin;
+
+/// Contents of a `*.wave.yaml` file, waves played one after the other.
+#[derive(TypeUuid, Deserialize, Debug, Clone, Default)]
+#[uuid = "6d0e2b1f-93c4-4a57-8b1e-2f7a9c3d5e60"]
+pub struct WaveSchedule {
+    pub waves: Vec<WaveDefinition>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct WaveDefinition {
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Seconds between the previous wave being cleared, or the start, and this wave starting.
+    #[serde(default)]
+    pub delay: f64,
+    pub groups: Vec<SpawnGroupDefinition>,
+    /// Paid when the wave is cleared.
+    #[serde(default)]
+    pub income: Funds,
+}
+
+/// Enemies of the same type leaving a spawn point one after the other.
+#[derive(Deserialize, Debug, Clone)]
+pub struct SpawnGroupDefinition {
+    /// Id of the enemy prototype.
+    pub enemy: String,
+    pub count: u32,
+    /// Seconds between two enemies.
+    #[serde(default)]
+    pub interval: f64,
+    /// Name of the [`SpawnPointComponent`] the enemies leave from.
+    pub spawn_point: String,
+    /// Seconds between the wave starting and the first enemy.
+    #[serde(default)]
+    pub delay: f64,
+    #[serde(default)]
+    pub modifiers: EnemyModifiers,
+}
+
+/// Multipliers applied to the stats of the spawned enemies.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct EnemyModifiers {
+    pub health: f32,
+    pub speed: f32,
+    pub reward: f32,
+}
+
+#[derive(Component, Debug, Clone)]
+pub struct SpawnPointComponent {
+    pub name: String,
+}
+
+#[derive(Component, Debug, Clone)]
+pub struct EnemyComponent {
+    pub enemy: String,
+    pub modifiers: EnemyModifiers,
+    /// Spawner and wave the enemy belongs to, the wave is cleared once all of its enemies are gone.
+    pub spawner: Entity,
+    pub wave: usize,
+}
+
+#[derive(Bundle)]
+pub struct EnemyBundle {
+    enemy: EnemyComponent,
+    #[bundle]
+    spatial: SpatialBundle,
+}
+
+/// Plays a [`WaveSchedule`], spawning enemies on the fixed timestep.
+#[derive(Component, Debug, Clone)]
+pub struct WaveSpawnerComponent {
+    pub schedule: Handle<WaveSchedule>,
+    wave: usize,
+    state: WaveState,
+    elapsed: Duration,
+    spawned: Vec<u32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WaveState {
+    /// Waiting for the delay of the current wave.
+    Waiting,
+    /// Spawning the enemies of the current wave.
+    Spawning,
+    /// Every enemy of the current wave was spawned, waiting for them to be gone.
+    Clearing,
+    /// Every wave was cleared.
+    Finished,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WaveStarted {
+    pub spawner: Entity,
+    pub wave: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WaveCleared {
+    pub spawner: Entity,
+    pub wave: usize,
+}
+
+/// What a spawner did during a step.
+#[derive(Debug, Clone, PartialEq)]
+pub enum WaveAction {
+    Started(usize),
+    Spawn { wave: usize, group: usize },
+    Cleared(usize),
+}
+
+impl Default for EnemyModifiers {
+    fn default() -> Self {
+        EnemyModifiers {
+            health: 1.0,
+            speed: 1.0,
+            reward: 1.0,
+        }
+    }
+}
+
+impl WaveSchedule {
+    pub fn parse(source: &str) -> AppResult<Self> {
+        let schedule: WaveSchedule = serde_yaml::from_str(source).map_err(AppError::WaveSyntax)?;
+        schedule.validate()?;
+        Ok(schedule)
+    }
+
+    /// Checks that every delay and interval is a duration.
+    fn validate(&self) -> AppResult<()> {
+        let is_duration = |seconds: f64| seconds.is_finite() && seconds >= 0.0;
+        for (i, wave) in self.waves.iter().enumerate() {
+            let invalid = |message: &str| -> AppResult<()> {
+                Err(AppError::InvalidWave {
+                    wave: i,
+                    message: message.to_string(),
+                })
+            };
+            if !is_duration(wave.delay) {
+                return invalid("`delay` must be finite and not negative");
+            }
+            for group in wave.groups.iter() {
+                if !is_duration(group.delay) {
+                    return invalid(&format!(
+                        "`delay` of the `{}` group must be finite and not negative",
+                        group.enemy
+                    ));
+                }
+                if !is_duration(group.interval) {
+                    return invalid(&format!(
+                        "`interval` of the `{}` group must be finite and not negative",
+                        group.enemy
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl SpawnGroupDefinition {
+    /// Time from the wave start to the spawn of the `i`th enemy.
+    pub fn spawn_time(&self, i: u32) -> Duration {
+        Duration::from_secs_f64(self.delay + self.interval * i as f64)
+    }
+}
+
+impl EnemyBundle {
+    pub fn new(enemy: EnemyComponent, transform: Transform) -> Self {
+        EnemyBundle {
+            enemy,
+            spatial: SpatialBundle::from_transform(transform),
+        }
+    }
+}
+
+impl WaveSpawnerComponent {
+    pub fn new(schedule: Handle<WaveSchedule>) -> Self {
+        WaveSpawnerComponent {
+            schedule,
+            wave: 0,
+            state: WaveState::Waiting,
+            elapsed: Duration::ZERO,
+            spawned: Vec::new(),
+        }
+    }
+
+    /// Index of the current wave, the number of waves when finished.
+    pub fn wave(&self) -> usize {
+        self.wave
+    }
+
+    pub fn state(&self) -> WaveState {
+        self.state
+    }
+
+    /// Time spent in the current state.
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// Advances the schedule by `delta`, `alive` being the number of enemies of the current wave
+    /// still around.
+    pub fn step(
+        &mut self,
+        schedule: &WaveSchedule,
+        delta: Duration,
+        alive: usize,
+    ) -> Vec<WaveAction> {
+        let mut actions = Vec::new();
+        self.elapsed += delta;
+        loop {
+            let wave = match schedule.waves.get(self.wave) {
+                Some(wave) => wave,
+                None => {
+                    self.state = WaveState::Finished;
+                    break;
+                }
+            };
+            match self.state {
+                WaveState::Waiting => {
+                    let delay = Duration::from_secs_f64(wave.delay);
+                    if self.elapsed < delay {
+                        break;
+                    }
+                    self.elapsed -= delay;
+                    self.state = WaveState::Spawning;
+                    self.spawned = vec![0; wave.groups.len()];
+                    actions.push(WaveAction::Started(self.wave));
+                }
+                WaveState::Spawning => {
+                    // Enemies due in the same step spawn in the order of their spawn times.
+                    let mut spawns = Vec::new();
+                    for (i, group) in wave.groups.iter().enumerate() {
+                        while self.spawned[i] < group.count
+                            && group.spawn_time(self.spawned[i]) <= self.elapsed
+                        {
+                            spawns.push((group.spawn_time(self.spawned[i]), i));
+                            self.spawned[i] += 1;
+                        }
+                    }
+                    spawns.sort_by_key(|(time, _)| *time);
+                    actions.extend(spawns.into_iter().map(|(_, group)| WaveAction::Spawn {
+                        wave: self.wave,
+                        group,
+                    }));
+                    let done = wave
+                        .groups
+                        .iter()
+                        .zip(self.spawned.iter())
+                        .all(|(group, spawned)| *spawned >= group.count);
+                    if !done {
+                        break;
+                    }
+                    self.state = WaveState::Clearing;
+                }
+                WaveState::Clearing => {
+                    // Enemies spawned during this step aren't counted in `alive` yet.
+                    if alive > 0
+                        || actions
+                            .iter()
+                            .any(|action| matches!(action, WaveAction::Spawn { .. }))
+                    {
+                        break;
+                    }
+                    actions.push(WaveAction::Cleared(self.wave));
+                    self.wave += 1;
+                    self.elapsed = Duration::ZERO;
+                    self.state = WaveState::Waiting;
+                }
+                WaveState::Finished => break,
+            }
+        }
+        actions
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn wave_spawner_system(
+    mut commands: Commands,
+    fixed_time: Res<FixedTime>,
+    schedules: Res<Assets<WaveSchedule>>,
+    mut spawner_query: Query<(Entity, &mut WaveSpawnerComponent)>,
+    spawn_point_query: Query<(&SpawnPointComponent, &GlobalTransform)>,
+    enemy_query: Query<&EnemyComponent>,
+    mut wave_started_events: EventWriter<WaveStarted>,
+    mut wave_cleared_events: EventWriter<WaveCleared>,
+) {
+    for (spawner_entity, mut spawner) in spawner_query.iter_mut() {
+        let schedule = match schedules.get(&spawner.schedule) {
+            Some(schedule) => schedule,
+            None => continue,
+        };
+        let alive = enemy_query
+            .iter()
+            .filter(|enemy| enemy.spawner == spawner_entity && enemy.wave == spawner.wave)
+            .count();
+        for action in spawner.step(schedule, fixed_time.period, alive) {
+            match action {
+                WaveAction::Started(wave) => wave_started_events.send(WaveStarted {
+                    spawner: spawner_entity,
+                    wave,
+                }),
+                WaveAction::Spawn { wave, group } => {
+                    let group = &schedule.waves[wave].groups[group];
+                    let transform = match spawn_point_query
+                        .iter()
+                        .find(|(spawn_point, _)| spawn_point.name == group.spawn_point)
+                    {
+                        Some((_, transform)) => transform.compute_transform(),
+                        None => {
+                            warn!("unknown spawn point `{}`", group.spawn_point);
+                            continue;
+                        }
+                    };
+                    commands
+                        .spawn(EnemyBundle::new(
+                            EnemyComponent {
+                                enemy: group.enemy.clone(),
+                                modifiers: group.modifiers,
+                                spawner: spawner_entity,
+                                wave,
+                            },
+                            transform,
+                        ))
+                        .insert_prototype(&group.enemy);
+                }
+                WaveAction::Cleared(wave) => wave_cleared_events.send(WaveCleared {
+                    spawner: spawner_entity,
+                    wave,
+                }),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wave::plugin::WavePlugin;
+    use bevy::ecs::event::ManualEventReader;
+
+    const SCHEDULE: &str = r#"
+waves:
+  - name: first
+    delay: 1.0
+    groups:
+      - enemy: slime
+        count: 3
+        interval: 0.5
+        spawn_point: north
+      - enemy: bat
+        count: 1
+        delay: 0.7
+        spawn_point: south
+        modifiers:
+          speed: 2.0
+  - delay: 2.0
+    groups:
+      - enemy: ogre
+        count: 1
+        spawn_point: north
+        modifiers:
+          health: 3.0
+"#;
+
+    #[test]
+    fn parse() {
+        let schedule = WaveSchedule::parse(SCHEDULE).unwrap();
+        assert_eq!(schedule.waves.len(), 2);
+        assert_eq!(schedule.waves[0].name.as_deref(), Some("first"));
+        let bat = &schedule.waves[0].groups[1];
+        assert_eq!(bat.spawn_time(0), Duration::from_millis(700));
+        assert_eq!(
+            bat.modifiers,
+            EnemyModifiers {
+                speed: 2.0,
+                ..Default::default()
+            }
+        );
+        assert_eq!(schedule.waves[1].groups[0].interval, 0.0);
+        assert!(matches!(
+            WaveSchedule::parse("waves: [{groups: [{enemy: slime}]}]"),
+            Err(AppError::WaveSyntax(_))
+        ));
+    }
+
+    #[test]
+    fn invalid_durations() {
+        let group = "{enemy: slime, count: 1, spawn_point: north";
+        for source in [
+            "waves: [{delay: -1, groups: []}]".to_string(),
+            "waves: [{delay: .nan, groups: []}]".to_string(),
+            "waves: [{delay: .inf, groups: []}]".to_string(),
+            format!("waves: [{{groups: [{}, delay: -0.5}}]}}]", group),
+            format!("waves: [{{groups: [{}, interval: .nan}}]}}]", group),
+        ] {
+            assert!(
+                matches!(
+                    WaveSchedule::parse(&source),
+                    Err(AppError::InvalidWave { .. })
+                ),
+                "{}",
+                source
+            );
+        }
+        assert!(matches!(
+            WaveSchedule::parse("waves: [{delay: 1, groups: []}, {delay: -1, groups: []}]"),
+            Err(AppError::InvalidWave { wave: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn schedule() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_plugin(AssetPlugin::default())
+            .add_plugin(WavePlugin)
+            .insert_resource(FixedTime::new(Duration::from_millis(100)));
+        app.world.spawn((
+            SpawnPointComponent {
+                name: "north".to_string(),
+            },
+            GlobalTransform::from_xyz(0.0, 10.0, 0.0),
+        ));
+        app.world.spawn((
+            SpawnPointComponent {
+                name: "south".to_string(),
+            },
+            GlobalTransform::from_xyz(0.0, -10.0, 0.0),
+        ));
+        let schedule = app
+            .world
+            .resource_mut::<Assets<WaveSchedule>>()
+            .add(WaveSchedule::parse(SCHEDULE).unwrap());
+        let spawner_entity = app.world.spawn(WaveSpawnerComponent::new(schedule)).id();
+
+        let mut started_reader = ManualEventReader::<WaveStarted>::default();
+        let mut cleared_reader = ManualEventReader::<WaveCleared>::default();
+        let mut enemy_query = app.world.query::<(Entity, &EnemyComponent, &Transform)>();
+        // Ticks of the fixed timestep at which enemies spawned and waves started or were cleared.
+        let mut log = Vec::new();
+        for tick in 1..=60 {
+            app.world.run_schedule(CoreSchedule::FixedUpdate);
+            for event in started_reader.iter(app.world.resource::<Events<WaveStarted>>()) {
+                assert_eq!(event.spawner, spawner_entity);
+                log.push((tick, format!("started {}", event.wave)));
+            }
+            for event in cleared_reader.iter(app.world.resource::<Events<WaveCleared>>()) {
+                log.push((tick, format!("cleared {}", event.wave)));
+            }
+            let mut enemies: Vec<(Entity, String, f32)> = enemy_query
+                .iter(&app.world)
+                .map(|(entity, enemy, transform)| {
+                    (entity, enemy.enemy.clone(), transform.translation.y)
+                })
+                .collect();
+            enemies.sort_by_key(|(entity, _, _)| *entity);
+            for (entity, enemy, y) in enemies {
+                log.push((tick, format!("{} at {}", enemy, y)));
+                // Enemies of the first wave die right away, the ogre lives for a second.
+                if enemy != "ogre" || tick >= 55 {
+                    app.world.despawn(entity);
+                }
+            }
+        }
+
+        let log: Vec<(i32, &str)> = log.iter().map(|(tick, s)| (*tick, s.as_str())).collect();
+        let expected: Vec<(i32, &str)> = [
+            (10, "started 0"),
+            (10, "slime at 10"),
+            (15, "slime at 10"),
+            (17, "bat at -10"),
+            (20, "slime at 10"),
+            (21, "cleared 0"),
+            (41, "started 1"),
+        ]
+        .into_iter()
+        .chain((41..=55).map(|tick| (tick, "ogre at 10")))
+        .chain([(56, "cleared 1")])
+        .collect();
+        assert_eq!(log, expected);
+
+        let spawner = app
+            .world
+            .get::<WaveSpawnerComponent>(spawner_entity)
+            .unwrap();
+        assert_eq!(spawner.state(), WaveState::Finished);
+        assert_eq!(spawner.wave(), 2);
+    }
+}
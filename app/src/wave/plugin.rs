@@ -0,0 +1,15 @@
+use crate::wave::loader::WaveScheduleLoader;
+use crate::wave::{wave_spawner_system, WaveCleared, WaveSchedule, WaveStarted};
+use bevy::prelude::*;
+
+pub struct WavePlugin;
+
+impl Plugin for WavePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<WaveSchedule>()
+            .init_asset_loader::<WaveScheduleLoader>()
+            .add_event::<WaveStarted>()
+            .add_event::<WaveCleared>()
+            .add_system(wave_spawner_system.in_schedule(CoreSchedule::FixedUpdate));
+    }
+}